@@ -7,8 +7,9 @@ use atty::Stream;
 use clap::Parser;
 use eframe::egui;
 use egui::Color32;
-use egui_plot::{Bar, BarChart, CoordinatesFormatter, Corner, Legend, Plot, Points, VLine};
-use hist3::data::InputSource;
+use egui_plot::{Bar, BarChart, CoordinatesFormatter, Corner, Legend, Line, Plot, Points, VLine};
+use hist3::data::{lossy_lines, BandwidthRule, InputSource, Kde, Kernel, Normalization, Scale};
+use hist3::render;
 use std::fs::File;
 use std::io::{self, BufRead};
 use std::path::Path;
@@ -23,6 +24,7 @@ mod colors {
     pub const PERCENTILE_50_COLOR: Color32 = Color32::from_rgb(77, 255, 77);
     pub const PERCENTILE_75_COLOR: Color32 = Color32::from_rgb(255, 77, 77);
     pub const DEFAULT_BAR_COLOR: Color32 = Color32::from_rgb(75, 75, 75);
+    pub const KDE_COLOR: Color32 = Color32::from_rgb(200, 80, 200);
 }
 
 #[derive(clap::Parser, Debug)]
@@ -91,54 +93,54 @@ fn process_reader<R: BufRead>(reader: R, data_ref: &Arc<Mutex<Vec<Vec<f64>>>>) {
     let mut batch = Vec::new();
     const BATCH_SIZE: usize = 1000;
 
-    for line in reader.lines() {
-        if let Ok(line) = line {
-            // Extract all numeric patterns that could be valid numbers
-            let mut values = Vec::new();
-            let mut start_idx = None;
-
-            // Scan the line character by character to identify number patterns
-            for (i, c) in line.char_indices() {
-                let is_num_char =
-                    c.is_numeric() || c == '.' || c == '-' || c == '+' || c == 'e' || c == 'E';
-
-                if is_num_char && start_idx.is_none() {
-                    // Start of a new number
-                    start_idx = Some(i);
-                } else if !is_num_char && start_idx.is_some() {
-                    // End of a number - extract the substring
-                    let start = start_idx.unwrap();
-                    values.push(&line[start..i]);
-                    start_idx = None;
-                }
-            }
-
-            // Handle case where the line ends with a number
-            if let Some(start) = start_idx {
-                values.push(&line[start..]);
+    for line in lossy_lines(reader).map_while(Result::ok) {
+        // Extract all numeric patterns that could be valid numbers
+        let mut values = Vec::new();
+        let mut start_idx = None;
+
+        // Scan the line character by character to identify number patterns
+        for (i, c) in line.char_indices() {
+            let is_num_char =
+                c.is_numeric() || c == '.' || c == '-' || c == '+' || c == 'e' || c == 'E';
+
+            if is_num_char && start_idx.is_none() {
+                // Start of a new number
+                start_idx = Some(i);
+            } else if !is_num_char && start_idx.is_some() {
+                // End of a number - extract the substring
+                let start = start_idx.unwrap();
+                values.push(&line[start..i]);
+                start_idx = None;
             }
+        }
 
-            // Parse all extracted strings into numbers, filtering out failures
-            let floats = values
-                .into_iter()
-                .filter_map(|s| f64::from_str(s).ok())
-                .collect::<Vec<_>>();
+        // Handle case where the line ends with a number
+        if let Some(start) = start_idx {
+            values.push(&line[start..]);
+        }
 
-            // Check if the number of values matches the size of the first line
-            // Also ensure we actually parsed some numbers
-            if !floats.is_empty() {
-                if first_line_size.is_none() {
+        // Parse all extracted strings into numbers, filtering out failures
+        let floats = values
+            .into_iter()
+            .filter_map(|s| f64::from_str(s).ok())
+            .collect::<Vec<_>>();
+
+        // Check if the number of values matches the size of the first line
+        // Also ensure we actually parsed some numbers
+        if !floats.is_empty() {
+            match first_line_size {
+                None => {
                     first_line_size = Some(floats.len());
                     batch.push(floats);
-                } else if floats.len() == first_line_size.unwrap() {
-                    batch.push(floats);
                 }
+                Some(size) if floats.len() == size => batch.push(floats),
+                Some(_) => {}
+            }
 
-                // Only lock the mutex when we have a full batch
-                if batch.len() >= BATCH_SIZE {
-                    let mut data = data_ref.lock().unwrap();
-                    data.extend(batch.drain(..));
-                }
+            // Only lock the mutex when we have a full batch
+            if batch.len() >= BATCH_SIZE {
+                let mut data = data_ref.lock().unwrap();
+                data.extend(batch.drain(..));
             }
         }
     }
@@ -171,8 +173,14 @@ struct ScatterSettings {
 struct HistogramSettings {
     column: usize,
     bins: usize,
+    scale: Scale,
+    normalization: Normalization,
+    kde: bool,
+    kernel: Kernel,
+    bandwidth_rule: BandwidthRule,
+    bandwidth_factor: f64, // multiplies the bandwidth given by the rule
     cached_stats: Option<(f64, f64, f64)>, // mean, variance, stddev
-    last_data_version: usize,              // To detect when recalculation is needed
+    last_data_version: usize, // To detect when recalculation is needed
 }
 
 impl Default for ScatterSettings {
@@ -191,6 +199,12 @@ impl Default for HistogramSettings {
         Self {
             column: 0,
             bins: 20,
+            scale: Scale::Linear,
+            normalization: Normalization::Count,
+            kde: false,
+            kernel: Kernel::Gaussian,
+            bandwidth_rule: BandwidthRule::Silverman,
+            bandwidth_factor: 1.0,
             cached_stats: None,
             last_data_version: 0,
         }
@@ -260,7 +274,7 @@ impl eframe::App for MainApp {
             }
 
             // Create a unique ID for each window
-            let viewport_id = egui::ViewportId::from_hash_of(&format!("scatter_plot_{}", i));
+            let viewport_id = egui::ViewportId::from_hash_of(format!("scatter_plot_{}", i));
             let window_title = format!("Scatter Plot {}", i + 1);
 
             // Clone the shared references for the window
@@ -334,7 +348,7 @@ impl eframe::App for MainApp {
             }
 
             // Create a unique ID for each window
-            let viewport_id = egui::ViewportId::from_hash_of(&format!("histogram_{}", i));
+            let viewport_id = egui::ViewportId::from_hash_of(format!("histogram_{}", i));
             let window_title = format!("Histogram {}", i + 1);
 
             // Clone the shared references for the window
@@ -387,7 +401,7 @@ impl eframe::App for MainApp {
                                     Self::show_histogram(
                                         ui,
                                         &filtered_data_refs,
-                                        &filters_ref,
+                                        filters_ref,
                                         &mut settings,
                                         _data_version,
                                     );
@@ -635,12 +649,8 @@ impl MainApp {
             .show_axes(true)
             .coordinates_formatter(Corner::LeftBottom, CoordinatesFormatter::default())
             .min_size(egui::vec2(500.0, 400.0)) // Set minimum plot size
-            .label_formatter(move |name, value| {
-                if !name.is_empty() {
-                    format!("{}: {:.2}, {}: {:.2}", x_name, value.x, y_name, value.y)
-                } else {
-                    format!("{}: {:.2}, {}: {:.2}", x_name, value.x, y_name, value.y)
-                }
+            .label_formatter(move |_name, value| {
+                format!("{}: {:.2}, {}: {:.2}", x_name, value.x, y_name, value.y)
             });
 
         plot.show(ui, |plot_ui| {
@@ -785,7 +795,7 @@ impl MainApp {
     fn show_histogram(
         ui: &mut egui::Ui,
         data: &[&Vec<f64>],
-        filters: &Vec<(f64, f64, f64, f64)>,
+        filters: &[(f64, f64, f64, f64)],
         settings: &mut HistogramSettings,
         _data_version: usize,
     ) {
@@ -824,6 +834,99 @@ impl MainApp {
             ui.label("Bins:");
             ui.add_space(2.0);
             ui.add(egui::Slider::new(&mut settings.bins, 5..=100).text(""));
+
+            ui.add_space(20.0);
+
+            // Linear or log-spaced bins
+            ui.label("Scale:");
+            ui.add_space(2.0);
+
+            let scale_items = [
+                (Scale::Linear, "Linear"),
+                (Scale::Log(2.0), "Log2"),
+                (Scale::Log(10.0), "Log10"),
+                (Scale::Log(std::f64::consts::E), "Ln"),
+            ];
+            let selected = scale_items
+                .iter()
+                .find(|(scale, _)| *scale == settings.scale)
+                .map_or("Linear", |(_, name)| name);
+            egui::ComboBox::new("histogram_scale_combo", "")
+                .selected_text(selected)
+                .width(80.0)
+                .show_ui(ui, |ui| {
+                    for (scale, name) in scale_items {
+                        ui.selectable_value(&mut settings.scale, scale, name);
+                    }
+                });
+
+            ui.add_space(20.0);
+
+            // What the bar heights stand for
+            ui.label("Heights:");
+            ui.add_space(2.0);
+            egui::ComboBox::new("histogram_normalization_combo", "")
+                .selected_text(settings.normalization.name())
+                .width(90.0)
+                .show_ui(ui, |ui| {
+                    for normalization in [
+                        Normalization::Count,
+                        Normalization::Fraction,
+                        Normalization::Percent,
+                        Normalization::Density,
+                    ] {
+                        ui.selectable_value(
+                            &mut settings.normalization,
+                            normalization,
+                            normalization.name(),
+                        );
+                    }
+                });
+        });
+
+        ui.horizontal(|ui| {
+            // Kernel density estimate over the bars
+            ui.checkbox(&mut settings.kde, "KDE");
+            ui.add_space(20.0);
+
+            ui.add_enabled_ui(settings.kde, |ui| {
+                ui.label("Kernel:");
+                ui.add_space(2.0);
+                egui::ComboBox::new("histogram_kernel_combo", "")
+                    .selected_text(format!("{:?}", settings.kernel))
+                    .width(110.0)
+                    .show_ui(ui, |ui| {
+                        for kernel in [Kernel::Gaussian, Kernel::Epanechnikov] {
+                            ui.selectable_value(
+                                &mut settings.kernel,
+                                kernel,
+                                format!("{:?}", kernel),
+                            );
+                        }
+                    });
+
+                ui.add_space(20.0);
+
+                ui.label("Bandwidth:");
+                ui.add_space(2.0);
+                egui::ComboBox::new("histogram_bandwidth_combo", "")
+                    .selected_text(format!("{:?}", settings.bandwidth_rule))
+                    .width(90.0)
+                    .show_ui(ui, |ui| {
+                        for rule in [BandwidthRule::Silverman, BandwidthRule::Scott] {
+                            ui.selectable_value(
+                                &mut settings.bandwidth_rule,
+                                rule,
+                                format!("{:?}", rule),
+                            );
+                        }
+                    });
+                ui.add(
+                    egui::Slider::new(&mut settings.bandwidth_factor, 0.1..=10.0)
+                        .logarithmic(true)
+                        .text("×"),
+                );
+            });
         });
 
         ui.add_space(10.0);
@@ -844,16 +947,48 @@ impl MainApp {
             return;
         }
 
-        // Calculate histogram bins
-        let min_value = filters[settings.column].2;
+        // Calculate histogram bins. On a log scale the bins are equally spaced in log(x), which
+        // is also the x coordinate they are drawn at, and non-positive values are left out.
+        let scale = settings.scale;
+        let non_positive = if scale.is_log() {
+            column_data.iter().filter(|&&x| x <= 0.0).count()
+        } else {
+            0
+        };
+        let mut min_value = filters[settings.column].2;
         let max_value = filters[settings.column].3;
-        let range = max_value - min_value;
+        if scale.is_log() {
+            let min_positive = column_data
+                .iter()
+                .copied()
+                .filter(|&x| x > 0.0)
+                .fold(f64::INFINITY, f64::min);
+            min_value = min_value.max(min_positive);
+        }
+        if non_positive > 0 {
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                format!(
+                    "{} zero or negative values not shown on the log scale",
+                    non_positive
+                ),
+            );
+        }
+        if !(min_value.is_finite() && max_value.is_finite()) {
+            ui.label("No positive values to show on a log scale");
+            return;
+        }
+        let min_t = scale.forward(min_value);
+        let range = scale.forward(max_value) - min_t;
         let bin_width = range / settings.bins as f64;
 
         // Count values in each bin
         let mut bin_counts = vec![0; settings.bins];
         for &value in &column_data {
-            let bin_index = ((value - min_value) / bin_width).floor() as usize;
+            if scale.is_log() && value <= 0.0 {
+                continue;
+            }
+            let bin_index = ((scale.forward(value) - min_t) / bin_width).floor() as usize;
             let clamped_index = bin_index.min(settings.bins - 1);
             bin_counts[clamped_index] += 1;
         }
@@ -880,61 +1015,105 @@ impl MainApp {
         };
 
         // Create bar chart data
+        let normalization = settings.normalization;
+        let binned = bin_counts.iter().sum::<usize>() as f64;
         let bars: Vec<Bar> = bin_counts
             .iter()
             .enumerate()
             .map(|(i, &count)| {
-                let bin_start = min_value + i as f64 * bin_width;
+                let bin_start = min_t + i as f64 * bin_width;
                 let bin_center = bin_start + bin_width / 2.0;
 
-                Bar::new(bin_center, count as f64)
-                    .width(bin_width * 0.95)
-                    .fill(colors::DEFAULT_BAR_COLOR)
-                    .name(format!("{:.2} - {:.2}", bin_start, bin_start + bin_width))
+                Bar::new(
+                    bin_center,
+                    normalization.height(count as f64, binned, bin_width),
+                )
+                .width(bin_width * 0.95)
+                .fill(colors::DEFAULT_BAR_COLOR)
+                .name(format!(
+                    "{:.2} - {:.2}",
+                    scale.inverse(bin_start),
+                    scale.inverse(bin_start + bin_width)
+                ))
             })
             .collect();
 
         let chart = BarChart::new(bars);
 
+        // Density curve in the same coordinates as the bars, scaled so its area matches theirs
+        let kde_line = if settings.kde {
+            let values = column_data
+                .iter()
+                .filter(|&&x| !scale.is_log() || x > 0.0)
+                .map(|&x| scale.forward(x))
+                .collect::<Vec<_>>();
+            Kde::from_values(&values).map(|kde| {
+                let bandwidth = kde.bandwidth(settings.bandwidth_rule) * settings.bandwidth_factor;
+                let height = normalization.height(kde.total(), binned, bin_width);
+                let curve = kde
+                    .curve(settings.kernel, bandwidth, 400)
+                    .into_iter()
+                    .map(|[x, density]| [x, density * bin_width * height])
+                    .collect::<Vec<_>>();
+                Line::new(curve)
+                    .color(colors::KDE_COLOR)
+                    .width(2.0)
+                    .name(format!("KDE (h = {:.4})", bandwidth))
+            })
+        } else {
+            None
+        };
+
         // Create and show the plot
         let column_name = format!("Column {}", settings.column);
 
-        Plot::new(format!("Histogram of {}", column_name))
+        let mut plot = Plot::new(format!("Histogram of {}", column_name))
             .legend(Legend::default())
             .show_grid(true)
             .show_axes(true)
             .allow_boxed_zoom(true)
             .allow_drag(true)
             .x_axis_label(column_name)
-            .y_axis_label("Count")
-            .show(ui, |plot_ui| {
-                plot_ui.bar_chart(chart);
-
-                // Show percentile lines
-                if let Some(x) = p25 {
-                    plot_ui.vline(
-                        VLine::new(x)
-                            .color(colors::PERCENTILE_25_COLOR)
-                            .name(format!("25th percentile: {:.4}", x)),
-                    );
-                }
+            .y_axis_label(normalization.name());
+        if scale.is_log() {
+            plot = plot.x_axis_formatter(move |mark, _range| {
+                render::tick_label(scale, mark.value, mark.step_size)
+            });
+        }
+        // Percentiles that cannot be drawn on a log axis are dropped
+        let to_plot = |x: Option<f64>| x.filter(|&x| !scale.is_log() || x > 0.0);
+        let (p25, p50, p75) = (to_plot(p25), to_plot(p50), to_plot(p75));
+        plot.show(ui, |plot_ui| {
+            plot_ui.bar_chart(chart);
+            if let Some(line) = kde_line {
+                plot_ui.line(line);
+            }
 
-                if let Some(x) = p50 {
-                    plot_ui.vline(
-                        VLine::new(x)
-                            .color(colors::PERCENTILE_50_COLOR)
-                            .name(format!("50th percentile: {:.4}", x)),
-                    );
-                }
+            // Show percentile lines
+            if let Some(x) = p25 {
+                plot_ui.vline(
+                    VLine::new(scale.forward(x))
+                        .color(colors::PERCENTILE_25_COLOR)
+                        .name(format!("25th percentile: {:.4}", x)),
+                );
+            }
 
-                if let Some(x) = p75 {
-                    plot_ui.vline(
-                        VLine::new(x)
-                            .color(colors::PERCENTILE_75_COLOR)
-                            .name(format!("75th percentile: {:.4}", x)),
-                    );
-                }
-            });
+            if let Some(x) = p50 {
+                plot_ui.vline(
+                    VLine::new(scale.forward(x))
+                        .color(colors::PERCENTILE_50_COLOR)
+                        .name(format!("50th percentile: {:.4}", x)),
+                );
+            }
+
+            if let Some(x) = p75 {
+                plot_ui.vline(
+                    VLine::new(scale.forward(x))
+                        .color(colors::PERCENTILE_75_COLOR)
+                        .name(format!("75th percentile: {:.4}", x)),
+                );
+            }
+        });
 
         // // // Show statistics
         // // ui.separator();
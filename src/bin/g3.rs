@@ -3,11 +3,12 @@ use clap::Parser;
 use core::f64;
 use eframe::egui;
 use egui::{Color32, Pos2, Rect, Stroke, Vec2};
+use hist3::data::lossy_lines;
 use petgraph::algo::kosaraju_scc;
 use petgraph::graph::{Graph, NodeIndex};
 use petgraph::Undirected;
 use std::collections::{HashMap, HashSet};
-use std::io::{self, BufRead};
+use std::io;
 use std::path::Path;
 use std::sync::{Arc, Mutex};
 use std::{fs::File, thread};
@@ -115,6 +116,7 @@ enum InteractionMode {
     Select,
 }
 
+#[derive(Default)]
 struct SelectionState {
     selected_nodes: HashSet<NodeIndex>,
     preview_nodes: HashSet<NodeIndex>, // New: tracks nodes currently in selection rectangle
@@ -122,17 +124,6 @@ struct SelectionState {
     drag_end: Option<Pos2>,
 }
 
-impl Default for SelectionState {
-    fn default() -> Self {
-        Self {
-            selected_nodes: HashSet::new(),
-            preview_nodes: HashSet::new(),
-            drag_start: None,
-            drag_end: None,
-        }
-    }
-}
-
 struct GraphVisualizerApp {
     graph_data: Arc<Mutex<Graph<String, f64, Undirected>>>,
     weight_histogram: Arc<Mutex<Histogram>>,
@@ -206,7 +197,7 @@ impl eframe::App for GraphVisualizerApp {
 
         if !self.initialized {
             let graph = self.graph_data.lock().unwrap();
-            if !graph.node_indices().next().is_none() {
+            if graph.node_indices().next().is_some() {
                 drop(graph);
                 self.reset_layout(ctx.available_rect().size());
                 self.initialized = true;
@@ -450,8 +441,6 @@ impl eframe::App for GraphVisualizerApp {
                                     if !self.selection_state.selected_nodes.remove(idx) {
                                         self.selection_state.selected_nodes.insert(*idx);
                                     }
-                                } else if modifiers.shift {
-                                    self.selection_state.selected_nodes.insert(*idx);
                                 } else {
                                     self.selection_state.selected_nodes.insert(*idx);
                                 }
@@ -478,7 +467,7 @@ impl eframe::App for GraphVisualizerApp {
                                 // Move all selected nodes by the same delta
                                 for &selected_idx in &self.selection_state.selected_nodes {
                                     if let Some(selected_pos) = positions.get_mut(&selected_idx) {
-                                        *selected_pos = *selected_pos + delta;
+                                        *selected_pos += delta;
                                     }
                                     self.velocities.insert(selected_idx, Vec2::ZERO);
                                 }
@@ -895,7 +884,7 @@ impl GraphVisualizerApp {
                             || graph
                                 .find_edge(node1, neighbor)
                                 .and_then(|edge| graph.edge_weight(edge))
-                                .map_or(false, |&weight| weight >= self.min_weight);
+                                .is_some_and(|&weight| weight >= self.min_weight);
 
                         if edge_weight_valid {
                             if let (Some(&pos1), Some(&pos2)) =
@@ -1149,9 +1138,8 @@ fn main() -> Result<(), eframe::Error> {
         if input == "stdin" {
             let stdin = io::stdin();
             let reader = stdin.lock();
-            let content = reader
-                .lines()
-                .filter_map(Result::ok)
+            let content = lossy_lines(reader)
+                .map_while(Result::ok)
                 .collect::<Vec<String>>()
                 .join("\n");
             parse_input(
@@ -1167,9 +1155,8 @@ fn main() -> Result<(), eframe::Error> {
             }
             let file = File::open(input).unwrap();
             let reader = io::BufReader::new(file);
-            let content = reader
-                .lines()
-                .filter_map(Result::ok)
+            let content = lossy_lines(reader)
+                .map_while(Result::ok)
                 .collect::<Vec<String>>()
                 .join("\n");
             parse_input(
@@ -5,12 +5,23 @@ extern crate egui_plot;
 use atty::Stream;
 use clap::Parser;
 use eframe::egui;
-use egui_plot::{Bar, BarChart, Legend, Plot};
+use egui_plot::{Bar, BarChart, Legend, Line, LineStyle, Plot};
 use hist3::data;
-use hist3::data::InputSource;
+use hist3::data::{
+    BandwidthRule, BinRule, CategoryOrder, Column, Family, FieldSelector, Fit, HistogramOptions,
+    InputLine, InputSource, Kde, Kernel, LiveValues, Normalization, Scale, Series, Window,
+};
+use hist3::render::{self, BarLayout, Figure};
+use hist3::report;
 use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
 use std::iter::FromIterator;
-use std::path::Path;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
 mod colors {
     use eframe::egui::Color32;
@@ -20,279 +31,2014 @@ mod colors {
     pub const PERCENTILE_50_COLOR: Color32 = Color32::from_rgb(77, 255, 77);
     pub const PERCENTILE_75_COLOR: Color32 = Color32::from_rgb(255, 77, 77);
     pub const DEFAULT_BAR_COLOR: Color32 = Color32::from_rgb(75, 75, 75);
+    pub const KDE_COLOR: Color32 = Color32::from_rgb(120, 60, 160);
+
+    /// Colours of the fitted densities, in the order of `Family::ALL`.
+    pub const FIT_COLORS: [Color32; 5] = [
+        Color32::from_rgb(0, 150, 136),
+        Color32::from_rgb(233, 30, 99),
+        Color32::from_rgb(121, 85, 72),
+        Color32::from_rgb(63, 81, 181),
+        Color32::from_rgb(205, 220, 57),
+    ];
+
+    /// Colours of the quantile lines in order, repeated if there are more quantiles.
+    pub const PERCENTILE_COLORS: [Color32; 6] = [
+        PERCENTILE_25_COLOR,
+        PERCENTILE_50_COLOR,
+        PERCENTILE_75_COLOR,
+        Color32::from_rgb(230, 170, 40),
+        Color32::from_rgb(40, 190, 200),
+        Color32::from_rgb(200, 80, 200),
+    ];
+
+    /// Bar colours of the series when several are compared.
+    pub const SERIES_COLORS: [Color32; 6] = [
+        Color32::from_rgb(31, 119, 180),
+        Color32::from_rgb(255, 127, 14),
+        Color32::from_rgb(44, 160, 44),
+        Color32::from_rgb(214, 39, 40),
+        Color32::from_rgb(148, 103, 189),
+        Color32::from_rgb(140, 86, 75),
+    ];
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum BinRuleArg {
+    /// Split the range into --bins equal-width bins
+    Count,
+    /// ceil(log2(n)) + 1 bins
+    Sturges,
+    /// Bin width from the standard deviation
+    Scott,
+    /// Bin width from the interquartile range
+    #[value(alias = "fd")]
+    FreedmanDiaconis,
+    /// ceil(sqrt(n)) bins
+    Sqrt,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum LogBase {
+    #[value(name = "2")]
+    Two,
+    #[value(name = "10")]
+    Ten,
+    #[value(name = "e")]
+    E,
+}
+
+impl LogBase {
+    fn base(self) -> f64 {
+        match self {
+            LogBase::Two => 2.0,
+            LogBase::Ten => 10.0,
+            LogBase::E => std::f64::consts::E,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum Layout {
+    /// Translucent bars on top of each other
+    Overlay,
+    /// Bars side by side within each bin
+    Grouped,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum KernelArg {
+    Gaussian,
+    Epanechnikov,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum BandwidthArg {
+    /// From the smaller of the standard deviation and the interquartile range
+    Silverman,
+    /// From the standard deviation
+    Scott,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum SortArg {
+    /// By count
+    Count,
+    /// Alphabetically by label
+    Label,
+    /// By label, with numbers in labels compared by value, e.g. item2 before item10
+    Natural,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum NormalizeArg {
+    /// Number of values
+    Count,
+    /// Fraction of the values, adding up to 1
+    Fraction,
+    /// Percentage of the values, adding up to 100
+    Percent,
+    /// Fraction of the values per unit of the x axis, so that the bars have an area of 1
+    Density,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum FitArg {
+    Normal,
+    #[value(alias = "lognormal")]
+    LogNormal,
+    Exponential,
+    Gamma,
+    Weibull,
+}
+
+/// What the window draws.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum View {
+    /// The histogram
+    Bars,
+    /// The empirical cumulative distribution of the values
+    Cdf,
+    /// The fraction of the values above each value, on a log scale, to look at the upper tail
+    Survival,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum Format {
+    Json,
+    Csv,
 }
 
 #[derive(clap::Parser, Debug)]
 #[command(author, version, about)]
 struct Args {
-    /// Input file
-    input: Option<String>,
+    /// Input files. Several files are drawn as separate series on the same bins
+    input: Vec<String>,
 
     /// Categorical input
     #[arg(long, short)]
     categorical: bool,
 
+    /// Keep only the N most frequent categories and count the rest in an "(other)" bar
+    #[arg(long, requires = "categorical")]
+    top: Option<NonZeroUsize>,
+
+    /// Order of the categorical bars
+    #[arg(long, value_enum, default_value_t = SortArg::Count, requires = "categorical")]
+    sort: SortArg,
+
+    /// Order the categorical bars from largest to smallest
+    #[arg(long, requires = "categorical")]
+    descending: bool,
+
+    /// Draw the categorical bars horizontally, from the top down, with their labels on the left
+    #[arg(long, requires = "categorical")]
+    horizontal: bool,
+
     /// Number of bins
-    #[arg(long, short, default_value_t = 20)]
+    #[arg(long, short, default_value_t = 20, conflicts_with_all = ["bin_width", "edges"])]
     bins: usize,
 
+    /// How to choose the bins
+    #[arg(long, value_enum, default_value_t = BinRuleArg::Count)]
+    bin_rule: BinRuleArg,
+
+    /// Width of each bin, with edges at multiples of the width
+    #[arg(long, conflicts_with_all = ["bin_rule", "edges"])]
+    bin_width: Option<f64>,
+
+    /// Explicit comma-separated bin edges, e.g. 0,1,5,10,100
+    #[arg(
+        long,
+        value_delimiter = ',',
+        allow_negative_numbers = true,
+        conflicts_with = "bin_rule"
+    )]
+    edges: Vec<f64>,
+
+    /// Space the bins evenly in the logarithm to this base and use a log x axis. Zero and
+    /// negative values are counted but not shown
+    #[arg(long, value_enum, conflicts_with = "categorical")]
+    log: Option<LogBase>,
+
+    /// Histogram one column of delimited input, by 1-based position or by header name
+    #[arg(long)]
+    column: Option<Column>,
+
+    /// Field delimiter for --column, e.g. ',' or 'tab' [default: tab if the first line has one,
+    /// otherwise ',']
+    #[arg(long, short, requires = "column", value_parser = data::parse_delimiter)]
+    delimiter: Option<char>,
+
+    /// Skip a header line (implied when --column is a name)
+    #[arg(long, requires = "column")]
+    header: bool,
+
+    /// Split the values of --column into series by the key in this column, by 1-based position
+    /// or by header name
+    #[arg(long, requires = "column")]
+    group_by: Option<Column>,
+
+    /// How the bars of several series share a bin
+    #[arg(long, value_enum, default_value_t = Layout::Overlay)]
+    layout: Layout,
+
+    /// Draw a kernel density estimate over the bars. It can also be turned on in the window
+    #[arg(long, conflicts_with_all = ["categorical", "output", "text", "format"])]
+    kde: bool,
+
+    /// Kernel of the density estimate
+    #[arg(long, value_enum, default_value_t = KernelArg::Gaussian)]
+    kernel: KernelArg,
+
+    /// How the bandwidth of the density estimate is chosen
+    #[arg(long, value_enum, default_value_t = BandwidthArg::Silverman)]
+    bandwidth: BandwidthArg,
+
+    /// What the bar heights stand for. Series of different sizes are easier to compare as
+    /// fractions, percentages or densities. It can also be changed in the window
+    #[arg(long, value_enum, default_value_t = NormalizeArg::Count, conflicts_with = "format")]
+    normalize: NormalizeArg,
+
+    /// Comma-separated distributions to fit to the values and draw over the bars. All of them
+    /// are fitted, with their goodness of fit, in the side panel of the window
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        conflicts_with_all = ["categorical", "streaming", "output", "text", "format"]
+    )]
+    fit: Vec<FitArg>,
+
+    /// What the window shows first. It can be switched in the window
+    #[arg(long, value_enum, default_value_t = View::Bars, conflicts_with_all = ["categorical", "streaming"])]
+    view: View,
+
     /// Title
     #[arg(long, short, default_value = "Histogram")]
     title: String,
+
+    /// Open the window straight away and re-bin the input as it arrives, e.g. from `tail -f`.
+    /// Without --window, the values are counted as they arrive, like with --streaming
+    #[arg(long, conflicts_with_all = ["streaming", "output", "text", "format"])]
+    live: bool,
+
+    /// Only bin the latest values of a live input: a number of values, e.g. 10000, or a
+    /// duration, e.g. 60s or 5m. Implies --live
+    #[arg(long, conflicts_with_all = ["streaming", "output", "text", "format"])]
+    window: Option<Window>,
+
+    /// Bin the input as it is read, in bounded memory, with approximate percentiles. With
+    /// --categorical, count the most frequent categories approximately once there are too many
+    #[arg(long)]
+    streaming: bool,
+
+    /// Write the histogram to an .svg or .png file instead of opening a window
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+
+    /// Print the histogram as text bars instead of opening a window. This is the default when
+    /// there is no display
+    #[arg(long, visible_alias = "tui", conflicts_with = "output")]
+    text: bool,
+
+    /// Print the bins, cumulative counts, percentiles, total and range instead of drawing them
+    #[arg(long, value_enum, conflicts_with_all = ["output", "text"])]
+    format: Option<Format>,
+
+    /// Comma-separated percentiles to compute and draw, e.g. 1,5,50,95,99,99.9
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_parser = parse_percent,
+        default_values_t = [25.0, 50.0, 75.0]
+    )]
+    quantiles: Vec<f64>,
+
+    /// Fail on the first value that is not a finite number instead of skipping it
+    #[arg(long, conflicts_with = "categorical")]
+    strict: bool,
+
+    /// Lower edge of a fixed histogram range, split into --bins bins
+    #[arg(long, requires = "max", allow_negative_numbers = true)]
+    min: Option<f64>,
+
+    /// Upper edge of a fixed histogram range
+    #[arg(long, requires = "min", allow_negative_numbers = true)]
+    max: Option<f64>,
+
+    /// Write the input lines in the selected bars to stdout, or to this file, when the window is
+    /// closed. They can also be written at any time with Enter or the "Write selection" button
+    #[arg(
+        long,
+        value_name = "FILE",
+        num_args = 0..=1,
+        default_missing_value = "-",
+        conflicts_with_all = ["streaming", "output", "text", "format"]
+    )]
+    emit_selection: Option<PathBuf>,
 }
 
 fn main() -> Result<(), eframe::Error> {
     let args = Args::parse();
     let title = args.title.clone();
+    let live = args.live || args.window.is_some();
+    let opens_window =
+        live || (args.output.is_none() && args.format.is_none() && !args.text && has_display());
+    // Without a display, the histogram is printed as text instead, which has none of these.
+    if !live && args.output.is_none() && args.format.is_none() && !args.text && !has_display() {
+        let window_only = [
+            ("--fit", !args.fit.is_empty()),
+            ("--kde", args.kde),
+            ("--emit-selection", args.emit_selection.is_some()),
+        ];
+        for (option, given) in window_only {
+            if given {
+                eprintln!("hist3: {} needs a display to draw the histogram in", option);
+                std::process::exit(1);
+            }
+        }
+    }
+    let options = histogram_options(&args, opens_window);
+    let normalization = match args.normalize {
+        NormalizeArg::Count => Normalization::Count,
+        NormalizeArg::Fraction => Normalization::Fraction,
+        NormalizeArg::Percent => Normalization::Percent,
+        NormalizeArg::Density => Normalization::Density,
+    };
+    let fits = args
+        .fit
+        .iter()
+        .map(|fit| match fit {
+            FitArg::Normal => Family::Normal,
+            FitArg::LogNormal => Family::LogNormal,
+            FitArg::Exponential => Family::Exponential,
+            FitArg::Gamma => Family::Gamma,
+            FitArg::Weibull => Family::Weibull,
+        })
+        .collect::<HashSet<_>>();
+    let layout = match args.layout {
+        Layout::Overlay => BarLayout::Overlay,
+        Layout::Grouped => BarLayout::Grouped,
+    };
+
+    let inputs = if args.input.is_empty() {
+        if atty::is(Stream::Stdin) {
+            eprintln!("hist3: input must either be piped in or given as a file");
+            std::process::exit(1);
+        }
+        vec![("stdin".to_owned(), InputSource::Stdin)]
+    } else {
+        args.input
+            .iter()
+            .map(|file_name| (file_name.clone(), InputSource::FileName(file_name.clone())))
+            .collect()
+    };
+
+    let kde = KdeSettings {
+        show: args.kde,
+        kernel: match args.kernel {
+            KernelArg::Gaussian => Kernel::Gaussian,
+            KernelArg::Epanechnikov => Kernel::Epanechnikov,
+        },
+        rule: match args.bandwidth {
+            BandwidthArg::Silverman => BandwidthRule::Silverman,
+            BandwidthArg::Scott => BandwidthRule::Scott,
+        },
+        factor: 1.0,
+    };
+
+    if live {
+        if inputs.len() > 1 || args.group_by.is_some() {
+            eprintln!("hist3: --live reads a single series");
+            std::process::exit(1);
+        }
+        if !has_display() {
+            eprintln!("hist3: --live needs a display to draw the histogram in");
+            std::process::exit(1);
+        }
+        let (name, input) = inputs.into_iter().next().expect("one input");
+        let values = match LiveValues::new(args.window, &options) {
+            Ok(values) => Arc::new(Mutex::new(values)),
+            Err(e) => {
+                eprintln!("hist3: {}", e);
+                std::process::exit(1);
+            }
+        };
+        let values_ref = values.clone();
+        let reader_options = options.clone();
+        thread::spawn(move || data::read_live(&input, &reader_options, &values_ref));
+
+        let plot = HistApp::new(Vec::new(), options, layout, vec![colors::DEFAULT_BAR_COLOR])
+            .set_kde(kde)
+            .set_horizontal(args.horizontal)
+            .set_view(args.view)
+            .set_normalization(normalization)
+            .set_shown_fits(fits)
+            .set_emit_selection(args.emit_selection)
+            .set_live(Live {
+                name,
+                values,
+                version: 0,
+                binned_at: None,
+                error: None,
+                lines: Vec::new(),
+            });
+        return run_window(&title, plot);
+    }
 
-    let input = if !atty::is(Stream::Stdin) {
-        InputSource::Stdin
+    let series = if args.streaming {
+        if inputs.len() > 1 || args.group_by.is_some() {
+            eprintln!("hist3: --streaming reads a single series");
+            std::process::exit(1);
+        }
+        let (name, input) = inputs.into_iter().next().expect("one input");
+        let histogram = if args.categorical {
+            data::compute_streaming_categories(input, &options)
+        } else {
+            data::compute_streaming_histogram(input, &options)
+        };
+        histogram.map(|histogram| {
+            vec![Series {
+                name,
+                histogram,
+                values: Vec::new(),
+                lines: Vec::new(),
+            }]
+        })
     } else {
-        let file_name = args
-            .input
-            .expect("Input must either be piped in or provide a file")
-            .to_owned();
-        if !Path::new(&file_name).exists() {
-            panic!("File does not exist");
-        }
-        InputSource::FileName(file_name)
+        data::compute_series(&inputs, &options)
+    };
+    let series = match series {
+        Ok(series) => series,
+        Err(e) => {
+            eprintln!("hist3: {}", e);
+            std::process::exit(1);
+        }
     };
 
-    let (labels_and_counts, p_25, p_50, p_75, total, range) =
-        data::compute_histogram(args.bins, input, args.categorical);
+    if let Some(format) = args.format {
+        match (format, series.as_slice()) {
+            (Format::Json, [single]) => print!("{}", report::to_json(&single.histogram)),
+            (Format::Csv, [single]) => print!("{}", report::to_csv(&single.histogram)),
+            (Format::Json, _) => print!("{}", report::series_to_json(&series)),
+            (Format::Csv, _) => print!("{}", report::series_to_csv(&series)),
+        }
+        return Ok(());
+    }
 
-    let plot = HistApp::new(labels_and_counts, p_25, p_50, p_75, total, range, args.bins);
+    let bar_colors: &[egui::Color32] = if series.len() == 1 {
+        &[colors::DEFAULT_BAR_COLOR]
+    } else {
+        &colors::SERIES_COLORS
+    };
+
+    if let Some(path) = &args.output {
+        let figure = Figure {
+            series: &series,
+            title: &title,
+            layout,
+            bar_colors,
+            quantile_colors: &colors::PERCENTILE_COLORS,
+            horizontal: args.horizontal,
+            normalization,
+        };
+        if let Err(e) = figure.save(path) {
+            eprintln!("hist3: {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
 
+    if args.text || !has_display() {
+        let width = terminal_width();
+        if let [single] = series.as_slice() {
+            print!(
+                "{}",
+                render::text(&single.histogram, &title, width, normalization)
+            );
+        } else {
+            println!("{}", title);
+            for s in &series {
+                print!(
+                    "{}",
+                    render::text(&s.histogram, &s.name, width, normalization)
+                );
+            }
+        }
+        return Ok(());
+    }
+
+    let plot = HistApp::new(series, options, layout, bar_colors.to_vec())
+        .set_kde(kde)
+        .set_horizontal(args.horizontal)
+        .set_view(args.view)
+        .set_normalization(normalization)
+        .set_shown_fits(fits)
+        .set_emit_selection(args.emit_selection);
+    run_window(&title, plot)
+}
+
+fn run_window(title: &str, plot: HistApp) -> Result<(), eframe::Error> {
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_inner_size([1024.0, 600.0]) // Wider default window
             .with_min_inner_size([400.0, 300.0]), // Set minimum size
         ..Default::default()
     };
-    eframe::run_native(title.as_str(), options, Box::new(|_| Ok(Box::new(plot))))
+    eframe::run_native(title, options, Box::new(|_| Ok(Box::new(plot))))
+}
+
+/// Whether a window can be opened. On X11 and Wayland systems this needs a display server.
+/// The options `args` ask for, with the input lines kept if [`keeps_lines`].
+fn histogram_options(args: &Args, opens_window: bool) -> HistogramOptions {
+    HistogramOptions {
+        rule: bin_rule(args),
+        scale: args.log.map_or(Scale::Linear, |log| Scale::Log(log.base())),
+        range: args.min.zip(args.max),
+        categorical: args.categorical,
+        field: args.column.clone().map(|column| FieldSelector {
+            column,
+            delimiter: args.delimiter,
+            header: args.header,
+            group_by: args.group_by.clone(),
+        }),
+        quantiles: args.quantiles.iter().map(|p| p / 100.0).collect(),
+        strict: args.strict,
+        top: args.top.map(NonZeroUsize::get),
+        order: match args.sort {
+            SortArg::Count => CategoryOrder::Count,
+            SortArg::Label => CategoryOrder::Label,
+            SortArg::Natural => CategoryOrder::Natural,
+        },
+        descending: args.descending,
+        keep_lines: keeps_lines(args, opens_window),
+    }
+}
+
+/// Whether the input lines are kept, so that those in the selected bars can be written with
+/// Enter or the "Write selection" button, and with `--emit-selection` when the window is closed.
+/// Streamed input is not held in memory, so neither are its lines.
+fn keeps_lines(args: &Args, opens_window: bool) -> bool {
+    opens_window && !args.streaming
+}
+
+fn has_display() -> bool {
+    if cfg!(any(target_os = "windows", target_os = "macos")) {
+        return true;
+    }
+    ["DISPLAY", "WAYLAND_DISPLAY"]
+        .iter()
+        .any(|var| std::env::var_os(var).is_some_and(|v| !v.is_empty()))
+}
+
+/// Width of the terminal on stdout, or of `$COLUMNS` when it is not a terminal.
+fn terminal_width() -> usize {
+    terminal_size::terminal_size()
+        .map(|(terminal_size::Width(w), _)| w as usize)
+        .or_else(|| std::env::var("COLUMNS").ok()?.parse().ok())
+        .unwrap_or(80)
+}
+
+/// Parses a percentile between 0 and 100.
+fn parse_percent(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(p) if (0.0..=100.0).contains(&p) => Ok(p),
+        Ok(p) => Err(format!("{} is not between 0 and 100", p)),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn bin_rule(args: &Args) -> BinRule {
+    if let Some(width) = args.bin_width {
+        return BinRule::Width(width);
+    }
+    if !args.edges.is_empty() {
+        return BinRule::Edges(args.edges.clone());
+    }
+    match args.bin_rule {
+        BinRuleArg::Count => BinRule::Count(args.bins),
+        BinRuleArg::Sturges => BinRule::Sturges,
+        BinRuleArg::Scott => BinRule::Scott,
+        BinRuleArg::FreedmanDiaconis => BinRule::FreedmanDiaconis,
+        BinRuleArg::Sqrt => BinRule::SquareRoot,
+    }
+}
+
+/// How the kernel density estimate over the bars is drawn.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct KdeSettings {
+    show: bool,
+    kernel: Kernel,
+    rule: BandwidthRule,
+    /// Multiplies the bandwidth given by the rule.
+    factor: f64,
+}
+
+/// Number of points each density curve is drawn with.
+const KDE_RESOLUTION: usize = 400;
+
+/// Number of points each fitted density is drawn with.
+const FIT_RESOLUTION: usize = 400;
+
+/// Largest number of points each cumulative distribution is drawn with.
+const CDF_RESOLUTION: usize = 4000;
+
+/// Largest number of bins the bins slider offers, unless there are more to start with.
+const MAX_SLIDER_BINS: usize = 200;
+
+/// How often a live input is re-binned while values arrive.
+const LIVE_REFRESH: Duration = Duration::from_millis(200);
+
+/// An input read on another thread, re-binned as its values arrive.
+struct Live {
+    name: String,
+    values: Arc<Mutex<LiveValues>>,
+    /// The version of the values the series were last binned from.
+    version: u64,
+    binned_at: Option<Instant>,
+    /// Why the latest values could not be binned.
+    error: Option<String>,
+    /// The lines of the values, which are kept here rather than in the series so that those of
+    /// an input without a window only need adding to.
+    lines: Vec<InputLine>,
 }
 
 struct HistApp {
-    data: Vec<(String, usize)>,
-    p_25: Option<(f64, f64)>,
-    p_50: Option<(f64, f64)>,
-    p_75: Option<(f64, f64)>,
-    total: f64,
-    range: f64,
-    num_bins: usize,
+    /// One or more histograms sharing the same bins, as shown.
+    series: Vec<Series>,
+    /// The settings the input was binned with, to bin it again.
+    options: HistogramOptions,
+    /// The histograms of the whole input, which the shown ones are re-binned from.
+    full: Vec<Series>,
+    /// Number of bins chosen with the slider, or `None` for the bins given by `options`.
+    bin_count: Option<usize>,
+    /// Whether the bins slider has moved since the series were last re-binned.
+    bin_count_changed: bool,
+    /// Ranges zoomed into, the innermost last.
+    zoom: Vec<(f64, f64)>,
+    /// Why the series could not be re-binned.
+    rebin_error: Option<String>,
+    /// Whether the plot bounds should be fitted to the bars again.
+    reset_plot: bool,
+    layout: BarLayout,
+    bar_colors: Vec<egui::Color32>,
+    /// Density estimate of each series in plot coordinates, or `None` if categorical.
+    kdes: Vec<Option<Kde>>,
+    kde: KdeSettings,
+    /// The density curves, scaled to counts, and the settings they were computed with.
+    kde_curves: Option<(KdeSettings, Vec<Vec<[f64; 2]>>)>,
+    /// Whether each quantile is drawn as a vertical line.
+    quantile_lines: Vec<bool>,
+    normalization: Normalization,
+    /// The distributions fitted to each series, in the order of [`Family::ALL`], computed when
+    /// first needed.
+    fits: Option<Vec<Vec<Option<Fit>>>>,
+    /// The fitted distributions drawn over the bars.
+    shown_fits: HashSet<Family>,
+    view: View,
+    /// The steps of the cumulative distribution of each series, computed when first drawn.
+    cdf_steps: Option<Vec<Vec<[f64; 2]>>>,
+    live: Option<Live>,
+    /// Whether categorical bars are drawn horizontally, the first one at the top.
+    horizontal: bool,
     grid: bool,
     axes: bool,
     selection: Option<HashSet<usize>>,
+    /// Where the lines in the selected bars are written: a file, or stdout if `None` or `-`.
+    selection_output: Option<PathBuf>,
+    /// Whether the lines in the selected bars are written when the window is closed.
+    emit_on_close: bool,
+    /// How many lines were last written and where, or why they could not be.
+    emit_status: Option<Result<String, String>>,
     drag_start: Option<egui_plot::PlotPoint>,
     drag_end: Option<egui_plot::PlotPoint>,
 }
 
 impl HistApp {
     fn new(
-        data: Vec<(String, usize)>,
-        p_25: Option<(f64, f64)>,
-        p_50: Option<(f64, f64)>,
-        p_75: Option<(f64, f64)>,
-        total: f64,
-        range: f64,
-        num_bins: usize,
+        series: Vec<Series>,
+        options: HistogramOptions,
+        layout: BarLayout,
+        bar_colors: Vec<egui::Color32>,
     ) -> Self {
-        HistApp {
-            data,
-            p_25,
-            p_50,
-            p_75,
-            total,
-            range,
-            num_bins,
+        let mut app = HistApp {
+            series: Vec::new(),
+            options,
+            full: Vec::new(),
+            bin_count: None,
+            bin_count_changed: false,
+            zoom: Vec::new(),
+            rebin_error: None,
+            reset_plot: false,
+            layout,
+            bar_colors,
+            kdes: Vec::new(),
+            kde: KdeSettings {
+                show: false,
+                kernel: Kernel::default(),
+                rule: BandwidthRule::default(),
+                factor: 1.0,
+            },
+            kde_curves: None,
+            quantile_lines: Vec::new(),
+            normalization: Normalization::Count,
+            fits: None,
+            shown_fits: HashSet::new(),
+            view: View::Bars,
+            cdf_steps: None,
+            live: None,
+            horizontal: false,
             grid: true,
             axes: true,
             selection: None,
+            selection_output: None,
+            emit_on_close: false,
+            emit_status: None,
             drag_start: None,
             drag_end: None,
+        };
+        app.full = series.clone();
+        app.set_series(series);
+        app
+    }
+
+    /// Shows the full series, or re-bins them with the number of bins from the slider and in
+    /// the innermost zoomed range. Returns whether that worked.
+    fn apply_view(&mut self) -> bool {
+        if self.full.is_empty() || (self.zoom.is_empty() && self.bin_count.is_none()) {
+            self.rebin_error = None;
+            self.set_series(self.full.clone());
+            self.reset_plot = true;
+            return true;
+        }
+        let options = HistogramOptions {
+            rule: BinRule::Count(self.bin_count.unwrap_or(self.full[0].histogram.bins.len())),
+            range: self.zoom.last().copied().or(self.options.range),
+            ..self.options.clone()
+        };
+        match data::rebin(&self.full, &options) {
+            Ok(series) => {
+                self.rebin_error = None;
+                self.set_series(series);
+                self.reset_plot = true;
+                true
+            }
+            Err(e) => {
+                self.rebin_error = Some(e.to_string());
+                false
+            }
         }
     }
 
-    fn is_bar_in_rect(
-        &self,
-        bar_idx: usize,
-        start: &egui_plot::PlotPoint,
-        end: &egui_plot::PlotPoint,
-    ) -> bool {
-        let bar_x = self.data[bar_idx]
-            .0
-            .parse::<f64>()
-            .unwrap_or(bar_idx as f64);
-        let bar_y = self.data[bar_idx].1 as f64;
+    /// Bins slider, zooming into the selected bars, and a breadcrumb of the zoomed ranges to go
+    /// back out. Series whose values were not kept cannot be re-binned.
+    fn view_controls(&mut self, ui: &mut egui::Ui) {
+        let rebinnable = self.full.iter().all(|s| !s.values.is_empty());
+        ui.add_enabled_ui(rebinnable, |ui| {
+            let mut count = self.bin_count.unwrap_or(self.bins().len());
+            let max = MAX_SLIDER_BINS.max(count);
+            let response = ui.add(egui::Slider::new(&mut count, 1..=max).text("bins"));
+            if response.changed() {
+                self.bin_count = Some(count);
+                self.bin_count_changed = true;
+            }
+            // Re-bin once the slider is let go rather than on every step of the drag.
+            if self.bin_count_changed && !response.dragged() {
+                self.bin_count_changed = false;
+                self.apply_view();
+            }
 
-        let width = self.range / self.num_bins as f64;
-        let half_width = width / 2.0;
+            let selected = self.selection.as_ref().map(|selection| {
+                let bins = self.bins();
+                let lo = selection
+                    .iter()
+                    .map(|&i| bins[i].lower)
+                    .fold(f64::INFINITY, f64::min);
+                let hi = selection
+                    .iter()
+                    .map(|&i| bins[i].upper)
+                    .fold(f64::NEG_INFINITY, f64::max);
+                (lo, hi)
+            });
+            if ui
+                .add_enabled(selected.is_some(), egui::Button::new("Zoom to selection"))
+                .clicked()
+            {
+                if let Some(range) = selected {
+                    self.zoom.push(range);
+                    if !self.apply_view() {
+                        self.zoom.pop();
+                    }
+                }
+            }
 
-        let rect_x_min = start.x.min(end.x);
-        let rect_x_max = start.x.max(end.x);
-        let rect_y_min = start.y.min(end.y);
-        let rect_y_max = start.y.max(end.y);
+            let mut depth = None;
+            if ui
+                .add_enabled(!self.zoom.is_empty(), egui::Button::new("⏴ Back"))
+                .clicked()
+            {
+                depth = Some(self.zoom.len() - 1);
+            }
+            if ui.selectable_label(self.zoom.is_empty(), "All").clicked() {
+                depth = Some(0);
+            }
+            for (i, (lo, hi)) in self.zoom.iter().enumerate() {
+                ui.label("›");
+                let innermost = i + 1 == self.zoom.len();
+                if ui
+                    .selectable_label(innermost, format!("{:.4} – {:.4}", lo, hi))
+                    .clicked()
+                {
+                    depth = Some(i + 1);
+                }
+            }
+            if let Some(depth) = depth.filter(|&depth| depth < self.zoom.len()) {
+                self.zoom.truncate(depth);
+                self.apply_view();
+            }
+        });
+        if let Some(error) = &self.rebin_error {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+        }
+    }
 
-        let bar_rect_x_min = bar_x - half_width;
-        let bar_rect_x_max = bar_x + half_width;
+    /// Replaces the histograms, keeping the settings and, if the bins are the same, the
+    /// selection.
+    fn set_series(&mut self, series: Vec<Series>) {
+        let bins = |series: &[Series]| series.first().map(|s| s.histogram.bins.clone());
+        if bins(&self.series) != bins(&series) {
+            self.selection = None;
+        }
+        self.kdes = series
+            .iter()
+            .map(|s| {
+                let hist = &s.histogram;
+                if hist.is_categorical() {
+                    None
+                } else if !s.values.is_empty() {
+                    let values = s.values.iter().map(|&x| hist.scale.forward(x));
+                    Kde::from_values(&values.collect::<Vec<_>>())
+                } else {
+                    // Streamed series only have their bins left.
+                    Kde::from_weighted(
+                        hist.bins
+                            .iter()
+                            .map(|b| {
+                                let mid = (hist.scale.forward(b.lower)
+                                    + hist.scale.forward(b.upper))
+                                    / 2.0;
+                                (mid, b.count as f64)
+                            })
+                            .collect(),
+                    )
+                }
+            })
+            .collect();
+        self.kde_curves = None;
+        self.cdf_steps = None;
+        self.fits = None;
+        if let Some(first) = series.first() {
+            self.quantile_lines
+                .resize(first.histogram.quantiles.len(), true);
+        }
+        self.series = series;
+    }
 
-        rect_x_max >= bar_rect_x_min
-            && rect_x_min <= bar_rect_x_max
-            && rect_y_max >= 0.0
-            && rect_y_min <= bar_y
+    fn set_kde(mut self, kde: KdeSettings) -> Self {
+        self.kde = kde;
+        self
     }
-}
 
-impl eframe::App for HistApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        let width = if self.p_25.is_some() {
-            self.range / self.num_bins as f64
+    fn set_normalization(mut self, normalization: Normalization) -> Self {
+        self.normalization = normalization;
+        self
+    }
+
+    fn set_shown_fits(mut self, shown_fits: HashSet<Family>) -> Self {
+        self.shown_fits = shown_fits;
+        self
+    }
+
+    fn set_view(mut self, view: View) -> Self {
+        self.view = view;
+        self
+    }
+
+    fn set_horizontal(mut self, horizontal: bool) -> Self {
+        self.horizontal = horizontal;
+        self
+    }
+
+    /// Writes the lines in the selected bars to `path`, or stdout for `-`, when the window is
+    /// closed, if given.
+    fn set_emit_selection(mut self, path: Option<PathBuf>) -> Self {
+        self.emit_on_close = path.is_some();
+        self.selection_output = path;
+        self
+    }
+
+    fn set_live(mut self, live: Live) -> Self {
+        self.live = Some(live);
+        self
+    }
+
+    /// Re-bins a live input if its values have changed, at most every [`LIVE_REFRESH`], and
+    /// keeps repainting while they may still change.
+    fn refresh_live(&mut self, ctx: &egui::Context) {
+        let Some(live) = &mut self.live else {
+            return;
+        };
+        let options = &self.options;
+        // Only copy the values out while they are locked, so that the reader is not held up
+        // while they are binned.
+        let snapshot = {
+            let mut values = live.values.lock().unwrap();
+            values.expire(Instant::now());
+            let changed = values.version() != live.version;
+            if changed || !values.is_done() || matches!(values.window(), Some(Window::Duration(_)))
+            {
+                ctx.request_repaint_after(LIVE_REFRESH);
+            }
+            if !changed || live.binned_at.is_some_and(|at| at.elapsed() < LIVE_REFRESH) {
+                return;
+            }
+            live.version = values.version();
+            live.binned_at = Some(Instant::now());
+            (!values.is_empty()).then(|| values.snapshot())
+        };
+        let result = snapshot.map(|snapshot| snapshot.series(&live.name, options, &mut live.lines));
+        match result {
+            Some(Ok(series)) => {
+                live.error = None;
+                self.full = vec![series];
+            }
+            Some(Err(e)) => {
+                live.error = Some(e.to_string());
+                return;
+            }
+            // Every value has fallen out of the window.
+            None => {
+                live.error = None;
+                self.full = Vec::new();
+            }
+        }
+        self.apply_view();
+    }
+
+    /// What a live input is doing, for the status bar: how much of it is binned, whether it is
+    /// still being read and why reading it stopped.
+    fn live_status(&self, ui: &mut egui::Ui) {
+        let Some(live) = &self.live else {
+            return;
+        };
+        let values = live.values.lock().unwrap();
+        match values.window() {
+            Some(Window::Count(count)) => ui.label(format!("Last {} values |", count)),
+            Some(Window::Duration(duration)) => ui.label(format!("Last {:?} |", duration)),
+            None => ui.label("All values |"),
+        };
+        ui.label(if values.is_done() {
+            "End of input |"
         } else {
-            1.0
+            "Reading… |"
+        });
+        for error in values
+            .error()
+            .map(|e| e.to_string())
+            .iter()
+            .chain(&live.error)
+        {
+            ui.colored_label(ui.visuals().error_fg_color, format!("{} |", error));
+        }
+    }
+
+    /// Whether the input lines were kept, so that those in the selected bars can be written.
+    fn has_lines(&self) -> bool {
+        !self.full.is_empty() && (0..self.full.len()).all(|i| !self.lines(i).is_empty())
+    }
+
+    /// The input lines of the `i`th series.
+    fn lines(&self, i: usize) -> &[InputLine] {
+        match &self.live {
+            Some(live) => &live.lines,
+            None => &self.full[i].lines,
+        }
+    }
+
+    /// The input lines in the selected bars, series by series, each in input order.
+    fn selected_lines(&self) -> Vec<&str> {
+        let Some(selection) = &self.selection else {
+            return Vec::new();
         };
-        let min_x = self.data.first().unwrap().0.parse::<f64>().unwrap_or(0.0);
-        let max_x = self
-            .data
-            .last()
-            .unwrap()
-            .0
-            .parse::<f64>()
-            .unwrap_or(self.data.len() as f64);
-        let max_y = *self.data.iter().map(|(_, c)| c).max().unwrap() as f64;
-        let chart = BarChart::new(
-            self.data
-                .iter()
-                .enumerate()
-                .map(|(i, (label, count))| {
-                    let mut bar = if self.p_25.is_some() {
-                        Bar::new(label.parse::<f64>().unwrap(), *count as f64)
-                            .width(width)
-                            .name(label)
-                            .fill(colors::DEFAULT_BAR_COLOR)
-                    } else {
-                        Bar::new(i as f64, *count as f64)
-                            .width(1.0)
-                            .name(label)
-                            .fill(colors::DEFAULT_BAR_COLOR)
-                    };
+        // The shown bins may be zoomed into, but the lines are those of the whole input.
+        self.series
+            .iter()
+            .enumerate()
+            .flat_map(|(i, shown)| shown.histogram.lines_in_bins(selection, self.lines(i)))
+            .collect()
+    }
 
-                    if let Some(selected_indices) = &self.selection {
-                        if selected_indices.contains(&i) {
-                            bar = bar.fill(colors::SELECTED_BAR_COLOR);
-                        }
+    /// Writes the input lines in the selected bars to the selection output. A file is
+    /// overwritten each time, while stdout gets one batch of lines after another.
+    fn write_selection(&mut self) {
+        let lines = self.selected_lines();
+        let count = lines.len();
+        let result = match self
+            .selection_output
+            .as_deref()
+            .filter(|path| *path != Path::new("-"))
+        {
+            Some(path) => File::create(path)
+                .and_then(|file| write_lines(BufWriter::new(file), &lines))
+                .map(|()| format!("Wrote {} lines to {}", count, path.display()))
+                .map_err(|e| format!("{}: {}", path.display(), e)),
+            None => write_lines(io::stdout().lock(), &lines)
+                .map(|()| format!("Wrote {} lines to stdout", count))
+                .map_err(|e| format!("stdout: {}", e)),
+        };
+        self.emit_status = Some(result);
+    }
+
+    /// The bins shared by all series.
+    fn bins(&self) -> &[data::Bin] {
+        &self.series[0].histogram.bins
+    }
+
+    fn scale(&self) -> Scale {
+        self.series[0].histogram.scale
+    }
+
+    /// Maps a value to the x coordinate it is drawn at, which is its logarithm on a log scale.
+    fn to_plot(&self, x: f64) -> f64 {
+        self.scale().forward(x)
+    }
+
+    /// Maps a point in plot coordinates to one with the bins along x and the counts along y,
+    /// which differ when the bars are horizontal and counted from the top.
+    fn in_bins(&self, point: egui_plot::PlotPoint) -> egui_plot::PlotPoint {
+        if self.horizontal {
+            egui_plot::PlotPoint {
+                x: self.flip(point.y),
+                y: point.x,
+            }
+        } else {
+            point
+        }
+    }
+
+    /// The inverse of [`HistApp::in_bins`].
+    fn in_plot(&self, point: egui_plot::PlotPoint) -> egui_plot::PlotPoint {
+        if self.horizontal {
+            egui_plot::PlotPoint {
+                x: point.y,
+                y: self.flip(point.x),
+            }
+        } else {
+            point
+        }
+    }
+
+    /// Turns the position of a horizontal bar into its height in the plot, the first one at
+    /// the top, and back.
+    fn flip(&self, position: f64) -> f64 {
+        self.bins().len() as f64 - 1.0 - position
+    }
+
+    fn bar_color(&self, series_idx: usize) -> egui::Color32 {
+        self.bar_colors[series_idx % self.bar_colors.len()]
+    }
+
+    /// The count of bin `bin_idx` in each series.
+    fn counts(&self, bin_idx: usize) -> impl Iterator<Item = usize> + '_ {
+        self.series
+            .iter()
+            .map(move |s| s.histogram.bins[bin_idx].count)
+    }
+
+    /// The bandwidth of each series' density estimate, in plot coordinates.
+    fn bandwidths(&self) -> Vec<Option<f64>> {
+        self.kdes
+            .iter()
+            .map(|kde| {
+                kde.as_ref()
+                    .map(|kde| kde.bandwidth(self.kde.rule) * self.kde.factor)
+            })
+            .collect()
+    }
+
+    /// The density curve of each series, scaled so that its area matches the area of its bars,
+    /// recomputed only when the settings or the bar heights change.
+    fn kde_curves(&mut self) -> &[Vec<[f64; 2]>] {
+        if self.kde_curves.as_ref().map(|(settings, _)| settings) != Some(&self.kde) {
+            let bin_width = self.average_bin_width();
+            let curves = self
+                .kdes
+                .iter()
+                .zip(self.bandwidths())
+                .zip(&self.series)
+                .map(|((kde, bandwidth), series)| match (kde, bandwidth) {
+                    (Some(kde), Some(bandwidth)) => {
+                        // The height of a bar as wide as the average bin holding every value.
+                        let height = self.normalization.height(
+                            kde.total(),
+                            series.histogram.total as f64,
+                            bin_width,
+                        );
+                        kde.curve(self.kde.kernel, bandwidth, KDE_RESOLUTION)
+                            .into_iter()
+                            .map(|[x, density]| [x, density * bin_width * height])
+                            .collect()
                     }
-                    bar
+                    _ => Vec::new(),
                 })
-                .collect(),
-        );
+                .collect();
+            self.kde_curves = Some((self.kde, curves));
+        }
+        self.kde_curves.as_ref().map_or(&[], |(_, curves)| curves)
+    }
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            Plot::new("")
-                .allow_boxed_zoom(false)
-                .allow_drag(false)
-                .allow_scroll(false)
-                .legend(Legend::default())
-                .show_grid(self.grid)
-                .show_axes(self.axes)
-                .x_axis_label(" ")
-                .label_formatter(|name, value| {
-                    if !name.is_empty() {
-                        name.to_owned()
+    /// Chooses what the bar heights stand for.
+    fn normalization_selector(&mut self, ui: &mut egui::Ui) {
+        let before = self.normalization;
+        egui::ComboBox::new("normalization_combo", "")
+            .selected_text(self.normalization.name())
+            .show_ui(ui, |ui| {
+                for normalization in [
+                    Normalization::Count,
+                    Normalization::Fraction,
+                    Normalization::Percent,
+                    Normalization::Density,
+                ] {
+                    ui.selectable_value(
+                        &mut self.normalization,
+                        normalization,
+                        normalization.name(),
+                    );
+                }
+            });
+        if self.normalization != before {
+            self.kde_curves = None;
+            self.reset_plot = true;
+        }
+    }
+
+    /// Switches between the bars and the cumulative distribution, which needs the values.
+    fn view_selector(&mut self, ui: &mut egui::Ui) {
+        let has_values = self.series.iter().all(|s| !s.values.is_empty());
+        let before = self.view;
+        ui.selectable_value(&mut self.view, View::Bars, "Bars");
+        ui.add_enabled_ui(has_values, |ui| {
+            ui.selectable_value(&mut self.view, View::Cdf, "CDF")
+                .on_hover_text("Fraction of the values at most x");
+            ui.selectable_value(&mut self.view, View::Survival, "1 − CDF")
+                .on_hover_text("Fraction of the values above x, on a log scale");
+        });
+        if self.view != before {
+            self.reset_plot = true;
+        }
+    }
+
+    /// The steps of the cumulative distribution of each series, recomputed only when the series
+    /// change.
+    fn cdf_steps(&mut self) -> &[Vec<[f64; 2]>] {
+        let series = &self.series;
+        self.cdf_steps.get_or_insert_with(|| {
+            series
+                .iter()
+                .map(|s| {
+                    s.ecdf()
+                        .map_or_else(Vec::new, |ecdf| ecdf.steps(CDF_RESOLUTION))
+                })
+                .collect()
+        })
+    }
+
+    /// Draws the cumulative distribution, or its complement on a log scale, of each series.
+    /// Hovering shows the fraction of each series below or above the pointer, and the value at
+    /// the pointer's height.
+    fn show_cdf(&self, ui: &mut egui::Ui, steps: Vec<Vec<[f64; 2]>>, reset: bool) {
+        let scale = self.scale();
+        let survival = self.view == View::Survival;
+        let ecdfs = self
+            .series
+            .iter()
+            .filter_map(|s| Some((s.name.as_str(), s.ecdf()?)))
+            .collect::<Vec<_>>();
+        let mut plot = Plot::new("cdf")
+            .allow_boxed_zoom(false)
+            .allow_drag(false)
+            .allow_scroll(false)
+            .legend(Legend::default())
+            .show_grid(self.grid)
+            .show_axes(self.axes)
+            .x_axis_label(" ")
+            .label_formatter(move |_, point| {
+                let x = scale.inverse(point.x);
+                let mut label = format!("x = {:.4}", x);
+                for (name, ecdf) in &ecdfs {
+                    let (op, fraction) = if survival {
+                        (">", ecdf.survival(x))
                     } else {
-                        format!("{:.1}", value.x)
+                        ("≤", ecdf.cdf(x))
+                    };
+                    let name = if ecdfs.len() > 1 { name } else { "" };
+                    label += &format!("\n{}P(X {} x) = {:.2}%", name, op, 100.0 * fraction);
+                }
+                let p = if survival {
+                    1.0 - 10f64.powf(point.y)
+                } else {
+                    point.y
+                };
+                if (0.0..=1.0).contains(&p) {
+                    for (name, ecdf) in &ecdfs {
+                        let name = if ecdfs.len() > 1 { name } else { "" };
+                        label += &format!("\n{}p{:.2} = {:.4}", name, 100.0 * p, ecdf.quantile(p));
                     }
-                })
-                .show(ui, |plot_ui| {
-                    if let Some(pointer) = plot_ui.pointer_coordinate() {
-                        let pointer = egui_plot::PlotPoint {
-                            x: pointer.x.clamp(min_x, max_x),
-                            y: pointer.y.clamp(0.0, max_y),
+                }
+                label
+            });
+        if reset {
+            plot = plot.reset();
+        }
+        if scale.is_log() {
+            plot = plot.x_axis_formatter(move |mark, _| {
+                render::tick_label(scale, mark.value, mark.step_size)
+            });
+        }
+        if survival {
+            // Only powers of ten are labelled, as fractions.
+            plot = plot.y_axis_formatter(|mark, _| {
+                let power = mark.value.round();
+                if (mark.value - power).abs() > 1e-9 {
+                    String::new()
+                } else {
+                    format!("{}", 10f64.powf(power))
+                }
+            });
+        }
+        let num_series = self.series.len();
+        let lines = steps
+            .into_iter()
+            .zip(&self.series)
+            .enumerate()
+            .map(|(series_idx, (steps, series))| {
+                let points = steps
+                    .into_iter()
+                    .filter_map(|[x, cdf]| {
+                        let y = if survival {
+                            // The largest value has nothing above it.
+                            Some(1.0 - cdf).filter(|&s| s > 0.0)?.log10()
+                        } else {
+                            cdf
                         };
-                        if plot_ui.ctx().input(|i| i.pointer.primary_pressed()) {
-                            self.drag_start = Some(pointer);
-                            self.drag_end = Some(pointer);
-                        } else if plot_ui.ctx().input(|i| i.pointer.primary_down()) {
-                            self.drag_end = Some(pointer);
-                        } else if plot_ui.ctx().input(|i| i.pointer.primary_released()) {
-                            if let (Some(start), Some(end)) = (self.drag_start, self.drag_end) {
-                                let selected_bars: HashSet<usize> = (0..self.data.len())
-                                    .filter(|&i| self.is_bar_in_rect(i, &start, &end))
-                                    .collect();
-
-                                if !selected_bars.is_empty() {
-                                    self.selection = Some(selected_bars);
-                                } else {
-                                    self.selection = None;
-                                }
+                        Some([self.to_plot(x), y])
+                    })
+                    .collect::<Vec<_>>();
+                let line = Line::new(points).width(2.0);
+                if num_series > 1 {
+                    line.color(self.bar_color(series_idx)).name(&series.name)
+                } else {
+                    line.color(colors::KDE_COLOR)
+                        .name(if survival { "1 − CDF" } else { "CDF" })
+                }
+            })
+            .collect::<Vec<_>>();
+        let vlines = self.quantile_vlines();
+        plot.show(ui, |plot_ui| {
+            for line in lines {
+                plot_ui.line(line);
+            }
+            for vline in vlines {
+                plot_ui.vline(vline);
+            }
+        });
+    }
+
+    /// A vertical line for each quantile that is shown, in every series.
+    fn quantile_vlines(&self) -> Vec<egui_plot::VLine> {
+        let num_series = self.series.len();
+        let mut vlines = Vec::new();
+        for (series_idx, series) in self.series.iter().enumerate() {
+            for ((q, color), _) in series
+                .histogram
+                .quantiles
+                .iter()
+                .zip(colors::PERCENTILE_COLORS.iter().cycle())
+                .zip(&self.quantile_lines)
+                .filter(|(_, &shown)| shown)
+            {
+                let x = self.to_plot(q.value);
+                let line = if num_series > 1 {
+                    // Each series' percentiles in its own colour, the quartiles dashed.
+                    let style = if q.p == 0.5 {
+                        LineStyle::Solid
+                    } else {
+                        LineStyle::dashed_loose()
+                    };
+                    egui_plot::VLine::new(x)
+                        .color(self.bar_color(series_idx))
+                        .style(style)
+                        .name(format!(
+                            "{} {} ptile: {:.4}",
+                            series.name,
+                            q.percent(),
+                            q.value
+                        ))
+                } else {
+                    egui_plot::VLine::new(x).color(*color).name(format!(
+                        "{} ptile: {:.4}",
+                        q.percent(),
+                        q.value
+                    ))
+                };
+                vlines.push(line);
+            }
+        }
+        vlines
+    }
+
+    /// The average width of the bins in plot coordinates, which densities are scaled by to
+    /// match bars that have no single width.
+    fn average_bin_width(&self) -> f64 {
+        let bins = self.bins();
+        let span = self.to_plot(bins[bins.len() - 1].upper) - self.to_plot(bins[0].lower);
+        span / bins.len() as f64
+    }
+
+    /// The distributions fitted to each series, fitted only when first needed after the
+    /// series change.
+    fn fits(&mut self) -> &[Vec<Option<Fit>>] {
+        let series = &self.series;
+        self.fits.get_or_insert_with(|| {
+            series
+                .iter()
+                .map(|s| Family::ALL.iter().map(|f| f.fit(&s.values)).collect())
+                .collect()
+        })
+    }
+
+    /// The density of each shown fit of each series, in plot coordinates and scaled like the
+    /// bars.
+    fn fit_lines(&mut self) -> Vec<Line<'static>> {
+        if self.shown_fits.is_empty() || self.series.iter().any(|s| s.values.is_empty()) {
+            return Vec::new();
+        }
+        let fits = self.fits().to_vec();
+        let bins = self.bins();
+        let (lo, hi) = (
+            self.to_plot(bins[0].lower),
+            self.to_plot(bins[bins.len() - 1].upper),
+        );
+        let bin_width = self.average_bin_width();
+        let scale = self.scale();
+        let num_series = self.series.len();
+        let mut lines = Vec::new();
+        for (series, fits) in self.series.iter().zip(fits) {
+            let total = series.histogram.total as f64;
+            let height = self.normalization.height(total, total, bin_width);
+            for ((family, fit), color) in Family::ALL.iter().zip(fits).zip(colors::FIT_COLORS) {
+                let Some(fit) = fit.filter(|_| self.shown_fits.contains(family)) else {
+                    continue;
+                };
+                let points = (0..FIT_RESOLUTION)
+                    .map(|i| {
+                        let t = lo + (hi - lo) * i as f64 / (FIT_RESOLUTION - 1) as f64;
+                        let x = scale.inverse(t);
+                        let density = fit.distribution.pdf(x) / scale.stretch(x);
+                        [t, density * bin_width * height]
+                    })
+                    .filter(|[_, y]| y.is_finite())
+                    .collect::<Vec<_>>();
+                let name = if num_series > 1 {
+                    format!("{} {} fit", series.name, family.name())
+                } else {
+                    format!("{} fit", family.name())
+                };
+                lines.push(
+                    Line::new(points)
+                        .color(color)
+                        .width(2.0)
+                        .style(LineStyle::dashed_dense())
+                        .name(name),
+                );
+            }
+        }
+        lines
+    }
+
+    /// Every family fitted to each series, with its parameters and goodness of fit, and a
+    /// checkbox to draw it. The best fit of each series by Anderson–Darling is in bold.
+    fn fit_panel(&mut self, ui: &mut egui::Ui) {
+        if self.series.iter().any(|s| s.values.is_empty()) {
+            ui.label("Fitting needs the values, which streamed input does not keep.");
+            return;
+        }
+        let names = self
+            .series
+            .iter()
+            .map(|s| s.name.clone())
+            .collect::<Vec<_>>();
+        let fits = self.fits().to_vec();
+        for (series_idx, (name, fits)) in names.iter().zip(fits).enumerate() {
+            if names.len() > 1 {
+                ui.strong(name);
+            }
+            let best = fits
+                .iter()
+                .flatten()
+                .min_by(|a, b| a.anderson_darling.total_cmp(&b.anderson_darling))
+                .map(|fit| fit.distribution.family());
+            egui::Grid::new(("fit_grid", series_idx))
+                .striped(true)
+                .num_columns(5)
+                .show(ui, |ui| {
+                    ui.label("");
+                    ui.label("Parameters");
+                    ui.label("KS")
+                        .on_hover_text("Kolmogorov–Smirnov statistic D");
+                    ui.label("p").on_hover_text(
+                        "KS p-value, too high since the parameters come from the same values",
+                    );
+                    ui.label("A²").on_hover_text("Anderson–Darling statistic");
+                    ui.end_row();
+                    for ((family, fit), color) in
+                        Family::ALL.iter().zip(fits).zip(colors::FIT_COLORS)
+                    {
+                        let mut shown = self.shown_fits.contains(family);
+                        let label = egui::RichText::new(family.name()).color(color);
+                        let label = if best == Some(*family) {
+                            label.strong()
+                        } else {
+                            label
+                        };
+                        let Some(fit) = fit else {
+                            ui.add_enabled(false, egui::Checkbox::new(&mut shown, label));
+                            ui.label("outside its support");
+                            ui.end_row();
+                            continue;
+                        };
+                        if ui.checkbox(&mut shown, label).changed() {
+                            if shown {
+                                self.shown_fits.insert(*family);
+                            } else {
+                                self.shown_fits.remove(family);
                             }
-                            self.drag_start = None;
-                            self.drag_end = None;
                         }
+                        let params = fit
+                            .distribution
+                            .params()
+                            .iter()
+                            .map(|(name, value)| format!("{} = {:.4}", name, value))
+                            .collect::<Vec<_>>();
+                        ui.label(params.join(", "));
+                        ui.label(format!("{:.4}", fit.ks));
+                        ui.label(format!("{:.3}", fit.ks_p_value));
+                        ui.label(format!("{:.3}", fit.anderson_darling));
+                        ui.end_row();
                     }
+                });
+        }
+    }
 
-                    plot_ui.bar_chart(chart.width(width * 0.98));
-
-                    if let (Some(start), Some(end)) = (self.drag_start, self.drag_end) {
-                        plot_ui.polygon(egui_plot::Polygon::new(egui_plot::PlotPoints::from_iter(
-                            vec![
-                                [start.x, start.y],
-                                [end.x, start.y],
-                                [end.x, end.y],
-                                [start.x, end.y],
-                            ],
-                        )));
+    /// Checkbox, kernel, bandwidth rule and bandwidth slider for the density estimate.
+    fn kde_controls(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.kde.show, "KDE");
+        ui.add_enabled_ui(self.kde.show, |ui| {
+            egui::ComboBox::new("kde_kernel_combo", "")
+                .selected_text(format!("{:?}", self.kde.kernel))
+                .show_ui(ui, |ui| {
+                    for kernel in [Kernel::Gaussian, Kernel::Epanechnikov] {
+                        ui.selectable_value(&mut self.kde.kernel, kernel, format!("{:?}", kernel));
                     }
+                });
+            egui::ComboBox::new("kde_bandwidth_combo", "")
+                .selected_text(format!("{:?}", self.kde.rule))
+                .show_ui(ui, |ui| {
+                    for rule in [BandwidthRule::Silverman, BandwidthRule::Scott] {
+                        ui.selectable_value(&mut self.kde.rule, rule, format!("{:?}", rule));
+                    }
+                });
+            ui.add(
+                egui::Slider::new(&mut self.kde.factor, 0.1..=10.0)
+                    .logarithmic(true)
+                    .text("× bandwidth"),
+            );
+            if let [Some(bandwidth)] = self.bandwidths()[..] {
+                ui.label(format!("h = {:.4}", bandwidth));
+            }
+        });
+    }
 
-                    if let Some((_, x)) = self.p_25 {
-                        plot_ui.vline(
-                            egui_plot::VLine::new(x)
-                                .color(colors::PERCENTILE_25_COLOR)
-                                .name(format!("25 ptile: {:.4}", x)),
-                        );
+    /// Descriptive statistics and quantiles, one column per series, with a checkbox to draw
+    /// each quantile.
+    fn stats_panel(&mut self, ui: &mut egui::Ui) {
+        let num_series = self.series.len();
+        egui::Grid::new("stats_grid")
+            .striped(true)
+            .num_columns(num_series + 1)
+            .show(ui, |ui| {
+                if num_series > 1 {
+                    ui.label("");
+                    for series in &self.series {
+                        ui.strong(&series.name);
                     }
-                    if let Some((_, x)) = self.p_50 {
-                        plot_ui.vline(
-                            egui_plot::VLine::new(x)
-                                .color(colors::PERCENTILE_50_COLOR)
-                                .name(format!("50 ptile: {:.4}", x)),
-                        );
+                    ui.end_row();
+                }
+                let hists = self.series.iter().map(|s| &s.histogram).collect::<Vec<_>>();
+                let mut row = |name: &str, value: &dyn Fn(&data::Histogram) -> String| {
+                    ui.label(name);
+                    for hist in &hists {
+                        ui.label(value(hist));
                     }
-                    if let Some((_, x)) = self.p_75 {
-                        plot_ui.vline(
-                            egui_plot::VLine::new(x)
-                                .color(colors::PERCENTILE_75_COLOR)
-                                .name(format!("75 ptile: {:.4}", x)),
-                        );
+                    ui.end_row();
+                };
+                let stat = |value: fn(&data::Stats) -> f64| {
+                    move |hist: &data::Histogram| {
+                        hist.stats
+                            .as_ref()
+                            .map_or_else(String::new, |s| format!("{:.4}", value(s)))
                     }
+                };
+                row("Count", &|h| {
+                    h.stats.as_ref().map_or(h.total, |s| s.count).to_string()
                 });
-        });
+                row("Min", &stat(|s| s.min));
+                row("Max", &stat(|s| s.max));
+                row("Mean", &stat(|s| s.mean));
+                row("Std dev", &stat(|s| s.std_dev));
+                row("Skewness", &stat(|s| s.skewness));
+                row("Kurtosis", &stat(|s| s.kurtosis));
+                row("MAD", &|h| match h.stats.as_ref().map(|s| s.mad) {
+                    Some(Some(mad)) => format!("{:.4}", mad),
+                    Some(None) => "n/a".to_owned(),
+                    None => String::new(),
+                });
+                row("Empty", &|h| h.rejected.empty.to_string());
+                row("Not numbers", &|h| h.rejected.unparsed.to_string());
+                row("NaN", &|h| h.rejected.nan.to_string());
+                row("+inf", &|h| h.rejected.pos_inf.to_string());
+                row("-inf", &|h| h.rejected.neg_inf.to_string());
+                row("Outside bins", &|h| h.rejected.out_of_range.to_string());
+                row("No field", &|h| h.rejected.missing.to_string());
+
+                for (i, shown) in self.quantile_lines.iter_mut().enumerate() {
+                    let q = self.series[0].histogram.quantiles[i];
+                    ui.checkbox(shown, format!("p{}", q.percent()));
+                    for series in &self.series {
+                        ui.label(format!("{:.4}", series.histogram.quantiles[i].value));
+                    }
+                    ui.end_row();
+                }
+            });
+    }
 
+    /// Totals, skipped values and the selection, and writing out the selected lines.
+    fn status_bar(&mut self, ctx: &egui::Context) {
+        let num_series = self.series.len();
         egui::TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
             ui.horizontal(|ui| {
-                ui.label(format!("Total Points: {} |", self.total as usize));
+                self.live_status(ui);
+                if num_series > 1 {
+                    for series in &self.series {
+                        ui.label(format!("{}: {} |", series.name, series.histogram.total));
+                    }
+                } else {
+                    ui.label(format!(
+                        "Total Points: {} |",
+                        self.series[0].histogram.total
+                    ));
+                }
+                for series in &self.series {
+                    if let Some(distinct) = series.histogram.distinct {
+                        ui.label(if num_series > 1 {
+                            format!("{}: {} |", series.name, distinct)
+                        } else {
+                            format!("{} |", distinct)
+                        });
+                    }
+                }
+                let non_positive = self
+                    .series
+                    .iter()
+                    .map(|s| s.histogram.non_positive)
+                    .sum::<usize>();
+                if non_positive > 0 {
+                    ui.colored_label(
+                        ui.visuals().warn_fg_color,
+                        format!(
+                            "{} zero or negative values not shown on the log scale |",
+                            non_positive
+                        ),
+                    );
+                }
+                for series in &self.series {
+                    let rejected = &series.histogram.rejected;
+                    if rejected.total() > 0 {
+                        let text = if num_series > 1 {
+                            format!("{}: {} |", series.name, rejected)
+                        } else {
+                            format!("{} |", rejected)
+                        };
+                        ui.colored_label(ui.visuals().warn_fg_color, text);
+                    }
+                }
 
                 if let Some(selected_indices) = &self.selection {
-                    let selected_data: Vec<_> = self
-                        .data
-                        .iter()
-                        .enumerate()
-                        .filter(|(i, _)| selected_indices.contains(i))
-                        .map(|(_, (_, count))| *count)
+                    let selected_data: Vec<_> = (0..self.bins().len())
+                        .filter(|i| selected_indices.contains(i))
+                        .flat_map(|i| self.counts(i))
                         .collect();
 
-                    ui.label(format!("Selected bars: {} |", selected_data.len()));
+                    ui.label(format!("Selected bars: {} |", selected_indices.len()));
                     if let (Some(&min), Some(&max)) =
                         (selected_data.iter().min(), selected_data.iter().max())
                     {
                         ui.label(format!("Min count: {} |", min));
                         ui.label(format!("Max count: {} |", max));
                         ui.label(format!(
-                            "Total in selection: {}",
+                            "Total in selection: {} |",
                             selected_data.iter().copied().sum::<usize>()
                         ));
                     }
                 }
+                let can_write = self.selection.is_some() && self.has_lines();
+                if ui
+                    .add_enabled(can_write, egui::Button::new("Write selection"))
+                    .on_hover_text("Write the input lines in the selected bars (Enter)")
+                    .on_disabled_hover_text(if self.has_lines() {
+                        "Select bars to write their input lines"
+                    } else {
+                        "The input lines are not kept with --streaming"
+                    })
+                    .clicked()
+                    || (can_write && ui.input(|i| i.key_pressed(egui::Key::Enter)))
+                {
+                    self.write_selection();
+                }
+                match &self.emit_status {
+                    Some(Ok(status)) => {
+                        ui.label(status);
+                    }
+                    Some(Err(error)) => {
+                        ui.colored_label(ui.visuals().error_fg_color, error);
+                    }
+                    None => {}
+                }
+            });
+        });
+    }
+
+    /// Whether the bar `bar_idx`, as tall as the tallest series in `bar_y`, overlaps the
+    /// rectangle between `start` and `end`.
+    fn is_bar_in_rect(
+        &self,
+        bar_idx: usize,
+        bar_y: f64,
+        start: &egui_plot::PlotPoint,
+        end: &egui_plot::PlotPoint,
+    ) -> bool {
+        let bin = &self.bins()[bar_idx];
+
+        let rect_x_min = start.x.min(end.x);
+        let rect_x_max = start.x.max(end.x);
+        let rect_y_min = start.y.min(end.y);
+        let rect_y_max = start.y.max(end.y);
+
+        rect_x_max >= self.to_plot(bin.lower)
+            && rect_x_min <= self.to_plot(bin.upper)
+            && rect_y_max >= 0.0
+            && rect_y_min <= bar_y
+    }
+}
+
+fn write_lines(mut out: impl Write, lines: &[&str]) -> io::Result<()> {
+    for line in lines {
+        writeln!(out, "{}", line)?;
+    }
+    out.flush()
+}
+
+impl eframe::App for HistApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        if self.emit_on_close && ctx.input(|i| i.viewport().close_requested()) {
+            self.emit_on_close = false;
+            self.write_selection();
+            if let Some(Err(e)) = &self.emit_status {
+                eprintln!("hist3: {}", e);
+            }
+        }
+        self.refresh_live(ctx);
+        if self.series.is_empty() {
+            egui::TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
+                ui.horizontal(|ui| self.live_status(ui));
+            });
+            egui::CentralPanel::default().show(ctx, |ui| {
+                ui.centered_and_justified(|ui| ui.label("Waiting for input…"));
+            });
+            return;
+        }
+        let bins = self.bins();
+        let scale = self.scale();
+        let num_series = self.series.len();
+        let min_x = bins.first().map_or(0.0, |b| self.to_plot(b.lower));
+        let max_x = bins.last().map_or(0.0, |b| self.to_plot(b.upper));
+        let heights = self
+            .series
+            .iter()
+            .map(|s| s.histogram.heights(self.normalization))
+            .collect::<Vec<_>>();
+        // The tallest bar of each bin.
+        let bar_heights = (0..bins.len())
+            .map(|i| heights.iter().map(|h| h[i]).fold(0.0, f64::max))
+            .collect::<Vec<_>>();
+        let max_y = bar_heights.iter().copied().fold(0.0, f64::max);
+        let charts = self
+            .series
+            .iter()
+            .zip(&heights)
+            .enumerate()
+            .map(|(series_idx, (series, heights))| {
+                let fill = self
+                    .layout
+                    .bar_color(self.bar_color(series_idx), num_series);
+                let selected_fill = self
+                    .layout
+                    .bar_color(colors::SELECTED_BAR_COLOR, num_series);
+                let chart = BarChart::new(
+                    series
+                        .histogram
+                        .bins
+                        .iter()
+                        .enumerate()
+                        .map(|(i, bin)| {
+                            let (lower, upper) = self.layout.bar_span(
+                                self.to_plot(bin.lower),
+                                self.to_plot(bin.upper),
+                                series_idx,
+                                num_series,
+                            );
+                            let mid = (lower + upper) / 2.0;
+                            let position = if self.horizontal { self.flip(mid) } else { mid };
+                            let name = if bin.error > 0 {
+                                format!("{} (±{})", bin.label, bin.error)
+                            } else {
+                                bin.label.clone()
+                            };
+                            let mut bar = Bar::new(position, heights[i])
+                                .width(upper - lower)
+                                .name(name)
+                                .fill(fill);
+
+                            if let Some(selected_indices) = &self.selection {
+                                if selected_indices.contains(&i) {
+                                    bar = bar.fill(selected_fill);
+                                }
+                            }
+                            bar
+                        })
+                        .collect(),
+                );
+                let chart = if self.horizontal {
+                    chart.horizontal()
+                } else {
+                    chart
+                };
+                if num_series > 1 {
+                    chart.name(&series.name).color(fill)
+                } else {
+                    chart
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let categorical = self.series[0].histogram.is_categorical();
+        egui::TopBottomPanel::top("controls").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if categorical {
+                    self.normalization_selector(ui);
+                    return;
+                }
+                self.view_selector(ui);
+                ui.separator();
+                ui.add_enabled_ui(self.view == View::Bars, |ui| {
+                    self.normalization_selector(ui);
+                    ui.separator();
+                    self.kde_controls(ui);
+                });
+                ui.separator();
+                self.view_controls(ui);
+            });
+        });
+        if !categorical {
+            egui::SidePanel::right("stats").show(ctx, |ui| {
+                ui.heading("Statistics");
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    self.stats_panel(ui);
+                    ui.separator();
+                    egui::CollapsingHeader::new("Distribution fits")
+                        .default_open(!self.shown_fits.is_empty())
+                        .show(ui, |ui| self.fit_panel(ui));
+                });
+            });
+        }
+        if self.view != View::Bars && !categorical {
+            let steps = self.cdf_steps().to_vec();
+            let reset = std::mem::take(&mut self.reset_plot);
+            egui::CentralPanel::default().show(ctx, |ui| self.show_cdf(ui, steps, reset));
+            self.status_bar(ctx);
+            return;
+        }
+        let kde_lines = if self.kde.show && !categorical {
+            let curves = self.kde_curves().to_vec();
+            curves
+                .into_iter()
+                .zip(&self.series)
+                .enumerate()
+                .map(|(series_idx, (curve, series))| {
+                    let line = Line::new(curve).width(2.0);
+                    if num_series > 1 {
+                        line.color(self.bar_color(series_idx))
+                            .name(format!("{} KDE", series.name))
+                    } else {
+                        line.color(colors::KDE_COLOR).name("KDE")
+                    }
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let fit_lines = if categorical {
+            Vec::new()
+        } else {
+            self.fit_lines()
+        };
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            let mut plot = Plot::new("")
+                .allow_boxed_zoom(false)
+                .allow_drag(false)
+                .allow_scroll(false)
+                .legend(Legend::default())
+                .show_grid(self.grid)
+                .show_axes(self.axes)
+                .label_formatter(move |name, value| {
+                    if !name.is_empty() {
+                        name.to_owned()
+                    } else {
+                        format!("{:.1}", scale.inverse(value.x))
+                    }
+                });
+            plot = if self.horizontal {
+                plot.x_axis_label(self.normalization.name())
+            } else {
+                plot.x_axis_label(" ")
+                    .y_axis_label(self.normalization.name())
+            };
+            if std::mem::take(&mut self.reset_plot) {
+                plot = plot.reset();
+            }
+            if scale.is_log() {
+                plot = plot.x_axis_formatter(move |mark, _range| {
+                    render::tick_label(scale, mark.value, mark.step_size)
+                });
+            }
+            if categorical {
+                // Label the whole positions the bars are centred on.
+                let mut labels = self
+                    .bins()
+                    .iter()
+                    .map(|b| b.label.clone())
+                    .collect::<Vec<_>>();
+                if self.horizontal {
+                    labels.reverse();
+                }
+                let label_of = move |mark: egui_plot::GridMark, _: &_| {
+                    let position = mark.value.round();
+                    if (mark.value - position).abs() > 1e-9 || position < 0.0 {
+                        return String::new();
+                    }
+                    labels.get(position as usize).cloned().unwrap_or_default()
+                };
+                plot = if self.horizontal {
+                    plot.y_axis_formatter(label_of)
+                } else {
+                    plot.x_axis_formatter(label_of)
+                };
+            }
+            plot.show(ui, |plot_ui| {
+                if let Some(pointer) = plot_ui.pointer_coordinate() {
+                    let pointer = self.in_bins(pointer);
+                    let pointer = self.in_plot(egui_plot::PlotPoint {
+                        x: pointer.x.clamp(min_x, max_x),
+                        y: pointer.y.clamp(0.0, max_y),
+                    });
+                    if plot_ui.ctx().input(|i| i.pointer.primary_pressed()) {
+                        self.drag_start = Some(pointer);
+                        self.drag_end = Some(pointer);
+                    } else if plot_ui.ctx().input(|i| i.pointer.primary_down()) {
+                        self.drag_end = Some(pointer);
+                    } else if plot_ui.ctx().input(|i| i.pointer.primary_released()) {
+                        if let (Some(start), Some(end)) = (self.drag_start, self.drag_end) {
+                            let (start, end) = (self.in_bins(start), self.in_bins(end));
+                            let selected_bars: HashSet<usize> = (0..self.bins().len())
+                                .filter(|&i| self.is_bar_in_rect(i, bar_heights[i], &start, &end))
+                                .collect();
+
+                            if !selected_bars.is_empty() {
+                                self.selection = Some(selected_bars);
+                            } else {
+                                self.selection = None;
+                            }
+                        }
+                        self.drag_start = None;
+                        self.drag_end = None;
+                    }
+                }
+
+                for chart in charts {
+                    plot_ui.bar_chart(chart);
+                }
+                for line in kde_lines.into_iter().chain(fit_lines) {
+                    plot_ui.line(line);
+                }
+
+                if let (Some(start), Some(end)) = (self.drag_start, self.drag_end) {
+                    plot_ui.polygon(egui_plot::Polygon::new(egui_plot::PlotPoints::from_iter(
+                        vec![
+                            [start.x, start.y],
+                            [end.x, start.y],
+                            [end.x, end.y],
+                            [start.x, end.y],
+                        ],
+                    )));
+                }
+
+                for vline in self.quantile_vlines() {
+                    plot_ui.vline(vline);
+                }
             });
         });
+
+        self.status_bar(ctx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bins_conflict_with_other_ways_to_give_the_bins() {
+        for other in [["--edges", "0,1,5"], ["--bin-width", "2"]] {
+            let args = Args::try_parse_from(["hist3", "--bins", "10", other[0], other[1]]);
+            let kind = args.err().map(|e| e.kind());
+            assert_eq!(
+                kind,
+                Some(clap::error::ErrorKind::ArgumentConflict),
+                "{:?}",
+                other
+            );
+        }
+        assert!(Args::try_parse_from(["hist3", "--edges", "0,1,5"]).is_ok());
+    }
+
+    #[test]
+    fn selected_lines_are_written_without_emit_selection() {
+        let dir = std::env::temp_dir().join(format!("hist3-selection-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("input.txt");
+        std::fs::write(&input, "a 1\nb 2\nc 9\nd 10\n").unwrap();
+        let args = Args::try_parse_from([
+            "hist3",
+            "--column",
+            "2",
+            "--delimiter",
+            " ",
+            "--bins",
+            "2",
+            input.to_str().unwrap(),
+        ])
+        .unwrap();
+        assert!(args.emit_selection.is_none());
+        let options = histogram_options(&args, true);
+        let inputs = [(
+            "input".to_owned(),
+            InputSource::FileName(input.to_str().unwrap().to_owned()),
+        )];
+        let series = data::compute_series(&inputs, &options).unwrap();
+        let mut app = HistApp::new(series, options, BarLayout::Overlay, Vec::new());
+        assert!(!app.emit_on_close);
+        app.selection = Some(HashSet::from([1]));
+        let output = dir.join("selection.txt");
+        app.selection_output = Some(output.clone());
+        app.write_selection();
+        let written = std::fs::read_to_string(&output).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            app.emit_status,
+            Some(Ok(format!("Wrote 2 lines to {}", output.display())))
+        );
+        assert_eq!(written, "c 9\nd 10\n");
     }
 }
@@ -3,15 +3,23 @@
 extern crate egui_plot;
 
 use atty::Stream;
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use eframe::egui;
-use egui_plot::{CoordinatesFormatter, Corner, Legend, Line, Plot, PlotPoints};
-use hist3::data::InputSource;
+use egui_plot::{
+    AxisHints, CoordinatesFormatter, Corner, GridInput, GridMark, HPlacement, Legend, Line, Plot,
+    PlotPoint, PlotPoints, PlotUi, Polygon,
+};
+use hist3::data::{
+    format_timestamp, lossy_lines, parse_delimiter, split_fields, split_logfmt, InputSource,
+    Pipeline, TimeFormat, Transform, Transformed,
+};
 use hist3::NUMERIC_REGEX;
+use std::collections::HashMap;
 use std::fs::File;
-use std::io::BufRead;
 use std::path::Path;
+use std::str::FromStr;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use std::{io, thread};
 
 #[derive(clap::Parser, Debug)]
@@ -27,6 +35,82 @@ struct Args {
     /// Series Names
     #[arg(short, long)]
     series: Vec<String>,
+
+    /// Plot the series against this field instead of the line number. Fields are numbered from
+    /// 0 and separated by whitespace or commas, or by the delimiter. With --header or --logfmt,
+    /// they can also be named
+    #[arg(long, value_name = "FIELD")]
+    x_column: Option<Field>,
+
+    /// Read the x field as a time: `epoch`, `epoch-ms`, `epoch-us`, `epoch-ns`, `iso8601`, or a
+    /// strftime pattern such as `%Y-%m-%dT%H:%M:%S%.f%z`. Times without an offset are in UTC
+    #[arg(long, value_name = "FORMAT", requires = "x_column")]
+    time_format: Option<TimeFormat>,
+
+    /// Split lines into fields at this character (or `tab`), and plot each field as a series.
+    /// Defaults to a tab if the first line contains one, otherwise a comma, when --header or
+    /// --fields is given
+    #[arg(long, short, value_parser = parse_delimiter)]
+    delimiter: Option<char>,
+
+    /// Name the series after the fields of the first line
+    #[arg(long)]
+    header: bool,
+
+    /// Plot only these fields, by number or, with --header or --logfmt, by name
+    #[arg(long, short, value_delimiter = ',', value_name = "FIELD")]
+    fields: Vec<Field>,
+
+    /// Read `key=value` pairs, and plot the numeric value of each key as a series named after it
+    #[arg(long, conflicts_with_all = ["delimiter", "header"])]
+    logfmt: bool,
+
+    /// Draw a series, given by name or number, against a y axis of its own on the right, or on a
+    /// plot of its own under the others: `left`, `right` or `stacked`, e.g. `ratio=right` or
+    /// `2=stacked`
+    #[arg(long, value_name = "SERIES=PLACEMENT", value_parser = parse_series_placement)]
+    axis: Vec<(String, Placement)>,
+}
+
+/// Where a series is drawn.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+enum Placement {
+    /// On the main plot, against the left axis
+    #[default]
+    Left,
+    /// On the main plot, against an axis of its own on the right
+    Right,
+    /// On a plot of its own under the main plot, with the same x axis
+    Stacked,
+}
+
+/// Parses `SERIES=PLACEMENT`.
+fn parse_series_placement(s: &str) -> Result<(String, Placement), String> {
+    let (series, placement) = s
+        .rsplit_once('=')
+        .ok_or_else(|| format!("expected SERIES=PLACEMENT, got {:?}", s))?;
+    Ok((series.to_owned(), Placement::from_str(placement, true)?))
+}
+
+/// A field of the input: by its position, counting from 0, or by its name in the header row or
+/// its key in `key=value` pairs.
+#[derive(Clone, Debug, PartialEq)]
+enum Field {
+    Index(usize),
+    Name(String),
+}
+
+impl FromStr for Field {
+    type Err = String;
+
+    /// Parses a number as a position and anything else as a name.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<usize>() {
+            Ok(index) => Ok(Field::Index(index)),
+            Err(_) if s.is_empty() => Err("field name is empty".to_owned()),
+            Err(_) => Ok(Field::Name(s.to_owned())),
+        }
+    }
 }
 
 fn main() -> Result<(), eframe::Error> {
@@ -35,9 +119,38 @@ fn main() -> Result<(), eframe::Error> {
     let plot = PlotApp::default()
         .set_series_names(args.series.clone())
         .set_grid(true)
-        .set_axes(true);
+        .set_axes(true)
+        .set_time_axis(args.time_format.is_some())
+        .set_placements(args.axis.clone());
     let data_ref = plot.data.clone();
     let title = args.title.clone();
+    let layout = if args.logfmt {
+        Layout::Logfmt
+    } else if args.delimiter.is_some() || args.header || !args.fields.is_empty() {
+        Layout::Delimited {
+            delimiter: args.delimiter,
+            header: args.header,
+        }
+    } else {
+        Layout::Numbers
+    };
+    let fields = || args.fields.iter().chain(&args.x_column);
+    let named = |field: &Field| matches!(field, Field::Name(_));
+    if args.logfmt && !fields().all(named) {
+        eprintln!("plot3: with --logfmt, fields are given by their key");
+        std::process::exit(1);
+    }
+    if !(args.logfmt || args.header) && fields().any(named) {
+        eprintln!("plot3: fields can only be given by name with --header or --logfmt");
+        std::process::exit(1);
+    }
+    let mut parser = LineParser {
+        layout,
+        x_column: args.x_column.clone(),
+        fields: args.fields.clone(),
+        time_format: args.time_format.clone(),
+        columns: None,
+    };
 
     thread::spawn(move || {
         let input = if !atty::is(Stream::Stdin) {
@@ -55,23 +168,20 @@ fn main() -> Result<(), eframe::Error> {
 
         match input {
             InputSource::Stdin => {
-                let reader = std::io::stdin();
-                for line in reader.lines() {
-                    if let Ok(line) = line {
-                        process_line(&data_ref, line);
-                    }
+                let reader = std::io::stdin().lock();
+                for line in lossy_lines(reader).map_while(Result::ok) {
+                    process_line(&data_ref, &mut parser, line);
                 }
             }
             InputSource::FileName(file_name) => {
                 let file = File::open(file_name).unwrap();
                 let reader = io::BufReader::new(file);
-                for line in reader.lines() {
-                    if let Ok(line) = line {
-                        process_line(&data_ref, line);
-                    }
+                for line in lossy_lines(reader).map_while(Result::ok) {
+                    process_line(&data_ref, &mut parser, line);
                 }
             }
         };
+        data_ref.lock().unwrap().done = true;
     });
 
     let options = eframe::NativeOptions {
@@ -83,36 +193,390 @@ fn main() -> Result<(), eframe::Error> {
     eframe::run_native(title.as_str(), options, Box::new(|_| Ok(Box::new(plot))))
 }
 
-fn process_line(data_ref: &Arc<Mutex<Vec<Vec<f64>>>>, line: String) {
-    let floats = NUMERIC_REGEX
-        .captures_iter(&line)
-        .map(|cap| cap[0].parse::<f64>().unwrap())
-        .collect::<Vec<_>>();
-    if floats.len() > 0 {
-        data_ref.lock().unwrap().push(floats);
+fn process_line(data_ref: &Arc<Mutex<Table>>, parser: &mut LineParser, line: String) {
+    let mut table = data_ref.lock().unwrap();
+    if let Some(row) = parser.parse(&line, &mut table) {
+        table.rows.push(row);
+    }
+}
+
+/// The rows read so far, and the names of the series their values belong to.
+#[derive(Default)]
+struct Table {
+    /// The series, in the order of their values in each row.
+    names: Vec<String>,
+    ids: HashMap<String, usize>,
+    rows: Vec<Row>,
+    /// Lines left out because their x field is missing or cannot be read, or because they have
+    /// none of the values to plot.
+    skipped: usize,
+    /// Whether the whole input has been read.
+    done: bool,
+}
+
+impl Table {
+    /// The position of the series `name` in the values of each row, which is added if it is new.
+    fn series_id(&mut self, name: &str) -> usize {
+        if let Some(&id) = self.ids.get(name) {
+            return id;
+        }
+        let id = self.names.len();
+        self.names.push(name.to_owned());
+        self.ids.insert(name.to_owned(), id);
+        id
+    }
+}
+
+/// One input line: where it goes on the x axis, and the values of the series. A series without a
+/// value on the line has a NaN, or no value at all past the end of `values`.
+struct Row {
+    x: f64,
+    values: Vec<f64>,
+}
+
+impl Row {
+    fn new(x: f64) -> Self {
+        Row {
+            x,
+            values: Vec::new(),
+        }
+    }
+
+    fn set(&mut self, id: usize, value: f64) {
+        if self.values.len() <= id {
+            self.values.resize(id + 1, f64::NAN);
+        }
+        self.values[id] = value;
+    }
+
+    fn get(&self, id: usize) -> f64 {
+        self.values.get(id).copied().unwrap_or(f64::NAN)
+    }
+}
+
+/// How the values are laid out on each line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Layout {
+    /// Any numbers, taken in order.
+    Numbers,
+    /// Fields between delimiters, possibly under a header row naming them.
+    Delimited {
+        delimiter: Option<char>,
+        header: bool,
+    },
+    /// `key=value` pairs, each key naming a series.
+    Logfmt,
+}
+
+/// The columns of delimited input, resolved from its first line.
+struct Columns {
+    delimiter: char,
+    /// The header row, if there is one.
+    names: Vec<String>,
+    x: Option<usize>,
+    /// The columns to plot, or all but `x` if empty.
+    selected: Vec<usize>,
+}
+
+/// How the x value and the series values are taken out of a line.
+struct LineParser {
+    layout: Layout,
+    x_column: Option<Field>,
+    fields: Vec<Field>,
+    time_format: Option<TimeFormat>,
+    /// Set from the first line of delimited input.
+    columns: Option<Columns>,
+}
+
+impl LineParser {
+    /// The row read from the next line, or `None` if the line holds no values, is the header, or
+    /// its x field cannot be read. Series seen for the first time are added to `table`, and lines
+    /// that are not blank but give no row are counted in its `skipped`.
+    fn parse(&mut self, line: &str, table: &mut Table) -> Option<Row> {
+        let row = match self.layout {
+            Layout::Numbers => self.parse_numbers(line, table),
+            Layout::Delimited { delimiter, header } => {
+                if self.columns.is_none() {
+                    self.columns = Some(self.resolve(line, delimiter, header, table));
+                    if header {
+                        return None;
+                    }
+                }
+                self.parse_delimited(line, table)
+            }
+            Layout::Logfmt => self.parse_logfmt(line, table),
+        };
+        let row = row.filter(|row| !row.values.is_empty());
+        if row.is_none() && !line.trim().is_empty() {
+            table.skipped += 1;
+        }
+        row
+    }
+
+    fn x_value(&self, field: &str) -> Option<f64> {
+        match &self.time_format {
+            Some(format) => format.parse(field),
+            None => field.trim().parse().ok(),
+        }
+    }
+
+    fn parse_numbers(&self, line: &str, table: &mut Table) -> Option<Row> {
+        let numbers = |text: &str| {
+            NUMERIC_REGEX
+                .captures_iter(text)
+                .map(|cap| cap[0].parse::<f64>().unwrap())
+                .collect::<Vec<_>>()
+        };
+        let (x, values) = match self.x_column {
+            Some(Field::Index(column)) => {
+                let start = field_start(line, column)?;
+                let field = &line[start..];
+                let (x, len) = match &self.time_format {
+                    Some(format) => format.parse_prefix(field)?,
+                    None => NUMERIC_REGEX
+                        .find(field)
+                        .filter(|m| m.start() == 0)
+                        .map(|m| (m.as_str().parse::<f64>().unwrap(), m.end()))?,
+                };
+                // The x field is left out, so that the digits of a timestamp are not read as
+                // values.
+                let mut values = numbers(&line[..start]);
+                values.extend(numbers(&field[len..]));
+                (x, values)
+            }
+            // Fields are only named with a header or logfmt.
+            _ => (table.rows.len() as f64, numbers(line)),
+        };
+        let mut row = Row::new(x);
+        for (i, value) in values.into_iter().enumerate() {
+            row.set(table.series_id(&i.to_string()), value);
+        }
+        Some(row)
+    }
+
+    /// Picks the delimiter and finds the columns given by name, from the first line. The series
+    /// asked for with `--fields` are added to `table` in that order.
+    fn resolve(
+        &self,
+        line: &str,
+        delimiter: Option<char>,
+        header: bool,
+        table: &mut Table,
+    ) -> Columns {
+        let delimiter = delimiter.unwrap_or(if line.contains('\t') { '\t' } else { ',' });
+        let names = if header {
+            split_fields(line, delimiter)
+        } else {
+            Vec::new()
+        };
+        let index = |field: &Field| match field {
+            Field::Index(index) => *index,
+            Field::Name(name) => names.iter().position(|n| n == name).unwrap_or_else(|| {
+                eprintln!("plot3: the header has no field named {:?}", name);
+                std::process::exit(1);
+            }),
+        };
+        let x = self.x_column.as_ref().map(index);
+        let selected = self.fields.iter().map(index).collect::<Vec<_>>();
+        for &column in &selected {
+            table.series_id(&column_name(&names, column));
+        }
+        Columns {
+            delimiter,
+            names,
+            x,
+            selected,
+        }
+    }
+
+    fn parse_delimited(&self, line: &str, table: &mut Table) -> Option<Row> {
+        let columns = self.columns.as_ref()?;
+        let fields = split_fields(line, columns.delimiter);
+        let x = match columns.x {
+            Some(x) => self.x_value(fields.get(x)?)?,
+            None => table.rows.len() as f64,
+        };
+        let all = (0..fields.len())
+            .filter(|&i| Some(i) != columns.x)
+            .collect::<Vec<_>>();
+        let selected = if columns.selected.is_empty() {
+            &all
+        } else {
+            &columns.selected
+        };
+        let mut row = Row::new(x);
+        for &column in selected {
+            // A missing or non-numeric field is a gap in its series.
+            if let Some(value) = fields.get(column).and_then(|field| number(field)) {
+                row.set(table.series_id(&column_name(&columns.names, column)), value);
+            }
+        }
+        Some(row)
+    }
+
+    fn parse_logfmt(&self, line: &str, table: &mut Table) -> Option<Row> {
+        if table.rows.is_empty() {
+            for field in &self.fields {
+                if let Field::Name(key) = field {
+                    table.series_id(key);
+                }
+            }
+        }
+        let pairs = split_logfmt(line);
+        let x_key = match &self.x_column {
+            Some(Field::Name(key)) => Some(key),
+            _ => None,
+        };
+        let x = match x_key {
+            Some(x_key) => self.x_value(&pairs.iter().find(|(key, _)| key == x_key)?.1)?,
+            None => table.rows.len() as f64,
+        };
+        let mut row = Row::new(x);
+        for (key, value) in &pairs {
+            let wanted = self.fields.is_empty() || self.fields.contains(&Field::Name(key.clone()));
+            if Some(key) == x_key || !wanted {
+                continue;
+            }
+            if let Some(value) = number(value) {
+                row.set(table.series_id(key), value);
+            }
+        }
+        Some(row)
+    }
+}
+
+/// The name of a column in the header row, or its number if there is none.
+fn column_name(names: &[String], column: usize) -> String {
+    names
+        .get(column)
+        .cloned()
+        .unwrap_or_else(|| column.to_string())
+}
+
+/// A whole field read as a finite number.
+fn number(field: &str) -> Option<f64> {
+    field.trim().parse::<f64>().ok().filter(|v| v.is_finite())
+}
+
+/// The byte offset at which the `index`th field of `line` starts, counting from 0, with runs of
+/// whitespace and commas between fields.
+fn field_start(line: &str, index: usize) -> Option<usize> {
+    let mut fields = 0;
+    let mut in_field = false;
+    for (i, c) in line.char_indices() {
+        if c.is_whitespace() || c == ',' {
+            in_field = false;
+        } else if !in_field {
+            if fields == index {
+                return Some(i);
+            }
+            fields += 1;
+            in_field = true;
+        }
+    }
+    None
+}
+
+/// Steps between the grid lines of a time axis, in seconds, from a second to a year.
+const TIME_STEPS: [f64; 21] = [
+    1.0,
+    2.0,
+    5.0,
+    10.0,
+    15.0,
+    30.0,
+    60.0,
+    2.0 * 60.0,
+    5.0 * 60.0,
+    10.0 * 60.0,
+    15.0 * 60.0,
+    30.0 * 60.0,
+    3600.0,
+    2.0 * 3600.0,
+    3.0 * 3600.0,
+    6.0 * 3600.0,
+    12.0 * 3600.0,
+    86400.0,
+    2.0 * 86400.0,
+    7.0 * 86400.0,
+    30.0 * 86400.0,
+];
+
+/// Grid lines of a time axis at whole seconds, minutes, hours or days, falling on midnight UTC,
+/// or at decimal fractions of a second.
+fn time_grid(input: GridInput) -> Vec<GridMark> {
+    let (min, max) = input.bounds;
+    // Zoomed in far enough, steps smaller than the precision of the timestamps would not move
+    // from one grid line to the next.
+    let precision = min.abs().max(max.abs()) * f64::EPSILON * 4.0;
+    let target = ((max - min) / 8.0).max(input.base_step_size).max(precision);
+    if !target.is_finite() || target <= 0.0 {
+        return Vec::new();
+    }
+    let decimal = |target: f64| {
+        let power = 10f64.powf(target.log10().floor());
+        [1.0, 2.0, 5.0, 10.0]
+            .iter()
+            .map(|m| m * power)
+            .find(|&step| step >= target)
+            .unwrap()
+    };
+    let step = if target < 1.0 {
+        decimal(target)
+    } else if target > TIME_STEPS[TIME_STEPS.len() - 1] {
+        decimal(target / 86400.0) * 86400.0
+    } else {
+        TIME_STEPS
+            .iter()
+            .copied()
+            .find(|&step| step >= target)
+            .unwrap()
+    };
+    ((min / step).ceil() as i64..=(max / step).floor() as i64)
+        .map(|k| GridMark {
+            value: k as f64 * step,
+            step_size: step,
+        })
+        .collect()
+}
+
+/// The label of a grid line on a time axis. Below a minute between lines, only the time
+/// of day is shown.
+fn time_tick(mark: GridMark) -> String {
+    let label = format_timestamp(mark.value, mark.step_size);
+    match label.split_once(' ') {
+        Some((_, time)) if mark.step_size < 60.0 => time.to_owned(),
+        _ => label,
     }
 }
 
 struct PlotApp {
-    data: Arc<Mutex<Vec<Vec<f64>>>>,
+    data: Arc<Mutex<Table>>,
     grid: bool,
     axes: bool,
-    cums: Vec<bool>,
+    time_axis: bool,
+    transforms: Vec<Vec<Transform>>,
     zscore: Vec<bool>,
-    box_width: Vec<usize>,
+    placements: Vec<Placement>,
+    /// Placements asked for on the command line, by series name or number.
+    initial_placements: Vec<(String, Placement)>,
     series_names: Vec<String>,
+    caches: Vec<SeriesCache>,
 }
 
 impl Default for PlotApp {
     fn default() -> Self {
         Self {
-            data: Arc::new(Mutex::new(Vec::new())),
+            data: Arc::new(Mutex::new(Table::default())),
             grid: false,
             axes: false,
-            cums: Vec::new(),
+            time_axis: false,
+            transforms: Vec::new(),
             zscore: Vec::new(),
-            box_width: Vec::new(),
+            placements: Vec::new(),
+            initial_placements: Vec::new(),
             series_names: Vec::new(),
+            caches: Vec::new(),
         }
     }
 }
@@ -128,6 +592,16 @@ impl PlotApp {
         self
     }
 
+    fn set_time_axis(mut self, time_axis: bool) -> Self {
+        self.time_axis = time_axis;
+        self
+    }
+
+    fn set_placements(mut self, placements: Vec<(String, Placement)>) -> Self {
+        self.initial_placements = placements;
+        self
+    }
+
     fn set_series_names(mut self, series_names: Vec<String>) -> Self {
         self.series_names = series_names;
         self
@@ -136,141 +610,629 @@ impl PlotApp {
 
 impl eframe::App for PlotApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        if self.data.lock().unwrap().len() == 0 {
+        let table = self.data.lock().unwrap();
+        if !table.done {
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+        let skipped = table.skipped;
+        if table.rows.is_empty() {
+            if table.done && skipped > 0 {
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    ui.label(format!("None of the {} lines have values to plot", skipped));
+                });
+            }
             return;
         }
-        let num_series = self.data.lock().unwrap()[0].len();
-        while self.cums.len() < num_series {
-            self.cums.push(false);
+        let num_series = table.names.len();
+        let names = (0..num_series)
+            .map(|i| self.series_names.get(i).unwrap_or(&table.names[i]).clone())
+            .collect::<Vec<_>>();
+        while self.transforms.len() < num_series {
+            let i = self.transforms.len();
+            self.transforms.push(Vec::new());
             self.zscore.push(false);
-            self.box_width.push(1);
+            let placement = self
+                .initial_placements
+                .iter()
+                .rev()
+                .find(|(series, _)| *series == names[i] || *series == i.to_string())
+                .map_or(Placement::Left, |(_, placement)| *placement);
+            self.placements.push(placement);
+        }
+        // Only the rows that arrived since the last frame go through the transforms, and the
+        // reader is not held up while drawing.
+        for (i, transforms) in self.transforms.iter().enumerate() {
+            match self.caches.get_mut(i) {
+                Some(cache) if cache.transforms == *transforms => {}
+                Some(cache) => *cache = SeriesCache::new(transforms),
+                None => self.caches.push(SeriesCache::new(transforms)),
+            }
+            self.caches[i].update(&table.rows, i, self.zscore[i], table.done);
         }
+        drop(table);
         egui::CentralPanel::default().show(ctx, |ui| {
+            if skipped > 0 {
+                ui.label(format!("{} lines skipped", skipped))
+                    .on_hover_text(
+                    "Lines whose x field is missing or cannot be read, or with none of the values",
+                );
+            }
             ui.vertical(|ui| {
-                for i in 0..num_series {
+                for (i, name) in names.iter().enumerate() {
                     ui.with_layout(egui::Layout::top_down(egui::Align::RIGHT), |ui| {
                         ui.group(|ui| {
                             ui.horizontal(|ui| {
-                                ui.checkbox(&mut self.cums[i], "Cumulative");
-                                ui.add(
-                                    egui::DragValue::new(&mut self.box_width[i]).range(1..=50000),
-                                );
-                                ui.label("Averaging");
+                                transform_controls(ui, i, &mut self.transforms[i]);
+                                ui.separator();
+                                let placement = &mut self.placements[i];
+                                ui.selectable_value(placement, Placement::Left, "Left")
+                                    .on_hover_text("Against the left axis");
+                                ui.selectable_value(placement, Placement::Right, "Right")
+                                    .on_hover_text("Against an axis of its own on the right");
+                                ui.selectable_value(placement, Placement::Stacked, "Stacked")
+                                    .on_hover_text("On a plot of its own below");
+                                ui.separator();
                                 ui.checkbox(&mut self.zscore[i], "Z-Score");
-                                ui.heading(format!(
-                                    "{}",
-                                    self.series_names.get(i).unwrap_or(&format!("{}", i))
-                                ));
+                                ui.heading(name);
                             })
                         })
                     });
                 }
             });
+            self.plots(ui, &names);
+        });
+    }
+}
 
-            let mut plot = Plot::new("")
-                .allow_boxed_zoom(true)
-                .allow_drag(false)
-                .legend(Legend::default())
-                .show_grid(self.grid)
-                .show_axes(self.axes);
-            plot = plot.coordinates_formatter(Corner::LeftBottom, CoordinatesFormatter::default());
+impl PlotApp {
+    /// Draws the series on the left and right axes on the main plot, and each stacked series on
+    /// a plot of its own below it, splitting the height between them.
+    fn plots(&self, ui: &mut egui::Ui, names: &[String]) {
+        let on = |placement: Placement| {
+            (0..names.len())
+                .filter(|&i| self.placements[i] == placement)
+                .collect::<Vec<_>>()
+        };
+        let (left, right, stacked) = (
+            on(Placement::Left),
+            on(Placement::Right),
+            on(Placement::Stacked),
+        );
+        let has_main = !(left.is_empty() && right.is_empty());
+        let panels = usize::from(has_main) + stacked.len();
+        let spacing = ui.spacing().item_spacing.y * panels.saturating_sub(1) as f32;
+        let height = ((ui.available_height() - spacing) / panels as f32).max(60.0);
+
+        if has_main {
+            let y_range = |series: &[usize]| {
+                series
+                    .iter()
+                    .filter_map(|&i| self.caches[i].y_range())
+                    .reduce(|(low, high), (l, h)| (low.min(l), high.max(h)))
+            };
+            let axis = RightAxis::new(y_range(&left), y_range(&right));
+            let right_names = right.iter().map(|&i| names[i].clone()).collect::<Vec<_>>();
+            let plot = self.plot(
+                0,
+                height,
+                (!right.is_empty()).then_some((axis, right_names)),
+            );
             plot.show(ui, |plot_ui| {
-                for i in 0..num_series {
-                    plot_ui.line(
-                        Line::new(PlotPoints::from_ys_f64(&make_series(
-                            &self.data.lock().unwrap(),
-                            i,
-                            self.box_width[i],
-                            self.cums[i],
-                            self.zscore[i],
-                        )))
-                        .name(format!(
-                            "{}",
-                            self.series_names.get(i).unwrap_or(&format!("{}", i))
-                        )),
-                    )
+                for &i in &left {
+                    self.draw_series(plot_ui, i, &names[i], |y| y);
+                }
+                for &i in &right {
+                    self.draw_series(plot_ui, i, &names[i], |y| axis.to_left(y));
                 }
             });
-        });
+        }
+        for (panel, &i) in stacked.iter().enumerate() {
+            self.plot(panel + 1, height, None).show(ui, |plot_ui| {
+                self.draw_series(plot_ui, i, &names[i], |y| y);
+            });
+        }
     }
-}
 
-fn make_series(
-    data: &Vec<Vec<f64>>,
-    series_idx: usize,
-    width: usize,
-    cumulative: bool,
-    normalize: bool,
-) -> Vec<f64> {
-    // let min = data
-    //     .iter()
-    //     .map(|v| v[series_idx])
-    //     .min_by(|a, b| a.total_cmp(b))
-    //     .unwrap();
-    // let max = data
-    //     .iter()
-    //     .map(|v| v[series_idx])
-    //     .max_by(|a, b| a.total_cmp(b))
-    //     .unwrap();
-    // let range = max - min;
-    let vv = data.iter().map(|v| v[series_idx]).collect::<Vec<_>>();
-    let (mean, std) = mean_std(&vv);
-    data.iter()
-        .enumerate()
-        .map(|(i, _)| {
-            if width > 1 {
-                let start = if i >= width / 2 { i - width / 2 } else { 0 };
-                let end = std::cmp::min(data.len(), i + width / 2 + 1);
-                let sum: f64 = data[start..end]
-                    .iter()
-                    .map(|v| {
-                        if normalize {
-                            ((v[series_idx] - mean) / std) * 2.0 - 1.0
-                        } else {
-                            v[series_idx]
-                        }
-                    })
-                    .sum();
-                let count = end - start;
-                sum / count as f64
-            } else {
-                if normalize {
-                    ((data[i][series_idx] - mean) / std) * 2.0 - 1.0
+    /// The `panel`th plot, with its x axis linked to the others. `right` gives the scale of the
+    /// right axis and the series drawn against it, if there are any.
+    fn plot(
+        &self,
+        panel: usize,
+        height: f32,
+        right: Option<(RightAxis, Vec<String>)>,
+    ) -> Plot<'static> {
+        let mut plot = Plot::new(("plot3", panel))
+            .height(height)
+            .allow_boxed_zoom(true)
+            .allow_drag(false)
+            .link_axis("plot3_x", [true, false])
+            .link_cursor("plot3_x", [true, false])
+            .legend(Legend::default())
+            .show_grid(self.grid)
+            .show_axes(self.axes);
+        if self.time_axis {
+            let time = |point: &PlotPoint| format_timestamp(point.x, 0.001);
+            plot = plot
+                .x_grid_spacer(time_grid)
+                .x_axis_formatter(|mark, _| time_tick(mark))
+                .coordinates_formatter(
+                    Corner::LeftBottom,
+                    CoordinatesFormatter::new(move |point, _| {
+                        format!("{}  y = {:.4}", time(point), point.y)
+                    }),
+                );
+        } else {
+            plot = plot.coordinates_formatter(Corner::LeftBottom, CoordinatesFormatter::default());
+        }
+        if self.time_axis || right.is_some() {
+            let time_axis = self.time_axis;
+            let right = right.clone();
+            plot = plot.label_formatter(move |name, point| {
+                let x = if time_axis {
+                    format_timestamp(point.x, 0.001)
                 } else {
-                    data[i][series_idx]
+                    format!("x = {:.4}", point.x)
+                };
+                // Series on the right axis are drawn scaled to the left one.
+                let y = match &right {
+                    Some((axis, names)) if names.iter().any(|n| n == name) => {
+                        axis.to_right(point.y)
+                    }
+                    _ => point.y,
+                };
+                format!("{}\n{}\ny = {:.4}", name, x, y)
+            });
+        }
+        if let Some((axis, names)) = right {
+            plot = plot.custom_y_axes(vec![
+                AxisHints::new_y(),
+                AxisHints::new_y()
+                    .placement(HPlacement::Right)
+                    .label(names.join(", "))
+                    .formatter(move |mark, _| {
+                        let value = axis.to_right(mark.value);
+                        let decimals = -(mark.step_size / axis.scale).log10().round();
+                        egui::emath::format_with_decimals_in_range(
+                            value,
+                            decimals.max(0.0) as usize..=decimals.max(0.0) as usize,
+                        )
+                    }),
+            ]);
+        }
+        plot
+    }
+
+    /// Draws the `index`th series, with `map` applied to its values.
+    fn draw_series(
+        &self,
+        plot_ui: &mut PlotUi,
+        index: usize,
+        name: &str,
+        map: impl Fn(f64) -> f64,
+    ) {
+        let columns = plot_ui.transform().frame().width().max(1.0) as usize;
+        let visible = if plot_ui.auto_bounds().x {
+            None
+        } else {
+            let bounds = plot_ui.plot_bounds();
+            Some((bounds.min()[0], bounds.max()[0]))
+        };
+        let points = self.caches[index].points(visible, columns);
+        // The band is drawn a slice at a time, as polygons have to be convex.
+        let fill = series_color(index).gamma_multiply(0.3);
+        for pair in points.band.windows(2) {
+            let ([x0, bottom0, top0], [x1, bottom1, top1]) = (pair[0], pair[1]);
+            let (bottom0, top0, bottom1, top1) = (map(bottom0), map(top0), map(bottom1), map(top1));
+            if [bottom0, top0, bottom1, top1].iter().all(|v| v.is_finite()) {
+                plot_ui.polygon(
+                    Polygon::new(PlotPoints::new(vec![
+                        [x0, bottom0],
+                        [x1, bottom1],
+                        [x1, top1],
+                        [x0, top0],
+                    ]))
+                    .name(name)
+                    .fill_color(fill)
+                    .width(0.0)
+                    .allow_hover(false),
+                );
+            }
+        }
+        // Missing values leave gaps, with the pieces of the line sharing a color and a legend
+        // entry.
+        for piece in points.line.split(|point| point[1].is_nan()) {
+            if !piece.is_empty() {
+                let piece = piece.iter().map(|&[x, y]| [x, map(y)]).collect::<Vec<_>>();
+                plot_ui.line(
+                    Line::new(PlotPoints::new(piece))
+                        .name(name)
+                        .color(series_color(index)),
+                );
+            }
+        }
+    }
+}
+
+/// How the values of the series on the right axis are scaled to be drawn against the left one,
+/// so that the ranges of the two line up.
+#[derive(Clone, Copy, Debug)]
+struct RightAxis {
+    scale: f64,
+    offset: f64,
+}
+
+impl RightAxis {
+    /// Lines up the `right` range of values with the `left` one, or leaves the values as they are
+    /// if either is missing or empty.
+    fn new(left: Option<(f64, f64)>, right: Option<(f64, f64)>) -> Self {
+        match (left, right) {
+            (Some((l0, l1)), Some((r0, r1))) if l1 > l0 && r1 > r0 && (l1 - l0).is_finite() => {
+                let scale = (l1 - l0) / (r1 - r0);
+                RightAxis {
+                    scale,
+                    offset: l0 - r0 * scale,
                 }
             }
-        })
-        .scan(0.0, |cum, v| {
-            if cumulative {
-                *cum += v;
-                Some(*cum)
-            } else {
-                Some(v)
+            _ => RightAxis {
+                scale: 1.0,
+                offset: 0.0,
+            },
+        }
+    }
+
+    fn to_left(self, y: f64) -> f64 {
+        y * self.scale + self.offset
+    }
+
+    fn to_right(self, y: f64) -> f64 {
+        (y - self.offset) / self.scale
+    }
+}
+
+/// The controls for the transforms of a series, each with its parameters and a button to remove
+/// it, followed by a menu to add another.
+fn transform_controls(ui: &mut egui::Ui, series: usize, transforms: &mut Vec<Transform>) {
+    let mut removed = None;
+    for (i, transform) in transforms.iter_mut().enumerate() {
+        ui.label(transform.name());
+        match transform {
+            Transform::Ewma { alpha } => {
+                ui.add(
+                    egui::DragValue::new(alpha)
+                        .range(0.001..=1.0)
+                        .speed(0.005)
+                        .prefix("α "),
+                );
             }
-        })
-        .collect()
+            Transform::Mean { width, centered } => {
+                ui.add(egui::DragValue::new(width).range(2..=50000).suffix(" rows"));
+                ui.checkbox(centered, "centered")
+                    .on_hover_text("Average the rows on both sides of each one, not the last ones");
+            }
+            Transform::Median { width } | Transform::MinMax { width } => {
+                ui.add(egui::DragValue::new(width).range(2..=50000).suffix(" rows"));
+            }
+            Transform::Percentiles { width, low, high } => {
+                ui.add(egui::DragValue::new(width).range(2..=50000).suffix(" rows"));
+                ui.add(egui::DragValue::new(low).range(0.0..=*high).suffix("%"));
+                ui.add(egui::DragValue::new(high).range(*low..=100.0).suffix("%"));
+            }
+            Transform::Difference | Transform::Rate | Transform::PercentChange | Transform::Sum => {
+            }
+        }
+        if ui.small_button("×").on_hover_text("Remove").clicked() {
+            removed = Some(i);
+        }
+        ui.separator();
+    }
+    if let Some(i) = removed {
+        transforms.remove(i);
+    }
+    egui::ComboBox::new(("add_transform_combo", series), "")
+        .selected_text("Add…")
+        .show_ui(ui, |ui| {
+            for transform in Transform::ALL {
+                if ui.selectable_label(false, transform.name()).clicked() {
+                    transforms.push(transform);
+                }
+            }
+        });
 }
 
-pub fn mean_std(data: &[f64]) -> (f64, f64) {
-    let mut n = 0.0;
-    let mut mean = 0.0;
-    let mut m2 = 0.0; // Sum of squares of differences from the current mean
+/// The points of a series after its transforms, ready to draw.
+struct SeriesPoints {
+    line: Vec<[f64; 2]>,
+    /// The x, bottom and top of the band around the line, if a transform draws one.
+    band: Vec<[f64; 3]>,
+}
+
+/// The transformed points of a series, kept from frame to frame and extended as rows arrive.
+struct SeriesCache {
+    transforms: Vec<Transform>,
+    pipeline: Pipeline,
+    /// How many rows have gone through the pipeline.
+    rows: usize,
+    /// Whether the values held back by the pipeline at the end of the input have been added.
+    finished: bool,
+    line: Vec<[f64; 2]>,
+    band: Vec<[f64; 3]>,
+    /// The smallest and largest x.
+    x_range: (f64, f64),
+    /// Whether x never decreases, so that the visible points can be found by bisection.
+    sorted: bool,
+    /// The smallest and largest value of the line and the band.
+    y_range: (f64, f64),
+    /// How many rows are counted in `moments`.
+    counted: usize,
+    /// The count, mean and sum of squared deviations of the values of the series, for their
+    /// z-score.
+    moments: (f64, f64, f64),
+    /// The mean and standard deviation the values were scaled by for their z-score before the
+    /// transforms, if they were.
+    scaled: Option<(f64, f64)>,
+}
 
-    for &x in data {
-        n += 1.0;
-        let delta = x - mean;
-        mean += delta / n;
-        let delta2 = x - mean;
-        m2 += delta * delta2;
+impl SeriesCache {
+    fn new(transforms: &[Transform]) -> Self {
+        SeriesCache {
+            transforms: transforms.to_vec(),
+            pipeline: Pipeline::new(transforms),
+            rows: 0,
+            finished: false,
+            line: Vec::new(),
+            band: Vec::new(),
+            x_range: (f64::INFINITY, f64::NEG_INFINITY),
+            sorted: true,
+            y_range: (f64::INFINITY, f64::NEG_INFINITY),
+            counted: 0,
+            moments: (0.0, 0.0, 0.0),
+            scaled: None,
+        }
     }
 
-    let variance = if n < 2.0 {
-        0.0
-    } else {
-        m2 / (n - 1.0) // Unbiased estimator for variance
+    /// Runs the rows that have not been seen yet through the transforms of series `series_idx`,
+    /// after their z-score if `zscore` is set, and once the input is `done`, adds the values the
+    /// transforms held back.
+    ///
+    /// The z-score changes with every row that arrives, so then all of the rows are run through
+    /// the transforms again.
+    fn update(&mut self, data: &[Row], series_idx: usize, zscore: bool, done: bool) {
+        for row in &data[self.counted..] {
+            let y = row.get(series_idx);
+            if y.is_finite() {
+                let (n, mean, m2) = &mut self.moments;
+                *n += 1.0;
+                let delta = y - *mean;
+                *mean += delta / *n;
+                *m2 += delta * (y - *mean);
+            }
+        }
+        self.counted = data.len();
+        let scale = zscore.then(|| self.mean_std());
+        if scale != self.scaled {
+            *self = SeriesCache {
+                counted: self.counted,
+                moments: self.moments,
+                scaled: scale,
+                ..SeriesCache::new(&self.transforms)
+            };
+        }
+
+        for row in &data[self.rows..] {
+            let y = row.get(series_idx);
+            let y = scale.map_or(y, |(mean, std)| ((y - mean) / std) * 2.0 - 1.0);
+            if let Some(value) = self.pipeline.push(row.x, y) {
+                self.add(value);
+            }
+        }
+        self.rows = data.len();
+        if done && !self.finished {
+            self.finished = true;
+            for value in self.pipeline.finish() {
+                self.add(value);
+            }
+        }
+    }
+
+    fn add(&mut self, value: Transformed) {
+        if let Some(last) = self.line.last() {
+            self.sorted &= value.x >= last[0];
+        }
+        self.x_range = (self.x_range.0.min(value.x), self.x_range.1.max(value.x));
+        self.line.push([value.x, value.y]);
+        if self.transforms.iter().any(Transform::is_band) {
+            let (bottom, top) = value.band.unwrap_or((f64::NAN, f64::NAN));
+            self.band.push([value.x, bottom, top]);
+        }
+        let (bottom, top) = value.band.unwrap_or((value.y, value.y));
+        for y in [value.y, bottom, top] {
+            if y.is_finite() {
+                self.y_range = (self.y_range.0.min(y), self.y_range.1.max(y));
+            }
+        }
+    }
+
+    /// The points between `visible` x values, or all of them, thinned out to at most four per
+    /// one of `columns` columns across: the first, lowest, highest and last.
+    fn points(&self, visible: Option<(f64, f64)>, columns: usize) -> SeriesPoints {
+        let (min, max) = visible.unwrap_or(self.x_range);
+        // One point past each edge, so that the line runs on to it.
+        let range = if self.sorted && visible.is_some() {
+            let start = self.line.partition_point(|p| p[0] < min).saturating_sub(1);
+            let end = (self.line.partition_point(|p| p[0] <= max) + 1).min(self.line.len());
+            start..end.max(start)
+        } else {
+            0..self.line.len()
+        };
+        let column = |x: f64| ((x - min) / (max - min) * columns as f64).floor() as i64;
+        SeriesPoints {
+            line: downsample_line(&self.line[range.clone()], column),
+            band: if self.band.is_empty() {
+                Vec::new()
+            } else {
+                downsample_band(&self.band[range], column)
+            },
+        }
+    }
+
+    /// The smallest and largest value drawn, if there are any.
+    fn y_range(&self) -> Option<(f64, f64)> {
+        let (low, high) = self.y_range;
+        (low <= high).then_some((low, high))
+    }
+
+    fn mean_std(&self) -> (f64, f64) {
+        let (n, mean, m2) = self.moments;
+        let std = if n < 2.0 {
+            0.0
+        } else {
+            (m2 / (n - 1.0)).sqrt()
+        };
+        (mean, std)
+    }
+}
+
+/// Keeps the first, lowest, highest and last of each run of points in the same column, so that
+/// the line looks the same with far fewer points. Missing values are kept to leave their gaps.
+fn downsample_line(points: &[[f64; 2]], column: impl Fn(f64) -> i64) -> Vec<[f64; 2]> {
+    let mut thinned = Vec::new();
+    // The column of the current run, and the indices of its first, lowest, highest and last
+    // points.
+    let mut run: Option<(i64, [usize; 4])> = None;
+    let flush = |thinned: &mut Vec<[f64; 2]>, run: Option<(i64, [usize; 4])>| {
+        if let Some((_, mut indices)) = run {
+            indices.sort_unstable();
+            let mut last = None;
+            for i in indices {
+                if last != Some(i) {
+                    thinned.push(points[i]);
+                    last = Some(i);
+                }
+            }
+        }
+    };
+    for (i, point) in points.iter().enumerate() {
+        if point[1].is_nan() {
+            flush(&mut thinned, run.take());
+            thinned.push(*point);
+            continue;
+        }
+        let c = column(point[0]);
+        match &mut run {
+            Some((run_column, [_, lowest, highest, last])) if *run_column == c => {
+                if point[1] < points[*lowest][1] {
+                    *lowest = i;
+                }
+                if point[1] > points[*highest][1] {
+                    *highest = i;
+                }
+                *last = i;
+            }
+            _ => {
+                flush(&mut thinned, run.take());
+                run = Some((c, [i; 4]));
+            }
+        }
+    }
+    flush(&mut thinned, run);
+    thinned
+}
+
+/// Replaces each run of band points in the same column by the outline of the run: its lowest
+/// bottom and highest top, at its first and last x.
+fn downsample_band(points: &[[f64; 3]], column: impl Fn(f64) -> i64) -> Vec<[f64; 3]> {
+    let mut thinned = Vec::new();
+    // The column of the current run, its first and last x, and its lowest bottom and highest
+    // top.
+    let mut run: Option<(i64, [f64; 4])> = None;
+    let flush = |thinned: &mut Vec<[f64; 3]>, run: Option<(i64, [f64; 4])>| {
+        if let Some((_, [first, last, bottom, top])) = run {
+            thinned.push([first, bottom, top]);
+            if last != first {
+                thinned.push([last, bottom, top]);
+            }
+        }
     };
-    let std_dev = variance.sqrt();
+    for point in points {
+        let [x, bottom, top] = *point;
+        if bottom.is_nan() || top.is_nan() {
+            flush(&mut thinned, run.take());
+            thinned.push(*point);
+            continue;
+        }
+        let c = column(x);
+        match &mut run {
+            Some((run_column, [_, last, lowest, highest])) if *run_column == c => {
+                *last = x;
+                *lowest = lowest.min(bottom);
+                *highest = highest.max(top);
+            }
+            _ => {
+                flush(&mut thinned, run.take());
+                run = Some((c, [x, x, bottom, top]));
+            }
+        }
+    }
+    flush(&mut thinned, run);
+    thinned
+}
+
+/// The color of the `index`th series, as `egui_plot` would pick it.
+fn series_color(index: usize) -> egui::Color32 {
+    let golden_ratio = (5.0_f32.sqrt() - 1.0) / 2.0;
+    egui::ecolor::Hsva::new(index as f32 * golden_ratio, 0.85, 0.5, 1.0).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    (mean, std_dev)
+    fn parser(layout: Layout, x_column: Option<Field>, fields: Vec<Field>) -> LineParser {
+        LineParser {
+            layout,
+            x_column,
+            fields,
+            time_format: None,
+            columns: None,
+        }
+    }
+
+    #[test]
+    fn logfmt_lines_without_the_keys_are_skipped() {
+        let name = |key: &str| Field::Name(key.to_owned());
+        let mut parser = parser(Layout::Logfmt, Some(name("t")), vec![name("ms")]);
+        let mut table = Table::default();
+        for line in [
+            "t=1 ms=5",
+            "ms=6",
+            "t=x ms=7",
+            "t=2 level=info",
+            "",
+            "t=3 ms=8",
+        ] {
+            if let Some(row) = parser.parse(line, &mut table) {
+                table.rows.push(row);
+            }
+        }
+        let rows = table.rows.iter().map(|row| (row.x, row.get(0)));
+        assert_eq!(rows.collect::<Vec<_>>(), [(1.0, 5.0), (3.0, 8.0)]);
+        assert_eq!(table.skipped, 3);
+    }
+
+    #[test]
+    fn short_delimited_lines_are_skipped() {
+        let layout = Layout::Delimited {
+            delimiter: Some(','),
+            header: true,
+        };
+        let mut parser = parser(layout, Some(Field::Index(2)), Vec::new());
+        let mut table = Table::default();
+        for line in ["a,b,t", "1,2,10", "3", "4,5,11"] {
+            if let Some(row) = parser.parse(line, &mut table) {
+                table.rows.push(row);
+            }
+        }
+        assert_eq!(table.rows.len(), 2);
+        assert_eq!(table.skipped, 1);
+    }
 }
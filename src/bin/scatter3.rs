@@ -7,7 +7,7 @@ use clap::Parser;
 use eframe::egui;
 use egui::Color32;
 use egui_plot::{CoordinatesFormatter, Corner, Plot, Points};
-use hist3::data::InputSource;
+use hist3::data::{lossy_lines, InputSource};
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::{self, BufRead};
@@ -34,12 +34,9 @@ fn main() -> Result<(), eframe::Error> {
     let plot = ScatterApp::default();
     let data_ref = plot.data.clone();
 
-    thread::spawn({
-        let args = args; // Move args into the closure
-        move || {
-            let input = get_input_source(&args);
-            process_input(input, &data_ref);
-        }
+    thread::spawn(move || {
+        let input = get_input_source(&args);
+        process_input(input, &data_ref);
     });
 
     let options = eframe::NativeOptions {
@@ -86,54 +83,54 @@ fn process_reader<R: BufRead>(reader: R, data_ref: &Arc<RwLock<Vec<Vec<f64>>>>)
     let mut batch = Vec::new();
     const BATCH_SIZE: usize = 1000;
 
-    for line in reader.lines() {
-        if let Ok(line) = line {
-            // Extract all numeric patterns that could be valid numbers
-            let mut values = Vec::new();
-            let mut start_idx = None;
-
-            // Scan the line character by character to identify number patterns
-            for (i, c) in line.char_indices() {
-                let is_num_char =
-                    c.is_numeric() || c == '.' || c == '-' || c == '+' || c == 'e' || c == 'E';
-
-                if is_num_char && start_idx.is_none() {
-                    // Start of a new number
-                    start_idx = Some(i);
-                } else if !is_num_char && start_idx.is_some() {
-                    // End of a number - extract the substring
-                    let start = start_idx.unwrap();
-                    values.push(&line[start..i]);
-                    start_idx = None;
-                }
-            }
-
-            // Handle case where the line ends with a number
-            if let Some(start) = start_idx {
-                values.push(&line[start..]);
+    for line in lossy_lines(reader).map_while(Result::ok) {
+        // Extract all numeric patterns that could be valid numbers
+        let mut values = Vec::new();
+        let mut start_idx = None;
+
+        // Scan the line character by character to identify number patterns
+        for (i, c) in line.char_indices() {
+            let is_num_char =
+                c.is_numeric() || c == '.' || c == '-' || c == '+' || c == 'e' || c == 'E';
+
+            if is_num_char && start_idx.is_none() {
+                // Start of a new number
+                start_idx = Some(i);
+            } else if !is_num_char && start_idx.is_some() {
+                // End of a number - extract the substring
+                let start = start_idx.unwrap();
+                values.push(&line[start..i]);
+                start_idx = None;
             }
+        }
 
-            // Parse all extracted strings into numbers, filtering out failures
-            let floats = values
-                .into_iter()
-                .filter_map(|s| f64::from_str(s).ok())
-                .collect::<Vec<_>>();
+        // Handle case where the line ends with a number
+        if let Some(start) = start_idx {
+            values.push(&line[start..]);
+        }
 
-            // Check if the number of values matches the size of the first line
-            // Also ensure we actually parsed some numbers
-            if !floats.is_empty() {
-                if first_line_size.is_none() {
+        // Parse all extracted strings into numbers, filtering out failures
+        let floats = values
+            .into_iter()
+            .filter_map(|s| f64::from_str(s).ok())
+            .collect::<Vec<_>>();
+
+        // Check if the number of values matches the size of the first line
+        // Also ensure we actually parsed some numbers
+        if !floats.is_empty() {
+            match first_line_size {
+                None => {
                     first_line_size = Some(floats.len());
                     batch.push(floats);
-                } else if floats.len() == first_line_size.unwrap() {
-                    batch.push(floats);
                 }
+                Some(size) if floats.len() == size => batch.push(floats),
+                Some(_) => {}
+            }
 
-                // Only lock the mutex when we have a full batch
-                if batch.len() >= BATCH_SIZE {
-                    if let Ok(mut data) = data_ref.write() {
-                        data.extend(batch.drain(..));
-                    }
+            // Only lock the mutex when we have a full batch
+            if batch.len() >= BATCH_SIZE {
+                if let Ok(mut data) = data_ref.write() {
+                    data.extend(batch.drain(..));
                 }
             }
         }
@@ -147,6 +144,9 @@ fn process_reader<R: BufRead>(reader: R, data_ref: &Arc<RwLock<Vec<Vec<f64>>>>)
     }
 }
 
+/// A plotted point: its `[x, y]` position plus optional color and size column values.
+type PlotPoint = ([f64; 2], Option<f64>, Option<f64>);
+
 struct ScatterApp {
     data: Arc<RwLock<Vec<Vec<f64>>>>,
     x_col: usize,
@@ -159,7 +159,7 @@ struct ScatterApp {
     // Track statistics to avoid recomputing them
     statistics: HashMap<usize, (f64, f64)>, // (mean, std) for each column
     data_version: usize,                    // Incremented when data or filters change
-    plot_data_cache: Option<(usize, Vec<([f64; 2], Option<f64>, Option<f64>)>)>,
+    plot_data_cache: Option<(usize, Vec<PlotPoint>)>,
 }
 
 impl Default for ScatterApp {
@@ -257,10 +257,12 @@ impl eframe::App for ScatterApp {
             static mut LAST_DATA_SIZE: usize = 0;
             let last_size = unsafe { LAST_DATA_SIZE };
             let data_growing = current_data_size > last_size;
-            unsafe { LAST_DATA_SIZE = current_data_size; }
+            unsafe {
+                LAST_DATA_SIZE = current_data_size;
+            }
             data_growing && current_data_size > 0
         };
-        
+
         if is_loading {
             ctx.request_repaint_after(std::time::Duration::from_millis(100));
         }
@@ -403,7 +405,7 @@ impl ScatterApp {
                     if old_min != filter.2 || old_max != filter.3 {
                         *data_changed = true;
                         self.statistics.remove(&i); // Invalidate statistics
-                        
+
                         // Also invalidate color and size caches when filters change
                         // so they're recalculated based on filtered data
                         self.color_cache.clear();
@@ -449,11 +451,15 @@ impl ScatterApp {
                 // First pass - find min/max while respecting filters
                 for row in data.iter() {
                     // Skip rows that don't pass the filter
-                    let passes_filter = row.iter().enumerate().take(self.filters.len()).all(|(i, val)| {
-                        let filter = &self.filters[i];
-                        *val >= filter.2 && *val <= filter.3
-                    });
-                    
+                    let passes_filter =
+                        row.iter()
+                            .enumerate()
+                            .take(self.filters.len())
+                            .all(|(i, val)| {
+                                let filter = &self.filters[i];
+                                *val >= filter.2 && *val <= filter.3
+                            });
+
                     if passes_filter {
                         if let Some(&val) = row.get(col) {
                             min_value = min_value.min(val);
@@ -496,11 +502,15 @@ impl ScatterApp {
                 // First pass - find min/max while respecting filters
                 for row in data.iter() {
                     // Skip rows that don't pass the filter
-                    let passes_filter = row.iter().enumerate().take(self.filters.len()).all(|(i, val)| {
-                        let filter = &self.filters[i];
-                        *val >= filter.2 && *val <= filter.3
-                    });
-                    
+                    let passes_filter =
+                        row.iter()
+                            .enumerate()
+                            .take(self.filters.len())
+                            .all(|(i, val)| {
+                                let filter = &self.filters[i];
+                                *val >= filter.2 && *val <= filter.3
+                            });
+
                     if passes_filter {
                         if let Some(&val) = row.get(col) {
                             min_value = min_value.min(val);
@@ -530,7 +540,7 @@ impl ScatterApp {
         }
     }
 
-    fn collect_plot_data(&self) -> Vec<([f64; 2], Option<f64>, Option<f64>)> {
+    fn collect_plot_data(&self) -> Vec<PlotPoint> {
         if let Ok(data) = self.data.read() {
             data.iter()
                 .filter_map(|row| {
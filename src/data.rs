@@ -1,5 +1,34 @@
+mod binning;
+mod categories;
+mod cdf;
+mod columns;
+mod density;
+mod fit;
+mod live;
+pub mod sketch;
+mod stats;
+mod time;
+mod transform;
+
+pub use binning::{BinRule, Scale};
+pub use categories::{natural_cmp, CategoryOrder, OTHER_LABEL};
+pub use cdf::Ecdf;
+pub use columns::{parse_delimiter, split_fields, split_logfmt, Column, FieldSelector};
+pub use density::{BandwidthRule, Kde, Kernel};
+pub use fit::{Distribution, Family, Fit};
+pub use live::{read_live, LiveValues, Snapshot, Window};
+pub use stats::Stats;
+pub use time::{format_timestamp, TimeFormat};
+pub use transform::{Pipeline, Transform, Transformed};
+
+use binning::Summary;
+use columns::{Extracted, FieldExtractor};
 use itertools::Itertools;
-use std::collections::HashMap;
+use serde::Serialize;
+use sketch::{HyperLogLog, P2Quantile, SpaceSaving};
+use stats::Moments;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::fs::File;
 use std::io::{self, BufRead};
 
@@ -9,153 +38,1800 @@ pub enum InputSource {
     Stdin,
 }
 
+/// Errors that can occur while reading input or building a histogram.
+#[derive(Debug)]
+pub enum DataError {
+    /// The input could not be opened or read.
+    Io(io::Error),
+    /// The input contained no lines at all.
+    EmptyInput,
+    /// None of the input lines could be parsed as a number.
+    NoNumericValues { lines: usize },
+    /// A fixed histogram range was empty or not finite.
+    InvalidRange { min: f64, max: f64 },
+    /// Every numeric value fell outside the fixed histogram range or the explicit bin edges.
+    OutOfRange { min: f64, max: f64, count: usize },
+    /// The requested binning cannot be applied.
+    InvalidBins(String),
+    /// A log-scale histogram was requested but no value is positive.
+    NoPositiveValues { count: usize },
+    /// The header row has no column with this name.
+    UnknownColumn(String),
+    /// One of several series to be compared has no values that can be binned.
+    EmptySeries(String),
+    /// A requested quantile is not a probability between 0 and 1.
+    InvalidQuantile(f64),
+    /// In strict mode, a value is not a finite number.
+    InvalidValue { line: usize, value: String },
+    /// None of the input lines has the selected field.
+    NoFields { lines: usize },
+    /// In strict mode, a line does not have the selected field.
+    MissingField { line: usize },
+}
+
+impl fmt::Display for DataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DataError::Io(e) => write!(f, "failed to read input: {}", e),
+            DataError::EmptyInput => write!(f, "input is empty"),
+            DataError::NoNumericValues { lines } => {
+                write!(f, "none of the {} input lines is a number", lines)
+            }
+            DataError::InvalidRange { min, max } => {
+                write!(f, "invalid histogram range [{}, {}]", min, max)
+            }
+            DataError::OutOfRange { min, max, count } => write!(
+                f,
+                "all {} numeric values fall outside the range [{}, {}]",
+                count, min, max
+            ),
+            DataError::InvalidBins(reason) => write!(f, "invalid bins: {}", reason),
+            DataError::NoPositiveValues { count } => write!(
+                f,
+                "none of the {} numeric values is positive, so none can be shown on a log scale",
+                count
+            ),
+            DataError::UnknownColumn(name) => {
+                write!(f, "the header has no column named {:?}", name)
+            }
+            DataError::EmptySeries(name) => write!(f, "{} has no values to bin", name),
+            DataError::InvalidQuantile(p) => {
+                write!(f, "invalid quantile {}: must be between 0 and 1", p)
+            }
+            DataError::InvalidValue { line, value } => {
+                write!(f, "line {}: {:?} is not a finite number", line, value)
+            }
+            DataError::NoFields { lines } => {
+                write!(
+                    f,
+                    "none of the {} input lines has the selected field",
+                    lines
+                )
+            }
+            DataError::MissingField { line } => {
+                write!(f, "line {}: the selected field is missing", line)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DataError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DataError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for DataError {
+    fn from(e: io::Error) -> Self {
+        DataError::Io(e)
+    }
+}
+
+/// A single bar of a histogram.
+///
+/// Numeric bins cover the half-open interval `[lower, upper)` (the last bin also includes its
+/// upper edge). Categorical bins are laid out at consecutive integer positions, so `lower` and
+/// `upper` are the bar's extent on the x axis rather than data values.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bin {
+    pub label: String,
+    pub lower: f64,
+    pub upper: f64,
+    pub count: usize,
+    /// How far `count` may be from the true count when categories are counted approximately.
+    /// Zero when the count is exact.
+    pub error: usize,
+    /// Whether this is the [`OTHER_LABEL`] bar, which counts the categories left out by
+    /// [`HistogramOptions::top`].
+    pub other: bool,
+}
+
+impl Bin {
+    pub fn mid(&self) -> f64 {
+        (self.lower + self.upper) / 2.0
+    }
+
+    pub fn width(&self) -> f64 {
+        self.upper - self.lower
+    }
+}
+
+/// The value at probability `p` (between 0 and 1) of a numeric distribution.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quantile {
+    pub p: f64,
+    pub value: f64,
+}
+
+impl Quantile {
+    /// `p` in percent, rounded to hide floating-point noise, e.g. 99.9 rather than 99.900000001.
+    pub fn percent(&self) -> f64 {
+        (self.p * 1e8).round() / 1e6
+    }
+}
+
+/// The quantiles computed unless others are requested: the quartiles and the median.
+pub const DEFAULT_QUANTILES: [f64; 3] = [0.25, 0.5, 0.75];
+
+/// The result of binning an input stream.
+#[derive(Clone, Debug)]
+pub struct Histogram {
+    pub bins: Vec<Bin>,
+    /// The requested quantiles, by default [`DEFAULT_QUANTILES`]. Empty for categorical
+    /// histograms.
+    pub quantiles: Vec<Quantile>,
+    /// Number of values that were counted into the bins.
+    pub total: usize,
+    /// The `(min, max)` of the numeric values, or `None` for categorical histograms.
+    pub range: Option<(f64, f64)>,
+    /// The spacing of the bins. Always linear for categorical histograms.
+    pub scale: Scale,
+    /// Number of zero or negative values left out of a log-scale histogram. They are not part of
+    /// `total`, `range` or the percentiles.
+    pub non_positive: usize,
+    /// Descriptive statistics of the values, or `None` for categorical histograms.
+    pub stats: Option<Stats>,
+    /// The values that were left out because they are not finite numbers or fall outside the
+    /// bins, and the lines without the selected field. Only the latter are counted for
+    /// categorical histograms.
+    pub rejected: Rejected,
+    /// The number of distinct categories, including those left out of the bins, or `None` for
+    /// numeric histograms.
+    pub distinct: Option<Distinct>,
+}
+
+impl Histogram {
+    pub fn is_categorical(&self) -> bool {
+        self.range.is_none()
+    }
+
+    pub fn quantile(&self, p: f64) -> Option<f64> {
+        self.quantiles
+            .iter()
+            .find(|q| (q.p - p).abs() < 1e-9)
+            .map(|q| q.value)
+    }
+
+    pub fn max_count(&self) -> usize {
+        self.bins.iter().map(|b| b.count).max().unwrap_or(0)
+    }
+
+    /// The height of each bar under `normalization`.
+    ///
+    /// Densities are per unit of the axis the bins are evenly spaced on, which is the logarithm
+    /// of the values on a log scale, so that the bars have a total area of 1 as drawn.
+    /// Categorical bins are one unit wide.
+    pub fn heights(&self, normalization: Normalization) -> Vec<f64> {
+        self.bins
+            .iter()
+            .map(|bin| {
+                let width = if self.is_categorical() {
+                    1.0
+                } else {
+                    self.scale.forward(bin.upper) - self.scale.forward(bin.lower)
+                };
+                normalization.height(bin.count as f64, self.total as f64, width)
+            })
+            .collect()
+    }
+
+    /// The tallest bar under `normalization`.
+    pub fn max_height(&self, normalization: Normalization) -> f64 {
+        self.heights(normalization).into_iter().fold(0.0, f64::max)
+    }
+
+    /// The text of the `lines` whose value is counted in one of the bins at the `selected`
+    /// indices, in input order.
+    ///
+    /// Numeric values are placed in bins by their edges; categorical values by their label, or in
+    /// the [`Bin::other`] bar, if there is one, when none has their label.
+    pub fn lines_in_bins<'a>(
+        &self,
+        selected: &HashSet<usize>,
+        lines: &'a [InputLine],
+    ) -> Vec<&'a str> {
+        if self.bins.is_empty() {
+            return Vec::new();
+        }
+        let select = |bin_of: &dyn Fn(&str) -> Option<usize>| {
+            lines
+                .iter()
+                .filter(|line| bin_of(&line.value).is_some_and(|i| selected.contains(&i)))
+                .map(|line| line.text.as_str())
+                .collect()
+        };
+        if self.is_categorical() {
+            let index = self
+                .bins
+                .iter()
+                .enumerate()
+                .filter(|(_, bin)| !bin.other)
+                .map(|(i, bin)| (bin.label.as_str(), i))
+                .collect::<HashMap<_, _>>();
+            let other = self.bins.iter().position(|bin| bin.other);
+            select(&|value| index.get(value).copied().or(other))
+        } else {
+            let edges = self
+                .bins
+                .iter()
+                .map(|b| b.lower)
+                .chain(self.bins.last().map(|b| b.upper))
+                .collect::<Vec<_>>();
+            let log = self.scale.is_log();
+            select(&|value| {
+                let x = value.parse::<f64>().ok()?;
+                if !x.is_finite() || (log && x <= 0.0) {
+                    return None;
+                }
+                binning::bin_of(x, &edges)
+            })
+        }
+    }
+}
+
+/// An input line and the value taken from it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InputLine {
+    pub text: String,
+    pub value: String,
+}
+
+/// What the height of a histogram bar stands for.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Normalization {
+    /// The number of values in the bin.
+    #[default]
+    Count,
+    /// The fraction of the values in the bin, so that the heights add up to 1.
+    Fraction,
+    /// The percentage of the values in the bin, so that the heights add up to 100.
+    Percent,
+    /// The fraction of the values in the bin divided by its width, so that the area of the bars
+    /// is 1, as for a probability density function.
+    Density,
+}
+
+impl Normalization {
+    /// The height of a bar of `count` values out of `total`, `width` wide.
+    pub fn height(self, count: f64, total: f64, width: f64) -> f64 {
+        if total == 0.0 {
+            return 0.0;
+        }
+        match self {
+            Normalization::Count => count,
+            Normalization::Fraction => count / total,
+            Normalization::Percent => 100.0 * count / total,
+            Normalization::Density => count / (total * width),
+        }
+    }
+
+    /// Describes the heights, e.g. for the label of the y axis.
+    pub fn name(self) -> &'static str {
+        match self {
+            Normalization::Count => "Count",
+            Normalization::Fraction => "Fraction",
+            Normalization::Percent => "Percent",
+            Normalization::Density => "Density",
+        }
+    }
+}
+
+/// The number of distinct categories of an input, exact or estimated.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Distinct {
+    pub count: usize,
+    /// Relative standard error of `count`, or zero if it is exact.
+    pub relative_error: f64,
+}
+
+/// E.g. `42 distinct` or `~1234567 distinct (±0.8%)`.
+impl fmt::Display for Distinct {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.relative_error > 0.0 {
+            write!(
+                f,
+                "~{} distinct (±{:.1}%)",
+                self.count,
+                100.0 * self.relative_error
+            )
+        } else {
+            write!(f, "{} distinct", self.count)
+        }
+    }
+}
+
+/// Maximum number of distinct values kept in [`Rejected::samples`].
+const MAX_REJECTED_SAMPLES: usize = 5;
+
+/// Values of a numeric input that were left out, by reason.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Rejected {
+    /// Empty lines or fields.
+    pub empty: usize,
+    /// Values that are not numbers.
+    pub unparsed: usize,
+    pub nan: usize,
+    pub pos_inf: usize,
+    pub neg_inf: usize,
+    /// Numbers outside the bin edges or the fixed range.
+    pub out_of_range: usize,
+    /// Lines too short to have the selected field.
+    pub missing: usize,
+    /// The first few distinct values that are not numbers.
+    pub samples: Vec<String>,
+}
+
+impl Rejected {
+    /// Number of values left out for any reason.
+    pub fn total(&self) -> usize {
+        self.empty
+            + self.unparsed
+            + self.nan
+            + self.pos_inf
+            + self.neg_inf
+            + self.out_of_range
+            + self.missing
+    }
+
+    /// Parses `value` as a finite number, or counts why it is not one.
+    fn parse(&mut self, value: &str) -> Option<f64> {
+        if value.is_empty() {
+            self.empty += 1;
+            return None;
+        }
+        match value.parse::<f64>() {
+            Ok(x) => self.check(x),
+            Err(_) => {
+                self.unparsed += 1;
+                if self.samples.len() < MAX_REJECTED_SAMPLES
+                    && !self.samples.iter().any(|s| s == value)
+                {
+                    self.samples.push(value.to_owned());
+                }
+                None
+            }
+        }
+    }
+
+    /// Returns `x` if it is finite, or counts it as NaN or infinite.
+    fn check(&mut self, x: f64) -> Option<f64> {
+        if x.is_nan() {
+            self.nan += 1;
+        } else if x == f64::INFINITY {
+            self.pos_inf += 1;
+        } else if x == f64::NEG_INFINITY {
+            self.neg_inf += 1;
+        } else {
+            return Some(x);
+        }
+        None
+    }
+}
+
+/// Lists the counts that are not zero, e.g.
+/// `3 values skipped: 2 not numbers (e.g. "n/a", "-"), 1 NaN`.
+impl fmt::Display for Rejected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.total();
+        write!(
+            f,
+            "{} value{} skipped:",
+            total,
+            if total == 1 { "" } else { "s" }
+        )?;
+        let mut parts = Vec::new();
+        if self.unparsed > 0 {
+            let samples = self
+                .samples
+                .iter()
+                .map(|s| format!("{:?}", s))
+                .collect::<Vec<_>>()
+                .join(", ");
+            parts.push(format!(
+                "{} not {} (e.g. {})",
+                self.unparsed,
+                if self.unparsed == 1 {
+                    "a number"
+                } else {
+                    "numbers"
+                },
+                samples
+            ));
+        }
+        for (count, what) in [
+            (self.empty, "empty"),
+            (self.nan, "NaN"),
+            (self.pos_inf, "+inf"),
+            (self.neg_inf, "-inf"),
+            (self.out_of_range, "outside the bins"),
+            (self.missing, "without the field"),
+        ] {
+            if count > 0 {
+                parts.push(format!("{} {}", count, what));
+            }
+        }
+        write!(f, " {}", parts.join(", "))
+    }
+}
+
+/// A named histogram, one of several drawn on the same bins.
+#[derive(Clone, Debug)]
+pub struct Series {
+    pub name: String,
+    pub histogram: Histogram,
+    /// The binned values in ascending order, kept for estimates that do not depend on the bins.
+    /// Empty for categorical and streamed histograms.
+    pub values: Vec<f64>,
+    /// Every input line of the series, including those whose value was left out, in input order.
+    /// Empty unless [`HistogramOptions::keep_lines`] is set.
+    pub lines: Vec<InputLine>,
+}
+
+impl Series {
+    /// The empirical distribution of the binned values, or `None` if they were not kept.
+    pub fn ecdf(&self) -> Option<Ecdf<'_>> {
+        Ecdf::new(&self.values)
+    }
+}
+
 fn compare_f64(x: &f64, y: &f64) -> std::cmp::Ordering {
     x.partial_cmp(y).unwrap_or(std::cmp::Ordering::Equal)
 }
 
-fn read_from_stdin(max_num_lines: usize) -> Vec<String> {
-    let mut vals: Vec<String> = Vec::new();
-    for line in std::io::stdin().lock().lines().take(max_num_lines) {
-        vals.push(line.unwrap().trim().to_owned());
+/// Settings for building a histogram from an input stream.
+#[derive(Clone, Debug)]
+pub struct HistogramOptions {
+    /// How numeric values are split into bins.
+    pub rule: BinRule,
+    /// Whether numeric bins are equally spaced in the values or in their logarithm.
+    pub scale: Scale,
+    /// Split exactly this range into [`BinRule::Count`] bins instead of the range of the data.
+    /// Values outside it are left out, and counted in [`Rejected::out_of_range`].
+    pub range: Option<(f64, f64)>,
+    /// Count distinct values instead of parsing numbers.
+    pub categorical: bool,
+    /// Take the value from one field of delimited lines instead of the whole line.
+    pub field: Option<FieldSelector>,
+    /// Probabilities, between 0 and 1, of the quantiles to compute.
+    pub quantiles: Vec<f64>,
+    /// Fail with [`DataError::InvalidValue`] on the first value that is not a finite number,
+    /// instead of counting it in [`Histogram::rejected`]. Ignored for categorical input.
+    pub strict: bool,
+    /// Keep only this many of the most frequent categories and count the rest in a single
+    /// [`OTHER_LABEL`] bar, which always comes last.
+    pub top: Option<usize>,
+    /// The order of the categorical bars.
+    pub order: CategoryOrder,
+    /// Order the categorical bars from largest to smallest.
+    pub descending: bool,
+    /// Keep the input lines in [`Series::lines`], e.g. to write out those in some bins.
+    pub keep_lines: bool,
+}
+
+impl Default for HistogramOptions {
+    fn default() -> Self {
+        HistogramOptions {
+            rule: BinRule::default(),
+            scale: Scale::default(),
+            range: None,
+            categorical: false,
+            field: None,
+            quantiles: DEFAULT_QUANTILES.to_vec(),
+            strict: false,
+            top: None,
+            order: CategoryOrder::default(),
+            descending: false,
+            keep_lines: false,
+        }
     }
-    vals
 }
 
-fn read_from_file(file_name: &String, max_num_lines: usize) -> Vec<String> {
-    let mut vals: Vec<String> = Vec::new();
-    let file = File::open(file_name).unwrap();
-    for line in io::BufReader::new(file).lines().take(max_num_lines) {
-        vals.push(line.unwrap().trim().to_owned());
+impl HistogramOptions {
+    fn validate_quantiles(&self) -> Result<(), DataError> {
+        match self.quantiles.iter().find(|p| !(0.0..=1.0).contains(*p)) {
+            Some(&p) => Err(DataError::InvalidQuantile(p)),
+            None => Ok(()),
+        }
+    }
+
+    /// In strict mode, fails because line `line` does not have the selected field.
+    fn check_field(&self, line: usize) -> Result<(), DataError> {
+        if self.strict && !self.categorical {
+            Err(DataError::MissingField { line })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// In strict mode, fails if the value on line `line` is not a finite number.
+    fn check_value(&self, line: usize, value: &str) -> Result<(), DataError> {
+        let finite = value.parse::<f64>().is_ok_and(f64::is_finite);
+        if self.strict && !self.categorical && !finite {
+            Err(DataError::InvalidValue {
+                line,
+                value: value.to_owned(),
+            })
+        } else {
+            Ok(())
+        }
     }
-    vals
 }
 
-fn histogram_from_categories(
-    vals: &Vec<String>,
-) -> (
-    Vec<(String, usize)>,
-    Option<(f64, f64)>,
-    Option<(f64, f64)>,
-    Option<(f64, f64)>,
-    f64,
-    f64,
-) {
-    let ret: Vec<(String, usize)> = vals
-        .iter()
-        .sorted()
-        .group_by(|e| (**e).to_owned())
+/// The lines of `reader` without their line endings, like [`BufRead::lines`], except that bytes
+/// that are not UTF-8 are replaced with U+FFFD rather than failing the line. Other read errors
+/// are returned as they are, and usually repeat, so the lines should not be read past one.
+pub fn lossy_lines<R: BufRead>(mut reader: R) -> impl Iterator<Item = io::Result<String>> {
+    let mut buf = Vec::new();
+    std::iter::from_fn(move || {
+        buf.clear();
+        match reader.read_until(b'\n', &mut buf) {
+            Ok(0) => None,
+            Ok(_) => Some(Ok(
+                trim_line_ending(&String::from_utf8_lossy(&buf)).to_owned()
+            )),
+            Err(e) => Some(Err(e)),
+        }
+    })
+}
+
+fn trim_line_ending(line: &str) -> &str {
+    line.trim_end_matches(&['\n', '\r'][..])
+}
+
+/// Calls `f` with the 1-based number and the text of every line of `reader`, without its line
+/// ending, reusing a single line buffer.
+///
+/// Bytes that are not UTF-8 are replaced with U+FFFD, so that a numeric value with such bytes
+/// is counted in [`Rejected::unparsed`] instead of ending the input.
+fn for_each_line_of<R, F>(mut reader: R, mut f: F) -> Result<(), DataError>
+where
+    R: BufRead,
+    F: FnMut(usize, &str) -> Result<(), DataError>,
+{
+    let mut line = Vec::new();
+    for number in 1.. {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        f(number, trim_line_ending(&String::from_utf8_lossy(&line)))?;
+    }
+    Ok(())
+}
+
+fn for_each_line<F>(input: &InputSource, f: F) -> Result<(), DataError>
+where
+    F: FnMut(usize, &str) -> Result<(), DataError>,
+{
+    match input {
+        InputSource::Stdin => for_each_line_of(io::stdin().lock(), f),
+        InputSource::FileName(file_name) => {
+            let file = File::open(file_name)
+                .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", file_name, e)))?;
+            for_each_line_of(io::BufReader::new(file), f)
+        }
+    }
+}
+
+/// Calls `f` with the line number, the text and the value of every line of `input`, which is
+/// the whole line or the selected field, and with its group key if the selector groups lines.
+/// Header lines are skipped, and the value of a line without the selected field is `None`.
+fn for_each_value<F>(
+    input: &InputSource,
+    field: Option<&FieldSelector>,
+    mut f: F,
+) -> Result<(), DataError>
+where
+    F: FnMut(usize, &str, Option<&str>, Option<&str>) -> Result<(), DataError>,
+{
+    match field {
+        None => for_each_line(input, |number, line| {
+            f(number, line, Some(line.trim()), None)
+        }),
+        Some(selector) => {
+            let mut extractor = FieldExtractor::new(selector);
+            for_each_line(input, |number, line| match extractor.extract(line)? {
+                Extracted::Header => Ok(()),
+                Extracted::Value(value, group) => f(number, line, Some(&value), group.as_deref()),
+                Extracted::Missing(group) => f(number, line, None, group.as_deref()),
+            })
+        }
+    }
+}
+
+/// The values read from an input, and the lines they were taken from if
+/// [`HistogramOptions::keep_lines`] is set.
+#[derive(Default)]
+struct InputValues {
+    values: Vec<String>,
+    lines: Vec<String>,
+    /// Number of lines without the selected field.
+    missing: usize,
+}
+
+impl InputValues {
+    fn push(&mut self, line: &str, value: &str, options: &HistogramOptions) {
+        self.values.push(value.to_owned());
+        if options.keep_lines {
+            self.lines.push(line.to_owned());
+        }
+    }
+
+    /// Pairs the kept lines with their values.
+    fn into_lines(self) -> Vec<InputLine> {
+        self.lines
+            .into_iter()
+            .zip(self.values)
+            .map(|(text, value)| InputLine { text, value })
+            .collect()
+    }
+}
+
+fn read_input(input: &InputSource, options: &HistogramOptions) -> Result<InputValues, DataError> {
+    let mut vals = InputValues::default();
+    for_each_value(input, options.field.as_ref(), |number, line, value, _| {
+        match value {
+            Some(value) => {
+                options.check_value(number, value)?;
+                vals.push(line, value, options);
+            }
+            None => {
+                options.check_field(number)?;
+                vals.missing += 1;
+            }
+        }
+        Ok(())
+    })?;
+    Ok(vals)
+}
+
+/// Reads the values of `input` split by group key, in order of first appearance.
+///
+/// Lines without the selected field are counted in the group of their key, if it has values,
+/// and otherwise in the first group.
+fn read_groups(
+    input: &InputSource,
+    field: &FieldSelector,
+    options: &HistogramOptions,
+) -> Result<Vec<(String, InputValues)>, DataError> {
+    let mut groups: Vec<(String, InputValues)> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    let mut missing: HashMap<Option<String>, usize> = HashMap::new();
+    for_each_value(input, Some(field), |number, line, value, group| {
+        let Some(value) = value else {
+            options.check_field(number)?;
+            *missing.entry(group.map(str::to_owned)).or_default() += 1;
+            return Ok(());
+        };
+        options.check_value(number, value)?;
+        let group = group.unwrap_or_default();
+        let i = *index.entry(group.to_owned()).or_insert_with(|| {
+            groups.push((group.to_owned(), InputValues::default()));
+            groups.len() - 1
+        });
+        groups[i].1.push(line, value, options);
+        Ok(())
+    })?;
+    if groups.is_empty() && !missing.is_empty() {
+        let lines = missing.values().sum();
+        return Err(DataError::NoFields { lines });
+    }
+    for (group, count) in missing {
+        let i = group
+            .and_then(|group| index.get(&group).copied())
+            .unwrap_or(0);
+        groups[i].1.missing += count;
+    }
+    Ok(groups)
+}
+
+/// Counts the distinct values, in the order given by `options.order` and `options.descending`,
+/// keeping only the `options.top` most frequent ones if it is set.
+///
+/// Each category gets a unit-wide bin centred on its position, so bars can be drawn directly.
+pub fn histogram_from_categories(
+    vals: &[String],
+    options: &HistogramOptions,
+) -> Result<Histogram, DataError> {
+    if vals.is_empty() {
+        return Err(DataError::EmptyInput);
+    }
+    let counts = vals.iter().map(String::as_str).counts();
+    let distinct = Distinct {
+        count: counts.len(),
+        relative_error: 0.0,
+    };
+    let ranked = categories::rank(counts, options.top, options.order, options.descending);
+    Ok(categorical_histogram(ranked, distinct))
+}
+
+fn categorical_histogram(ranked: categories::Ranked, distinct: Distinct) -> Histogram {
+    let bins: Vec<Bin> = ranked
+        .kept
         .into_iter()
-        .map(|(k, group_k)| (k, group_k.count()))
-        .sorted_by(|(_, i), (_, j)| i.cmp(j))
+        .map(|(label, count)| (label, count, false))
+        .chain(
+            ranked
+                .other
+                .map(|count| (OTHER_LABEL.to_owned(), count, true)),
+        )
+        .enumerate()
+        .map(|(i, (label, count, other))| Bin {
+            label,
+            lower: i as f64 - 0.5,
+            upper: i as f64 + 0.5,
+            count,
+            error: 0,
+            other,
+        })
         .collect();
-    let total = ret.iter().fold(0.0, |t, (_s, x)| t + *x as f64);
-    (ret, None, None, None, total, 1.0)
-}
-
-/// Generates a histogram from a vector of numerical string values.
-///
-/// This function takes a vector of strings which are expected to be parseable as floating-point
-/// numbers and a reference to the desired number of bars (bins) for the histogram. It returns
-/// a tuple containing the histogram as a vector of tuples where each tuple consists of a string
-/// representation of the bin's midpoint and the count of values in that bin, and three `Option`
-/// tuples representing the 25th, 50th, and 75th percentiles, respectively, if they can be computed.
-/// It also returns the total count of all values as a `f64`.
-///
-/// # Arguments
-///
-/// * `vals` - A reference to a vector of strings to be parsed into floating-point numbers.
-/// * `num_bars` - A reference to the number of bars (bins) the histogram should have.
-///
-/// # Returns
-///
-/// A tuple containing:
-/// - A vector of tuples, where each tuple contains a string representation of the bin's midpoint
-///   and the count of values in that bin.
-/// - An `Option<(f64, f64)>` for the 25th percentile, where the first `f64` is the normalized
-///   position of the percentile, and the second `f64` is the value at the 25th percentile.
-/// - An `Option<(f64, f64)>` for the 50th percentile (median), similar to the 25th percentile.
-/// - An `Option<(f64, f64)>` for the 75th percentile, similar to the 25th percentile.
-/// - The total count of all values as a `f64`.
-///
-/// # Panics
-///
-/// This function panics if the input vector `vals` contains no parseable floating-point numbers
-/// or if the first or last element cannot be parsed into a `f64`.
-fn histogram_from_numbers(
-    vals: &Vec<String>,
-    num_bars: &usize,
-) -> (
-    Vec<(String, usize)>,
-    Option<(f64, f64)>,
-    Option<(f64, f64)>,
-    Option<(f64, f64)>,
-    f64,
-    f64,
-) {
-    let sorted_nums = vals
+    let total = bins.iter().map(|b| b.count).sum();
+    Histogram {
+        bins,
+        quantiles: Vec::new(),
+        total,
+        range: None,
+        scale: Scale::Linear,
+        non_positive: 0,
+        stats: None,
+        rejected: Rejected::default(),
+        distinct: Some(distinct),
+    }
+}
+
+/// Numbers parsed from strings, in ascending order, and what was left out.
+struct Parsed {
+    sorted: Vec<f64>,
+    rejected: Rejected,
+}
+
+/// Sorted values split into the part that can be binned on a scale and the rest.
+struct Prepared<'a> {
+    /// The values that can be binned, in ascending order.
+    binned: &'a [f64],
+    /// `binned` mapped to the space in which the bins are equally spaced.
+    transformed: Vec<f64>,
+    /// Number of zero or negative values left out on a log scale.
+    non_positive: usize,
+    /// Number of values left out for falling outside the bins.
+    out_of_range: usize,
+    rejected: &'a Rejected,
+}
+
+impl<'a> Prepared<'a> {
+    fn new(parsed: &'a Parsed, scale: Scale) -> Self {
+        let sorted_nums = &parsed.sorted;
+        let non_positive = if scale.is_log() {
+            sorted_nums.partition_point(|&x| x <= 0.0)
+        } else {
+            0
+        };
+        let binned = &sorted_nums[non_positive..];
+        Prepared {
+            binned,
+            transformed: binned.iter().map(|&x| scale.forward(x)).collect(),
+            non_positive,
+            out_of_range: 0,
+            rejected: &parsed.rejected,
+        }
+    }
+
+    /// Leaves out the values outside `edges`, which are in the space of the scale, so that the
+    /// quantiles and statistics are those of the values counted into the bins.
+    fn keep_within(&mut self, edges: &[f64]) {
+        let (first, last) = (edges[0], edges[edges.len() - 1]);
+        let start = self.transformed.partition_point(|&t| t < first);
+        let end = self.transformed.partition_point(|&t| t <= last);
+        self.out_of_range = self.transformed.len() - (end - start);
+        self.binned = &self.binned[start..end];
+        self.transformed.truncate(end);
+        self.transformed.drain(..start);
+    }
+
+    /// Counts the values into bins with `edges` given in the space of the scale of `options`,
+    /// and computes the quantiles and statistics of the values, which must be within the edges
+    /// after [`Prepared::keep_within`].
+    fn histogram(&self, edges: &[f64], options: &HistogramOptions) -> Histogram {
+        let binned = self.binned;
+        let scale = options.scale;
+        let counts = binning::count_sorted(&self.transformed, edges);
+        let edges = edges.iter().map(|&e| scale.inverse(e)).collect::<Vec<_>>();
+
+        Histogram {
+            bins: binning::bins_from_counts(&edges, &counts),
+            quantiles: options
+                .quantiles
+                .iter()
+                .map(|&p| Quantile {
+                    p,
+                    value: binning::sorted_quantile(binned, p),
+                })
+                .collect(),
+            total: counts.iter().sum(),
+            range: Some((binned[0], binned[binned.len() - 1])),
+            scale,
+            non_positive: self.non_positive,
+            stats: Some(Stats::of_sorted(binned)),
+            rejected: Rejected {
+                out_of_range: self.rejected.out_of_range + self.out_of_range,
+                ..self.rejected.clone()
+            },
+            distinct: None,
+        }
+    }
+}
+
+/// The error for `count` values that all fall outside `edges`, which are in the space of `scale`.
+fn out_of_range(edges: &[f64], scale: Scale, count: usize) -> DataError {
+    DataError::OutOfRange {
+        min: scale.inverse(edges[0]),
+        max: scale.inverse(edges[edges.len() - 1]),
+        count,
+    }
+}
+
+/// Bin edges, in the space of `options.scale`, for sorted values already mapped to that space.
+fn edges_for(transformed: &[f64], options: &HistogramOptions) -> Result<Vec<f64>, DataError> {
+    match options.range {
+        Some((min, max)) => options.rule.range_edges(options.scale, min, max),
+        None => options
+            .rule
+            .edges(&Summary::of_sorted(transformed), options.scale),
+    }
+}
+
+/// Bins already parsed, non-empty values according to `options`.
+fn histogram_from_parsed(
+    parsed: &Parsed,
+    options: &HistogramOptions,
+) -> Result<Histogram, DataError> {
+    options.scale.validate()?;
+    options.validate_quantiles()?;
+    let mut prepared = Prepared::new(parsed, options.scale);
+    if prepared.binned.is_empty() {
+        return Err(DataError::NoPositiveValues {
+            count: prepared.non_positive,
+        });
+    }
+    let edges = edges_for(&prepared.transformed, options)?;
+    prepared.keep_within(&edges);
+    if prepared.binned.is_empty() {
+        return Err(out_of_range(&edges, options.scale, prepared.out_of_range));
+    }
+    Ok(prepared.histogram(&edges, options))
+}
+
+/// Generates a histogram from a slice of numerical string values.
+///
+/// Empty strings and values that cannot be parsed as `f64` are skipped. The bins are chosen by
+/// `options.rule`; rules that split `[min, max]` into equal-width bins spread them over a
+/// unit-wide range around the value instead if all values are equal. On a log scale, zero and
+/// negative values are counted in [`Histogram::non_positive`] and otherwise ignored.
+///
+/// The quantiles in `options.quantiles` of the values counted into the bins are returned in
+/// [`Histogram::quantiles`], interpolated between the closest values, and their descriptive
+/// statistics in [`Histogram::stats`]. Values that are not finite numbers, including empty
+/// ones, and values outside fixed edges or a fixed range are counted in
+/// [`Histogram::rejected`].
+///
+/// # Errors
+///
+/// Returns [`DataError::EmptyInput`] if `vals` is empty, [`DataError::NoNumericValues`] if
+/// none of the values parse as a number, [`DataError::OutOfRange`] if they all fall outside the
+/// bins and [`DataError::InvalidBins`], [`DataError::InvalidRange`] or
+/// [`DataError::InvalidQuantile`] if the options cannot be applied.
+pub fn histogram_from_numbers(
+    vals: &[String],
+    options: &HistogramOptions,
+) -> Result<Histogram, DataError> {
+    if vals.is_empty() {
+        return Err(DataError::EmptyInput);
+    }
+    let parsed = parse_sorted(vals);
+    if parsed.sorted.is_empty() {
+        return Err(DataError::NoNumericValues { lines: vals.len() });
+    }
+    histogram_from_parsed(&parsed, options)
+}
+
+/// The values that parse as finite numbers, in ascending order, and the rest.
+fn parse_sorted(vals: &[String]) -> Parsed {
+    let mut rejected = Rejected::default();
+    let mut sorted = vals
         .iter()
-        .filter(|x| x.len() > 0)
-        .filter_map(|x| x.parse::<f64>().ok())
-        .sorted_by(|x, y| compare_f64(x, y))
+        .filter_map(|val| rejected.parse(val))
         .collect::<Vec<_>>();
-    let min = *sorted_nums.first().unwrap();
-    let max = *sorted_nums.last().unwrap();
-    let range = max - min;
-    let delta = range / (*num_bars as f64);
-    let len_25 = ((sorted_nums.len() as f64) * 0.25) as usize;
-    let existing_counts: HashMap<usize, usize> = sorted_nums
+    sorted.sort_by(compare_f64);
+    Parsed { sorted, rejected }
+}
+
+/// Bins several named groups of values on the same bins, so that they can be compared.
+///
+/// Numeric bins are chosen from all the values together, as [`histogram_from_numbers`] would
+/// for a single group; each series keeps its own percentiles. Categorical bins are the distinct
+/// values of all groups, ordered and trimmed by their total count as in
+/// [`histogram_from_categories`].
+///
+/// # Errors
+///
+/// Returns the errors of [`histogram_from_numbers`] for the values of all groups together, and
+/// [`DataError::EmptySeries`] if only some of the groups have no values that can be binned.
+pub fn histograms_from_groups(
+    groups: &[(String, Vec<String>)],
+    options: &HistogramOptions,
+) -> Result<Vec<Series>, DataError> {
+    let lines = groups.iter().map(|(_, vals)| vals.len()).sum();
+    if lines == 0 {
+        return Err(DataError::EmptyInput);
+    }
+    if options.categorical {
+        return categories_from_groups(groups, options);
+    }
+    let parsed = groups
         .iter()
-        .map(|x| ((*x - min) / delta) as usize)
-        .group_by(|e| *e)
-        .into_iter()
-        .map(|(k, group_k)| (k, group_k.count()))
-        .collect();
-    let total = existing_counts.iter().fold(0.0, |t, (_, x)| t + *x as f64);
-    (
-        (0..*num_bars)
-            .map(|i| {
-                (
-                    format!("{:?}", min + ((i as f64 + 0.5) * delta)),
-                    *existing_counts.get(&(i as usize)).unwrap_or(&(0 as usize)),
-                )
+        .map(|(name, vals)| (name.clone(), parse_sorted(vals)))
+        .collect::<Vec<_>>();
+    series_from_parsed(&parsed, options, lines)
+}
+
+/// Bins the values kept in `series` again with `options`, e.g. with another number of bins.
+///
+/// If `options.range` is set, only the values within it are binned, and the quantiles and
+/// statistics are those of these values, so that part of the range can be looked at more
+/// closely. The values left out because they are not finite numbers or not positive are carried
+/// over from `series`.
+///
+/// # Errors
+///
+/// Returns the errors of [`histograms_from_groups`], and [`DataError::OutOfRange`] if no value
+/// falls within the range.
+pub fn rebin(series: &[Series], options: &HistogramOptions) -> Result<Vec<Series>, DataError> {
+    let parsed = series
+        .iter()
+        .map(|s| {
+            let sorted = s
+                .values
+                .iter()
+                .copied()
+                .filter(|x| options.range.is_none_or(|(lo, hi)| (lo..=hi).contains(x)))
+                .collect();
+            let rejected = s.histogram.rejected.clone();
+            (s.name.clone(), Parsed { sorted, rejected })
+        })
+        .collect::<Vec<_>>();
+    let lines = series.iter().map(|s| s.values.len()).sum();
+    if let Some((min, max)) = options.range {
+        if parsed.iter().all(|(_, p)| p.sorted.is_empty()) {
+            return Err(DataError::OutOfRange {
+                min,
+                max,
+                count: lines,
+            });
+        }
+    }
+    let mut rebinned = series_from_parsed(&parsed, options, lines)?;
+    for (new, old) in rebinned.iter_mut().zip(series) {
+        new.histogram.non_positive = old.histogram.non_positive;
+        new.lines = old.lines.clone();
+    }
+    Ok(rebinned)
+}
+
+/// Bins named, parsed groups of values on the same bins.
+fn series_from_parsed(
+    parsed: &[(String, Parsed)],
+    options: &HistogramOptions,
+    lines: usize,
+) -> Result<Vec<Series>, DataError> {
+    options.scale.validate()?;
+    options.validate_quantiles()?;
+    let mut prepared = parsed
+        .iter()
+        .map(|(_, parsed)| Prepared::new(parsed, options.scale))
+        .collect::<Vec<_>>();
+    if prepared.iter().all(|p| p.binned.is_empty()) {
+        let non_positive = prepared.iter().map(|p| p.non_positive).sum();
+        return Err(if non_positive > 0 {
+            DataError::NoPositiveValues {
+                count: non_positive,
+            }
+        } else {
+            DataError::NoNumericValues { lines }
+        });
+    }
+    if let Some(((name, _), _)) = parsed
+        .iter()
+        .zip(&prepared)
+        .find(|(_, p)| p.binned.is_empty())
+    {
+        return Err(DataError::EmptySeries(name.clone()));
+    }
+
+    let all = prepared
+        .iter()
+        .flat_map(|p| p.transformed.iter().copied())
+        .sorted_by(compare_f64)
+        .collect::<Vec<_>>();
+    let edges = edges_for(&all, options)?;
+    for p in &mut prepared {
+        p.keep_within(&edges);
+    }
+    if prepared.iter().all(|p| p.binned.is_empty()) {
+        let count = prepared.iter().map(|p| p.out_of_range).sum();
+        return Err(out_of_range(&edges, options.scale, count));
+    }
+    if let Some(((name, _), _)) = parsed
+        .iter()
+        .zip(&prepared)
+        .find(|(_, p)| p.binned.is_empty())
+    {
+        return Err(DataError::EmptySeries(name.clone()));
+    }
+    Ok(parsed
+        .iter()
+        .zip(&prepared)
+        .map(|((name, _), p)| Series {
+            name: name.clone(),
+            histogram: p.histogram(&edges, options),
+            values: p.binned.to_vec(),
+            lines: Vec::new(),
+        })
+        .collect())
+}
+
+fn categories_from_groups(
+    groups: &[(String, Vec<String>)],
+    options: &HistogramOptions,
+) -> Result<Vec<Series>, DataError> {
+    let counts = groups
+        .iter()
+        .flat_map(|(_, vals)| vals.iter().map(String::as_str))
+        .counts();
+    let distinct = Distinct {
+        count: counts.len(),
+        relative_error: 0.0,
+    };
+    let ranked = categories::rank(counts, options.top, options.order, options.descending);
+    let has_other = ranked.other.is_some();
+    let combined = categorical_histogram(ranked, distinct);
+    Ok(groups
+        .iter()
+        .map(|(name, vals)| {
+            let counts = vals.iter().map(String::as_str).counts();
+            let mut histogram = combined.clone();
+            let kept = if has_other {
+                histogram.bins.len() - 1
+            } else {
+                histogram.bins.len()
+            };
+            for bin in &mut histogram.bins[..kept] {
+                bin.count = counts.get(bin.label.as_str()).copied().unwrap_or(0);
+            }
+            let kept_total = histogram.bins[..kept]
+                .iter()
+                .map(|b| b.count)
+                .sum::<usize>();
+            if has_other {
+                histogram.bins[kept].count = vals.len() - kept_total;
+            }
+            histogram.total = vals.len();
+            histogram.distinct = Some(Distinct {
+                count: counts.len(),
+                relative_error: 0.0,
+            });
+            Series {
+                name: name.clone(),
+                histogram,
+                values: Vec::new(),
+                lines: Vec::new(),
+            }
+        })
+        .collect())
+}
+
+/// Number of fine-grained cells a [`StreamingHistogram`] counts into once it stops buffering.
+const STREAMING_RESOLUTION: usize = 4096;
+
+/// Builds a numeric histogram from values pushed one at a time, in bounded memory.
+///
+/// The first few thousand values are kept verbatim, and if the stream ends there the result is
+/// identical to [`histogram_from_numbers`]. Beyond that, values are counted into a fixed number
+/// of fine cells whose width doubles whenever a value lands outside them, and the requested
+/// bins are assembled from those cells when the stream is finished. Percentiles then come from
+/// [`P2Quantile`] estimators. Both are approximations, but memory no longer grows with the
+/// input. The moments in [`Histogram::stats`] stay exact, but the median absolute deviation is
+/// not available.
+///
+/// If the options fix the range or the bin edges, values are binned directly into exact bins
+/// instead, and values outside them are counted in [`Rejected::out_of_range`].
+#[derive(Clone, Debug)]
+pub struct StreamingHistogram {
+    options: HistogramOptions,
+    fixed_edges: Option<Vec<f64>>,
+    buffer: Vec<f64>,
+    cells: Vec<usize>,
+    origin: f64,
+    cell_width: f64,
+    min: f64,
+    max: f64,
+    mean: f64,
+    m2: f64,
+    total: usize,
+    non_positive: usize,
+    rejected: Rejected,
+    /// Moments of the counted values themselves, rather than in the space of the scale.
+    moments: Moments,
+    quantiles: Vec<P2Quantile>,
+    /// Estimates of the quartiles, for the bin rules that need the interquartile range.
+    quartiles: [P2Quantile; 2],
+}
+
+impl StreamingHistogram {
+    /// Creates an empty histogram.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DataError::InvalidBins`], [`DataError::InvalidRange`] or
+    /// [`DataError::InvalidQuantile`] if the options cannot be applied.
+    pub fn new(options: &HistogramOptions) -> Result<Self, DataError> {
+        options.rule.validate()?;
+        options.scale.validate()?;
+        options.validate_quantiles()?;
+        // Explicit edges do not depend on the data, so they are as good as a fixed range.
+        let fixed_edges = match (&options.range, &options.rule) {
+            (Some((min, max)), rule) => Some(rule.range_edges(options.scale, *min, *max)?),
+            (None, BinRule::Edges(edges)) => Some(binning::explicit_edges(edges, options.scale)?),
+            (None, _) => None,
+        };
+        Ok(StreamingHistogram {
+            options: options.clone(),
+            cells: fixed_edges
+                .as_ref()
+                .map_or_else(Vec::new, |edges| vec![0; edges.len() - 1]),
+            fixed_edges,
+            buffer: Vec::new(),
+            origin: 0.0,
+            cell_width: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            mean: 0.0,
+            m2: 0.0,
+            total: 0,
+            non_positive: 0,
+            rejected: Rejected::default(),
+            moments: Moments::default(),
+            quantiles: options
+                .quantiles
+                .iter()
+                .map(|&p| P2Quantile::new(p))
+                .collect(),
+            quartiles: [P2Quantile::new(0.25), P2Quantile::new(0.75)],
+        })
+    }
+
+    /// Number of values counted into the histogram so far.
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Number of values rejected for falling outside a fixed range or explicit edges.
+    pub fn out_of_range(&self) -> usize {
+        self.rejected.out_of_range
+    }
+
+    /// Number of zero or negative values rejected by a log scale.
+    pub fn non_positive(&self) -> usize {
+        self.non_positive
+    }
+
+    /// Values left out so far because they are not finite numbers.
+    pub fn rejected(&self) -> &Rejected {
+        &self.rejected
+    }
+
+    /// Parses a value and adds it to the histogram, or counts it in [`Histogram::rejected`] if
+    /// it is not a finite number.
+    pub fn push_value(&mut self, value: &str) {
+        if let Some(x) = self.rejected.parse(value) {
+            self.push(x);
+        }
+    }
+
+    /// Counts a line without the selected field in [`Rejected::missing`].
+    pub fn push_missing(&mut self) {
+        self.rejected.missing += 1;
+    }
+
+    /// Adds a value to the histogram. NaN and infinite values are counted in
+    /// [`Histogram::rejected`].
+    pub fn push(&mut self, x: f64) {
+        if self.rejected.check(x).is_none() {
+            return;
+        }
+        let scale = self.options.scale;
+        if scale.is_log() && x <= 0.0 {
+            self.non_positive += 1;
+            return;
+        }
+        let t = scale.forward(x);
+        if let Some(edges) = &self.fixed_edges {
+            match binning::bin_of(t, edges) {
+                Some(bin) => self.cells[bin] += 1,
+                None => {
+                    self.rejected.out_of_range += 1;
+                    return;
+                }
+            }
+        } else if self.cells.is_empty() {
+            self.buffer.push(x);
+            if self.buffer.len() == STREAMING_RESOLUTION {
+                self.spill_buffer();
+            }
+        } else {
+            self.count_into_cells(t);
+        }
+
+        self.min = self.min.min(x);
+        self.max = self.max.max(x);
+        self.total += 1;
+        let delta = t - self.mean;
+        self.mean += delta / self.total as f64;
+        self.m2 += delta * (t - self.mean);
+        self.moments.push(x);
+        for quantile in self.quantiles.iter_mut().chain(self.quartiles.iter_mut()) {
+            quantile.push(x);
+        }
+    }
+
+    /// Switches from buffering values to counting them into cells.
+    ///
+    /// The cells, like the running mean and variance, are kept in the space given by the scale.
+    fn spill_buffer(&mut self) {
+        let scale = self.options.scale;
+        let (min, max) = self
+            .buffer
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &x| {
+                (lo.min(x), hi.max(x))
+            });
+        let (min, max) = (scale.forward(min), scale.forward(max));
+        self.origin = min;
+        // Leave headroom above the buffered values so the grid does not grow straight away. A
+        // spread too small to split into cells of a normal width, which includes none at all,
+        // gets a unit-wide grid instead, since a zero width could never grow to fit new values.
+        let resolution = STREAMING_RESOLUTION as f64;
+        let width = 2.0 * (max / resolution - min / resolution);
+        self.cell_width = if width.is_normal() {
+            width
+        } else {
+            1.0 / resolution
+        };
+        self.cells = vec![0; STREAMING_RESOLUTION];
+        for x in std::mem::take(&mut self.buffer) {
+            self.count_into_cells(scale.forward(x));
+        }
+    }
+
+    fn count_into_cells(&mut self, x: f64) {
+        while x < self.origin {
+            self.grow_down();
+        }
+        while x >= self.origin + self.cell_width * STREAMING_RESOLUTION as f64 {
+            self.grow_up();
+        }
+        let cell = ((x - self.origin) / self.cell_width) as usize;
+        self.cells[cell.min(STREAMING_RESOLUTION - 1)] += 1;
+    }
+
+    /// Doubles the cell width, keeping the lower edge of the grid where it is.
+    fn grow_up(&mut self) {
+        let half = STREAMING_RESOLUTION / 2;
+        for i in 0..half {
+            self.cells[i] = self.cells[2 * i] + self.cells[2 * i + 1];
+        }
+        self.cells[half..].iter_mut().for_each(|c| *c = 0);
+        self.cell_width *= 2.0;
+    }
+
+    /// Doubles the cell width, keeping the upper edge of the grid where it is.
+    fn grow_down(&mut self) {
+        let half = STREAMING_RESOLUTION / 2;
+        for i in (0..half).rev() {
+            self.cells[half + i] = self.cells[2 * i] + self.cells[2 * i + 1];
+        }
+        self.cells[..half].iter_mut().for_each(|c| *c = 0);
+        self.origin -= self.cell_width * STREAMING_RESOLUTION as f64;
+        self.cell_width *= 2.0;
+    }
+
+    /// Produces the histogram of every value pushed so far.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DataError::EmptyInput`] if no value has been counted and
+    /// [`DataError::InvalidBins`] if the bin rule is invalid.
+    pub fn finish(self) -> Result<Histogram, DataError> {
+        if self.total == 0 {
+            return Err(DataError::EmptyInput);
+        }
+        if self.cells.is_empty() {
+            let mut parsed = Parsed {
+                sorted: self.buffer,
+                rejected: self.rejected,
+            };
+            parsed.sorted.sort_by(compare_f64);
+            let mut hist = histogram_from_parsed(&parsed, &self.options)?;
+            hist.non_positive = self.non_positive;
+            return Ok(hist);
+        }
+
+        let scale = self.options.scale;
+        let (edges, counts) = match &self.fixed_edges {
+            Some(edges) => (edges.clone(), self.cells.clone()),
+            None => {
+                let (min, max) = (scale.forward(self.min), scale.forward(self.max));
+                let quartile = |i: usize| {
+                    self.quartiles[i]
+                        .estimate()
+                        .map_or(0.0, |q| scale.forward(q))
+                };
+                let summary = Summary {
+                    n: self.total,
+                    min,
+                    max,
+                    std_dev: (self.m2 / (self.total as f64 - 1.0).max(1.0)).sqrt(),
+                    iqr: quartile(1) - quartile(0),
+                };
+                let edges = self.options.rule.edges(&summary, scale)?;
+                let mut counts = vec![0; edges.len() - 1];
+                for (i, &count) in self.cells.iter().enumerate() {
+                    if count == 0 {
+                        continue;
+                    }
+                    let mid = (self.origin + (i as f64 + 0.5) * self.cell_width).clamp(min, max);
+                    if let Some(bin) = binning::bin_of(mid, &edges) {
+                        counts[bin] += count;
+                    }
+                }
+                (edges, counts)
+            }
+        };
+        let edges = edges.iter().map(|&e| scale.inverse(e)).collect::<Vec<_>>();
+
+        Ok(Histogram {
+            bins: binning::bins_from_counts(&edges, &counts),
+            quantiles: self
+                .quantiles
+                .iter()
+                .filter_map(|q| q.estimate().map(|value| Quantile { p: q.p(), value }))
+                .collect(),
+            total: counts.iter().sum(),
+            range: Some((self.min, self.max)),
+            scale,
+            non_positive: self.non_positive,
+            stats: Some(self.moments.stats()),
+            rejected: self.rejected,
+            distinct: None,
+        })
+    }
+
+    /// Produces the histogram of the values of `lines` input lines, explaining why it is empty
+    /// if none of them could be counted.
+    fn finish_lines(self, lines: usize) -> Result<Histogram, DataError> {
+        if lines == 0 {
+            return Err(DataError::EmptyInput);
+        }
+        if self.total == 0 {
+            return Err(if self.rejected.out_of_range > 0 {
+                let edges = self.fixed_edges.as_deref();
+                let edges = edges.expect("only fixed edges leave values out");
+                out_of_range(edges, self.options.scale, self.rejected.out_of_range)
+            } else if self.non_positive > 0 {
+                DataError::NoPositiveValues {
+                    count: self.non_positive,
+                }
+            } else if self.rejected.missing == lines {
+                DataError::NoFields { lines }
+            } else {
+                DataError::NoNumericValues { lines }
+            });
+        }
+        self.finish()
+    }
+}
+
+/// Number of categories a [`StreamingCategories`] counts exactly before it starts to estimate.
+const STREAMING_CATEGORIES: usize = 4096;
+
+/// Counts the most frequent categories of values pushed one at a time, in bounded memory.
+///
+/// Up to a few thousand categories, or ten times [`HistogramOptions::top`] if that is more, are
+/// counted exactly, and if the stream has no more than that the result is identical to
+/// [`histogram_from_categories`]. Beyond that, the categories are tracked with a
+/// [`SpaceSaving`] summary: each bin's [`Bin::error`] bounds how much its count may overstate
+/// the true count, and the categories that are no longer tracked are counted in the
+/// [`OTHER_LABEL`] bar. Unless [`HistogramOptions::top`] is set, only the categories that are
+/// certainly more frequent than any untracked one get a bar of their own. The number of distinct
+/// categories is then estimated with a [`HyperLogLog`].
+#[derive(Clone, Debug)]
+pub struct StreamingCategories {
+    options: HistogramOptions,
+    counters: SpaceSaving,
+    distinct: HyperLogLog,
+    total: usize,
+    /// Number of lines without the selected field.
+    missing: usize,
+}
+
+impl StreamingCategories {
+    pub fn new(options: &HistogramOptions) -> Self {
+        let capacity = STREAMING_CATEGORIES.max(options.top.unwrap_or(0).saturating_mul(10));
+        StreamingCategories {
+            options: options.clone(),
+            counters: SpaceSaving::new(capacity),
+            distinct: HyperLogLog::default(),
+            total: 0,
+            missing: 0,
+        }
+    }
+
+    /// Number of values counted so far.
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    pub fn push(&mut self, value: &str) {
+        self.counters.push(value);
+        self.distinct.push(value);
+        self.total += 1;
+    }
+
+    /// Counts a line without the selected field in [`Rejected::missing`].
+    pub fn push_missing(&mut self) {
+        self.missing += 1;
+    }
+
+    /// Produces the histogram of every value pushed so far.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DataError::EmptyInput`] if no value has been pushed, or
+    /// [`DataError::NoFields`] if only lines without the selected field have.
+    pub fn finish(self) -> Result<Histogram, DataError> {
+        if self.total == 0 {
+            return Err(if self.missing > 0 {
+                DataError::NoFields {
+                    lines: self.missing,
+                }
+            } else {
+                DataError::EmptyInput
+            });
+        }
+        let counters = self.counters.counters();
+        let exact = self.counters.is_exact();
+        let distinct = if exact {
+            Distinct {
+                count: counters.len(),
+                relative_error: 0.0,
+            }
+        } else {
+            Distinct {
+                count: self.distinct.estimate().round() as usize,
+                relative_error: self.distinct.relative_error(),
+            }
+        };
+        // Without a limit, only the categories that are certainly more frequent than any that
+        // is no longer tracked, which was seen at most as often as the smallest counter, get a
+        // bar.
+        let top = self.options.top.or_else(|| {
+            let min = counters.iter().map(|c| c.count).min().unwrap_or(0);
+            (!exact).then(|| {
+                let certain = counters.iter().filter(|c| c.count - c.error > min).count();
+                certain.max(1)
             })
-            .collect::<Vec<(String, usize)>>(),
-        Some(((sorted_nums[len_25] - min) / range, sorted_nums[len_25])),
-        Some((
-            (sorted_nums[len_25 * 2] - min) / range,
-            sorted_nums[len_25 * 2],
-        )),
-        Some((
-            (sorted_nums[len_25 * 3] - min) / range,
-            sorted_nums[len_25 * 3],
-        )),
-        total,
-        range,
-    )
+        });
+        let ranked = categories::rank(
+            counters.iter().map(|c| (c.key.as_str(), c.count)).collect(),
+            top,
+            self.options.order,
+            self.options.descending,
+        );
+        let errors = counters
+            .iter()
+            .map(|c| (c.key.as_str(), c.error))
+            .collect::<HashMap<_, _>>();
+        let has_other = ranked.other.is_some();
+        let mut hist = categorical_histogram(ranked, distinct);
+        let kept = hist.bins.len() - usize::from(has_other);
+        for bin in &mut hist.bins[..kept] {
+            bin.error = errors[bin.label.as_str()];
+        }
+        if has_other {
+            // The rest is the total less the kept counts, which may each be overstated.
+            hist.bins[kept].error = hist.bins[..kept].iter().map(|b| b.error).sum();
+        }
+        hist.rejected.missing = self.missing;
+        Ok(hist)
+    }
 }
 
+/// Reads every value of `input` and bins it, either as numbers or as categories.
 pub fn compute_histogram(
-    num_bins: usize,
     input: InputSource,
-    categorical: bool,
-) -> (
-    Vec<(String, usize)>,
-    Option<(f64, f64)>,
-    Option<(f64, f64)>,
-    Option<(f64, f64)>,
-    f64,
-    f64,
-) {
-    let max_num_lines = 10_000_000;
-
-    let vals = match input {
-        InputSource::Stdin => read_from_stdin(max_num_lines),
-        InputSource::FileName(file_name) => read_from_file(&file_name, max_num_lines),
-    };
+    options: &HistogramOptions,
+) -> Result<Histogram, DataError> {
+    let InputValues {
+        values: vals,
+        missing,
+        ..
+    } = read_input(&input, options)?;
+    if vals.is_empty() && missing > 0 {
+        return Err(DataError::NoFields { lines: missing });
+    }
 
-    if categorical {
-        histogram_from_categories(&vals)
+    let mut hist = if options.categorical {
+        histogram_from_categories(&vals, options)
     } else {
-        histogram_from_numbers(&vals, &num_bins)
+        histogram_from_numbers(&vals, options)
+    }?;
+    hist.rejected.missing = missing;
+    Ok(hist)
+}
+
+/// Reads several named inputs, each split into series by the group column if `options` has
+/// one, and bins them all on the same bins with [`histograms_from_groups`].
+///
+/// Grouped series are named after their key, prefixed with the input name if there are several
+/// inputs.
+pub fn compute_series(
+    inputs: &[(String, InputSource)],
+    options: &HistogramOptions,
+) -> Result<Vec<Series>, DataError> {
+    let mut groups = Vec::new();
+    let mut lines = Vec::new();
+    let mut missing = Vec::new();
+    for (name, input) in inputs {
+        match options.field.as_ref().filter(|f| f.group_by.is_some()) {
+            Some(field) => {
+                for (key, vals) in read_groups(input, field, options)? {
+                    let name = if inputs.len() > 1 {
+                        format!("{}: {}", name, key)
+                    } else {
+                        key
+                    };
+                    groups.push((name, vals.values));
+                    lines.push(vals.lines);
+                    missing.push(vals.missing);
+                }
+            }
+            None => {
+                let vals = read_input(input, options)?;
+                if vals.values.is_empty() && vals.missing > 0 {
+                    return Err(DataError::NoFields {
+                        lines: vals.missing,
+                    });
+                }
+                groups.push((name.clone(), vals.values));
+                lines.push(vals.lines);
+                missing.push(vals.missing);
+            }
+        }
+    }
+    let mut series = histograms_from_groups(&groups, options)?;
+    for (s, missing) in series.iter_mut().zip(missing) {
+        s.histogram.rejected.missing = missing;
+    }
+    if options.keep_lines {
+        for ((s, (_, values)), lines) in series.iter_mut().zip(groups).zip(lines) {
+            s.lines = InputValues {
+                values,
+                lines,
+                ..InputValues::default()
+            }
+            .into_lines();
+        }
+    }
+    Ok(series)
+}
+
+/// Bins the numbers in `input` with a [`StreamingHistogram`], without holding the input in
+/// memory.
+pub fn compute_streaming_histogram(
+    input: InputSource,
+    options: &HistogramOptions,
+) -> Result<Histogram, DataError> {
+    let mut hist = StreamingHistogram::new(options)?;
+    let mut lines = 0;
+    for_each_value(&input, options.field.as_ref(), |number, _, value, _| {
+        lines += 1;
+        match value {
+            Some(value) => {
+                options.check_value(number, value)?;
+                hist.push_value(value);
+            }
+            None => {
+                options.check_field(number)?;
+                hist.push_missing();
+            }
+        }
+        Ok(())
+    })?;
+
+    hist.finish_lines(lines)
+}
+
+/// Counts the categories in `input` with [`StreamingCategories`], without holding the input in
+/// memory.
+pub fn compute_streaming_categories(
+    input: InputSource,
+    options: &HistogramOptions,
+) -> Result<Histogram, DataError> {
+    let mut categories = StreamingCategories::new(options);
+    for_each_value(&input, options.field.as_ref(), |_, _, value, _| {
+        match value {
+            Some(value) => categories.push(value),
+            None => categories.push_missing(),
+        }
+        Ok(())
+    })?;
+    categories.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A reader that always fails, like a directory opened as a file.
+    struct Failing;
+
+    impl io::Read for Failing {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::other("always fails"))
+        }
+    }
+
+    /// Writes `contents` to a file in a directory of its own, named after the test, for reading
+    /// as an input.
+    fn input_file(test: &str, contents: &[u8]) -> InputSource {
+        let dir = std::env::temp_dir().join(format!("hist3-{}-{}", test, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("input");
+        std::fs::write(&path, contents).unwrap();
+        InputSource::FileName(path.to_str().unwrap().to_owned())
+    }
+
+    #[test]
+    fn values_that_are_not_utf8_are_rejected() {
+        let input = input_file("not-utf8", b"1\n2\xff\n3\n\xfe\n");
+        let hist = compute_histogram(input, &HistogramOptions::default()).unwrap();
+        assert_eq!(hist.total, 2);
+        assert_eq!(hist.rejected.unparsed, 2);
+        assert_eq!(hist.rejected.samples, ["2\u{fffd}", "\u{fffd}"]);
+    }
+
+    #[test]
+    fn only_the_other_bar_takes_categories_without_a_bar() {
+        let lines = ["(other)", "b", "a", "a", "c"]
+            .iter()
+            .map(|&value| InputLine {
+                text: format!("line {}", value),
+                value: value.to_owned(),
+            })
+            .collect::<Vec<_>>();
+        let contents = "(other)\nb\na\na\n";
+        let categorical = HistogramOptions {
+            categorical: true,
+            order: CategoryOrder::Label,
+            descending: true,
+            ..HistogramOptions::default()
+        };
+        // A category named like the catch-all bar is still only itself, even in the last bar.
+        let hist = compute_histogram(input_file("other-label", contents.as_bytes()), &categorical)
+            .unwrap();
+        assert!(hist.bins.iter().all(|bin| !bin.other));
+        assert_eq!(hist.bins[2].label, OTHER_LABEL);
+        let selected = HashSet::from([2]);
+        assert_eq!(hist.lines_in_bins(&selected, &lines), ["line (other)"]);
+
+        let top = HistogramOptions {
+            top: Some(1),
+            ..categorical
+        };
+        let hist = compute_histogram(input_file("other-bar", contents.as_bytes()), &top).unwrap();
+        let labels = hist.bins.iter().map(|bin| (bin.label.as_str(), bin.other));
+        assert_eq!(
+            labels.collect::<Vec<_>>(),
+            [("a", false), (OTHER_LABEL, true)]
+        );
+        let selected = HashSet::from([1]);
+        assert_eq!(
+            hist.lines_in_bins(&selected, &lines),
+            ["line (other)", "line b", "line c"]
+        );
+    }
+
+    #[test]
+    fn lines_without_the_field_are_rejected() {
+        let input = || input_file("missing-field", b"a,1\nb\nc,3\n\nd,4\n");
+        let options = HistogramOptions {
+            field: Some(FieldSelector {
+                column: Column::Index(2),
+                delimiter: None,
+                header: false,
+                group_by: None,
+            }),
+            ..HistogramOptions::default()
+        };
+        let hist = compute_histogram(input(), &options).unwrap();
+        assert_eq!((hist.total, hist.rejected.missing), (3, 2));
+        let hist = compute_streaming_histogram(input(), &options).unwrap();
+        assert_eq!((hist.total, hist.rejected.missing), (3, 2));
+        let categorical = HistogramOptions {
+            categorical: true,
+            ..options.clone()
+        };
+        let hist = compute_histogram(input(), &categorical).unwrap();
+        assert_eq!((hist.total, hist.rejected.missing), (3, 2));
+        let strict = HistogramOptions {
+            strict: true,
+            ..options.clone()
+        };
+        assert!(matches!(
+            compute_histogram(input(), &strict),
+            Err(DataError::MissingField { line: 2 })
+        ));
+        let none = input_file("no-field", b"1\n2\n");
+        assert!(matches!(
+            compute_histogram(none, &options),
+            Err(DataError::NoFields { lines: 2 })
+        ));
+    }
+
+    #[test]
+    fn streaming_histogram_of_a_subnormal_spread() {
+        let mut hist = StreamingHistogram::new(&HistogramOptions::default()).unwrap();
+        for i in 0..2 * STREAMING_RESOLUTION {
+            hist.push(if i % 2 == 0 { 0.0 } else { 5e-324 });
+        }
+        hist.push(1.0);
+        let hist = hist.finish().unwrap();
+        assert_eq!(hist.total, 2 * STREAMING_RESOLUTION + 1);
+        assert_eq!(hist.range, Some((0.0, 1.0)));
+    }
+
+    #[test]
+    fn lossy_lines_replace_bytes_that_are_not_utf8() {
+        let input: &[u8] = b"1\r\n2\xff\n\xfe\n3";
+        let lines: Vec<_> = lossy_lines(input).map(Result::unwrap).collect();
+        assert_eq!(lines, ["1", "2\u{fffd}", "\u{fffd}", "3"]);
+    }
+
+    #[test]
+    fn lossy_lines_end_at_a_read_error() {
+        let reader = io::BufReader::new(Failing);
+        assert_eq!(lossy_lines(reader).map_while(Result::ok).count(), 0);
     }
 }
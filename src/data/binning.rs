@@ -0,0 +1,394 @@
+//! Choosing bin edges for numeric histograms.
+
+use super::{Bin, DataError};
+
+/// Upper bound on the number of bins a count, a data-dependent rule or a bin width may produce.
+const MAX_BINS: usize = 10_000;
+
+/// How the range of a numeric histogram is split into bins.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BinRule {
+    /// A fixed number of equal-width bins between the smallest and largest value.
+    Count(usize),
+    /// `ceil(log2(n)) + 1` equal-width bins.
+    Sturges,
+    /// Equal-width bins about `3.49 * stddev * n^(-1/3)` wide.
+    Scott,
+    /// Equal-width bins about `2 * IQR * n^(-1/3)` wide.
+    FreedmanDiaconis,
+    /// `ceil(sqrt(n))` equal-width bins.
+    SquareRoot,
+    /// Bins of the given width, aligned to multiples of it so that the edges do not move with
+    /// the smallest value.
+    Width(f64),
+    /// Explicit, strictly increasing bin edges. Values outside them are left out, and counted in
+    /// [`Rejected::out_of_range`](super::Rejected::out_of_range).
+    Edges(Vec<f64>),
+}
+
+impl Default for BinRule {
+    fn default() -> Self {
+        BinRule::Count(20)
+    }
+}
+
+/// Whether numeric bins are equally spaced in the values or in their logarithm.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Scale {
+    #[default]
+    Linear,
+    /// Bins equally spaced in `log(x)` to the given base. Only positive values can be binned.
+    Log(f64),
+}
+
+impl Scale {
+    pub fn is_log(&self) -> bool {
+        matches!(self, Scale::Log(_))
+    }
+
+    pub fn validate(&self) -> Result<(), DataError> {
+        match self {
+            Scale::Log(base) if !(base.is_finite() && *base > 0.0 && *base != 1.0) => Err(
+                DataError::InvalidBins(format!("invalid logarithm base {}", base)),
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    /// Maps a value to the space in which bins are equally spaced.
+    pub fn forward(&self, x: f64) -> f64 {
+        match self {
+            Scale::Linear => x,
+            Scale::Log(base) => x.ln() / base.ln(),
+        }
+    }
+
+    /// The inverse of [`Scale::forward`].
+    pub fn inverse(&self, t: f64) -> f64 {
+        match self {
+            Scale::Linear => t,
+            Scale::Log(base) => base.powf(t),
+        }
+    }
+
+    /// The derivative of [`Scale::forward`] at `x`, which turns a density of the values into a
+    /// density of their image when divided by it.
+    pub fn stretch(&self, x: f64) -> f64 {
+        match self {
+            Scale::Linear => 1.0,
+            Scale::Log(base) => 1.0 / (x * base.ln()),
+        }
+    }
+}
+
+/// The sample statistics the data-dependent rules are based on.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Summary {
+    pub n: usize,
+    pub min: f64,
+    pub max: f64,
+    pub std_dev: f64,
+    pub iqr: f64,
+}
+
+impl Summary {
+    /// Summarises already sorted, non-empty values.
+    pub fn of_sorted(sorted_nums: &[f64]) -> Self {
+        let n = sorted_nums.len() as f64;
+        let mean = sorted_nums.iter().sum::<f64>() / n;
+        let variance =
+            sorted_nums.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0).max(1.0);
+        Summary {
+            n: sorted_nums.len(),
+            min: sorted_nums[0],
+            max: sorted_nums[sorted_nums.len() - 1],
+            std_dev: variance.sqrt(),
+            iqr: sorted_quantile(sorted_nums, 0.75) - sorted_quantile(sorted_nums, 0.25),
+        }
+    }
+}
+
+/// The value at probability `p` of already sorted, non-empty values, interpolating linearly
+/// between the two closest ranks (type 7 of Hyndman & Fan, as in R and NumPy).
+pub(crate) fn sorted_quantile(sorted_nums: &[f64], p: f64) -> f64 {
+    let rank = (sorted_nums.len() - 1) as f64 * p.clamp(0.0, 1.0);
+    let below = rank.floor() as usize;
+    let above = (below + 1).min(sorted_nums.len() - 1);
+    let (lo, hi) = (sorted_nums[below], sorted_nums[above]);
+    if lo == hi {
+        lo
+    } else {
+        lo + (rank - below as f64) * (hi - lo)
+    }
+}
+
+impl BinRule {
+    /// Checks the parameters that do not depend on the data.
+    pub fn validate(&self) -> Result<(), DataError> {
+        match self {
+            BinRule::Count(0) => Err(DataError::InvalidBins(
+                "the number of bins must be at least 1".to_owned(),
+            )),
+            BinRule::Count(count) if *count > MAX_BINS => Err(DataError::InvalidBins(format!(
+                "{} bins are more than the limit of {}",
+                count, MAX_BINS
+            ))),
+            BinRule::Width(w) if !(w.is_finite() && *w > 0.0) => Err(DataError::InvalidBins(
+                format!("bin width must be positive, got {}", w),
+            )),
+            BinRule::Edges(edges) if edges.len() < 2 => Err(DataError::InvalidBins(
+                "at least two bin edges are needed".to_owned(),
+            )),
+            BinRule::Edges(edges)
+                if edges.iter().any(|e| !e.is_finite())
+                    || edges.windows(2).any(|w| w[0] >= w[1]) =>
+            {
+                Err(DataError::InvalidBins(
+                    "bin edges must be finite and strictly increasing".to_owned(),
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Computes the bin edges for a sample described by `summary`.
+    ///
+    /// Both `summary` and the returned edges are in the space given by `scale`, so bin widths
+    /// are in units of the logarithm on a log scale. Explicit edges are given as values.
+    pub(crate) fn edges(&self, summary: &Summary, scale: Scale) -> Result<Vec<f64>, DataError> {
+        self.validate()?;
+        let n = summary.n.max(1) as f64;
+        let (min, max) = (summary.min, summary.max);
+        Ok(match self {
+            BinRule::Count(count) => equal_edges(min, max, *count),
+            BinRule::Sturges => equal_edges(min, max, sturges(n)),
+            BinRule::SquareRoot => equal_edges(min, max, (n.sqrt().ceil() as usize).max(1)),
+            BinRule::Scott => equal_edges(
+                min,
+                max,
+                count_for_width(summary, 3.49 * summary.std_dev / n.cbrt()),
+            ),
+            BinRule::FreedmanDiaconis => equal_edges(
+                min,
+                max,
+                count_for_width(summary, 2.0 * summary.iqr / n.cbrt()),
+            ),
+            BinRule::Width(width) => {
+                let start = (min / width).floor() * width;
+                let count = (((max - start) / width).ceil() as usize).max(1);
+                if count > MAX_BINS {
+                    return Err(DataError::InvalidBins(format!(
+                        "a bin width of {} gives {} bins, more than the limit of {}",
+                        width, count, MAX_BINS
+                    )));
+                }
+                (0..=count).map(|i| start + (i as f64) * width).collect()
+            }
+            BinRule::Edges(edges) => explicit_edges(edges, scale)?,
+        })
+    }
+
+    /// Splits the fixed range `[min, max]` into bins, in the space given by `scale`.
+    ///
+    /// Only [`BinRule::Count`] can be combined with a fixed range.
+    pub(crate) fn range_edges(
+        &self,
+        scale: Scale,
+        min: f64,
+        max: f64,
+    ) -> Result<Vec<f64>, DataError> {
+        let valid = min.is_finite() && max.is_finite() && min < max;
+        if !valid || (scale.is_log() && min <= 0.0) {
+            return Err(DataError::InvalidRange { min, max });
+        }
+        match self {
+            BinRule::Count(count) => {
+                self.validate()?;
+                Ok(equal_edges(scale.forward(min), scale.forward(max), *count))
+            }
+            _ => Err(DataError::InvalidBins(
+                "a fixed range can only be split into a number of bins".to_owned(),
+            )),
+        }
+    }
+}
+
+/// Maps explicit, already validated bin edges to the space given by `scale`.
+pub(crate) fn explicit_edges(edges: &[f64], scale: Scale) -> Result<Vec<f64>, DataError> {
+    if scale.is_log() && edges[0] <= 0.0 {
+        return Err(DataError::InvalidBins(
+            "bin edges must be positive on a log scale".to_owned(),
+        ));
+    }
+    Ok(edges.iter().map(|&e| scale.forward(e)).collect())
+}
+
+fn sturges(n: f64) -> usize {
+    n.log2().ceil() as usize + 1
+}
+
+/// Number of bins of roughly `width` needed to cover the sample, falling back to Sturges' rule
+/// when the spread statistic is zero.
+fn count_for_width(summary: &Summary, width: f64) -> usize {
+    if width.is_finite() && width > 0.0 {
+        (((summary.max - summary.min) / width).ceil() as usize).clamp(1, MAX_BINS)
+    } else {
+        sturges(summary.n.max(1) as f64)
+    }
+}
+
+/// Widens a degenerate `[min, max]` range so that it can be split into bins.
+pub(crate) fn bin_range(min: f64, max: f64) -> (f64, f64) {
+    if max > min {
+        (min, max)
+    } else {
+        (min - 0.5, max + 0.5)
+    }
+}
+
+/// Edges of `count` equal-width bins covering `[min, max]`.
+pub(crate) fn equal_edges(min: f64, max: f64, count: usize) -> Vec<f64> {
+    let (lo, hi) = bin_range(min, max);
+    let count = count.max(1);
+    let delta = (hi - lo) / (count as f64);
+    (0..count)
+        .map(|i| lo + (i as f64) * delta)
+        .chain(std::iter::once(hi))
+        .collect()
+}
+
+/// Index of the bin that contains `x`, treating the last bin as closed on the right.
+pub(crate) fn bin_of(x: f64, edges: &[f64]) -> Option<usize> {
+    let last = edges.len() - 1;
+    if x < edges[0] || x > edges[last] {
+        return None;
+    }
+    Some((edges.partition_point(|&e| e <= x) - 1).min(last - 1))
+}
+
+/// Counts already sorted values into the bins delimited by `edges`.
+pub(crate) fn count_sorted(sorted_nums: &[f64], edges: &[f64]) -> Vec<usize> {
+    let num_bins = edges.len() - 1;
+    let mut counts = vec![0; num_bins];
+    let mut bin = 0;
+    for &x in sorted_nums {
+        if x < edges[0] || x > edges[num_bins] {
+            continue;
+        }
+        while bin + 1 < num_bins && x >= edges[bin + 1] {
+            bin += 1;
+        }
+        counts[bin] += 1;
+    }
+    counts
+}
+
+pub(crate) fn bins_from_counts(edges: &[f64], counts: &[usize]) -> Vec<Bin> {
+    edges
+        .windows(2)
+        .zip(counts.iter())
+        .map(|(edge, &count)| Bin {
+            label: format!("{:.4} - {:.4}", edge[0], edge[1]),
+            lower: edge[0],
+            upper: edge[1],
+            count,
+            error: 0,
+            other: false,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edges_of(rule: BinRule, values: &[f64]) -> Result<Vec<f64>, DataError> {
+        rule.edges(&Summary::of_sorted(values), Scale::Linear)
+    }
+
+    const ONE_TO_EIGHT: [f64; 8] = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0];
+
+    #[test]
+    fn edges_for_each_rule() {
+        let bins = |rule| edges_of(rule, &ONE_TO_EIGHT).unwrap().len() - 1;
+        assert_eq!(
+            edges_of(BinRule::Count(7), &ONE_TO_EIGHT).unwrap(),
+            ONE_TO_EIGHT
+        );
+        assert_eq!(
+            edges_of(BinRule::Sturges, &ONE_TO_EIGHT).unwrap(),
+            [1.0, 2.75, 4.5, 6.25, 8.0]
+        );
+        assert_eq!(bins(BinRule::SquareRoot), 3);
+        // 3.49 * sqrt(6) / 2 and 2 * 3.5 / 2 wide.
+        assert_eq!(bins(BinRule::Scott), 2);
+        assert_eq!(bins(BinRule::FreedmanDiaconis), 2);
+        assert_eq!(
+            edges_of(BinRule::Width(3.0), &ONE_TO_EIGHT).unwrap(),
+            [0.0, 3.0, 6.0, 9.0]
+        );
+        assert_eq!(
+            edges_of(BinRule::Edges(vec![0.0, 5.0, 6.0]), &ONE_TO_EIGHT).unwrap(),
+            [0.0, 5.0, 6.0]
+        );
+    }
+
+    #[test]
+    fn edges_of_a_single_value() {
+        assert_eq!(
+            edges_of(BinRule::Count(2), &[5.0]).unwrap(),
+            [4.5, 5.0, 5.5]
+        );
+        // No spread falls back to Sturges' rule.
+        assert_eq!(edges_of(BinRule::Scott, &[5.0, 5.0]).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn explicit_edges_on_a_log_scale() {
+        let summary = Summary::of_sorted(&[1.0, 10.0]);
+        let rule = BinRule::Edges(vec![1.0, 10.0, 100.0]);
+        assert_eq!(
+            rule.edges(&summary, Scale::Log(10.0)).unwrap(),
+            [0.0, 1.0, 2.0]
+        );
+        let rule = BinRule::Edges(vec![0.0, 10.0]);
+        assert!(matches!(
+            rule.edges(&summary, Scale::Log(10.0)),
+            Err(DataError::InvalidBins(_))
+        ));
+    }
+
+    #[test]
+    fn too_many_bins_are_rejected() {
+        assert!(BinRule::Count(MAX_BINS).validate().is_ok());
+        for rule in [BinRule::Count(0), BinRule::Count(MAX_BINS + 1)] {
+            assert!(matches!(rule.validate(), Err(DataError::InvalidBins(_))));
+        }
+        assert!(matches!(
+            edges_of(BinRule::Width(0.001), &[0.0, 100.0]),
+            Err(DataError::InvalidBins(_))
+        ));
+        assert!(matches!(
+            BinRule::Count(MAX_BINS + 1).range_edges(Scale::Linear, 0.0, 1.0),
+            Err(DataError::InvalidBins(_))
+        ));
+        // The data-dependent rules are capped instead.
+        let mut outlier = (0..1000).map(|i| i as f64 * 1e-6).collect::<Vec<_>>();
+        outlier.push(1e6);
+        assert_eq!(
+            edges_of(BinRule::FreedmanDiaconis, &outlier).unwrap().len(),
+            MAX_BINS + 1
+        );
+    }
+
+    #[test]
+    fn values_on_the_edges() {
+        let edges = [0.0, 1.0, 2.0];
+        let bins = [-0.1, 0.0, 0.999, 1.0, 2.0, 2.1].map(|x| bin_of(x, &edges));
+        assert_eq!(bins, [None, Some(0), Some(0), Some(1), Some(1), None]);
+        assert_eq!(
+            count_sorted(&[-1.0, 0.0, 0.5, 1.0, 2.0, 3.0], &edges),
+            [2, 2]
+        );
+    }
+}
@@ -0,0 +1,136 @@
+//! Ordering and trimming the distinct values of categorical histograms.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// Label of the bar that collects the categories left out by [`super::HistogramOptions::top`].
+pub const OTHER_LABEL: &str = "(other)";
+
+/// The order of the bars of a categorical histogram.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum CategoryOrder {
+    /// By count, ties broken by label.
+    #[default]
+    Count,
+    /// By label, character by character.
+    Label,
+    /// By label, comparing runs of digits as numbers, so that `item2` comes before `item10`.
+    Natural,
+}
+
+impl CategoryOrder {
+    /// Compares two `(label, count)` pairs, smallest first unless `descending`. Ties in count
+    /// stay in ascending label order either way.
+    fn compare(self, a: &(String, usize), b: &(String, usize), descending: bool) -> Ordering {
+        let flip = |ord: Ordering| if descending { ord.reverse() } else { ord };
+        match self {
+            CategoryOrder::Count => flip(a.1.cmp(&b.1)).then_with(|| a.0.cmp(&b.0)),
+            CategoryOrder::Label => flip(a.0.cmp(&b.0)),
+            CategoryOrder::Natural => flip(natural_cmp(&a.0, &b.0)),
+        }
+    }
+}
+
+/// Compares strings with runs of ASCII digits compared by their numeric value.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        match (a.chars().next(), b.chars().next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let (x_digits, x_rest) = split_digits(a);
+                let (y_digits, y_rest) = split_digits(b);
+                let (x_num, y_num) = (
+                    x_digits.trim_start_matches('0'),
+                    y_digits.trim_start_matches('0'),
+                );
+                let ord = x_num
+                    .len()
+                    .cmp(&y_num.len())
+                    .then_with(|| x_num.cmp(y_num))
+                    // Fewer leading zeros first, so that the order is total.
+                    .then_with(|| x_digits.len().cmp(&y_digits.len()));
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+                a = x_rest;
+                b = y_rest;
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(&y);
+                }
+                a = &a[x.len_utf8()..];
+                b = &b[y.len_utf8()..];
+            }
+        }
+    }
+}
+
+fn split_digits(s: &str) -> (&str, &str) {
+    let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    s.split_at(end)
+}
+
+/// The categories that get a bar of their own, and the total count of the rest.
+pub(crate) struct Ranked {
+    pub kept: Vec<(String, usize)>,
+    /// Sum of the counts of the categories left out, which are shown as [`OTHER_LABEL`].
+    pub other: Option<usize>,
+}
+
+/// Keeps the `top` most frequent categories, if given, and orders them.
+pub(crate) fn rank(
+    counts: HashMap<&str, usize>,
+    top: Option<usize>,
+    order: CategoryOrder,
+    descending: bool,
+) -> Ranked {
+    let mut kept = counts
+        .into_iter()
+        .map(|(label, count)| (label.to_owned(), count))
+        .collect::<Vec<_>>();
+    let mut other = None;
+    if let Some(top) = top.filter(|&top| top < kept.len()) {
+        kept.sort_by(|a, b| CategoryOrder::Count.compare(a, b, true));
+        other = Some(kept.drain(top..).map(|(_, count)| count).sum());
+    }
+    kept.sort_by(|a, b| order.compare(a, b, descending));
+    Ranked { kept, other }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn natural_order() {
+        let mut labels = vec![
+            "item10", "item2", "item02", "item1b", "item", "Item3", "é1", "10",
+        ];
+        labels.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(
+            labels,
+            ["10", "Item3", "item", "item1b", "item2", "item02", "item10", "é1"]
+        );
+        assert_eq!(natural_cmp("a007", "a007"), Ordering::Equal);
+        assert_eq!(
+            natural_cmp("v99999999999999999999", "v100000000000000000000"),
+            Ordering::Less
+        );
+    }
+
+    #[test]
+    fn top_categories_and_the_rest() {
+        let counts = HashMap::from([("b", 3), ("a", 3), ("c", 1), ("d", 2)]);
+        let ranked = rank(counts.clone(), Some(2), CategoryOrder::Label, true);
+        assert_eq!(ranked.kept, [("b".to_owned(), 3), ("a".to_owned(), 3)]);
+        assert_eq!(ranked.other, Some(3));
+        let ranked = rank(counts, Some(4), CategoryOrder::Count, false);
+        let labels = ranked.kept.iter().map(|(label, _)| label.as_str());
+        assert_eq!(labels.collect::<Vec<_>>(), ["c", "d", "a", "b"]);
+        assert_eq!(ranked.other, None);
+    }
+}
@@ -0,0 +1,114 @@
+//! Empirical cumulative distribution of the values behind a numeric histogram.
+
+use super::binning::sorted_quantile;
+
+/// The empirical cumulative distribution function of a sample, read from its sorted values.
+#[derive(Clone, Copy, Debug)]
+pub struct Ecdf<'a> {
+    sorted: &'a [f64],
+}
+
+impl<'a> Ecdf<'a> {
+    /// The distribution of values sorted in ascending order, or `None` if there are none.
+    pub fn new(sorted: &'a [f64]) -> Option<Self> {
+        (!sorted.is_empty()).then_some(Ecdf { sorted })
+    }
+
+    /// The fraction of the values that are at most `x`.
+    pub fn cdf(&self, x: f64) -> f64 {
+        self.sorted.partition_point(|&v| v <= x) as f64 / self.sorted.len() as f64
+    }
+
+    /// The fraction of the values that are greater than `x`.
+    pub fn survival(&self, x: f64) -> f64 {
+        1.0 - self.cdf(x)
+    }
+
+    /// The value at probability `p`, interpolated between the closest values as the quantiles of
+    /// a [`super::Histogram`] are.
+    pub fn quantile(&self, p: f64) -> f64 {
+        sorted_quantile(self.sorted, p)
+    }
+
+    /// The corners of the step function as `[x, cdf]` points, starting from zero below the
+    /// smallest value.
+    ///
+    /// If that takes more than `max_points` points, the function is drawn through the values at
+    /// about `max_points` ranks instead: half of them evenly spaced, and half spaced evenly in the
+    /// logarithm of the number of values above, so that the upper tail stays exact on a log
+    /// scale.
+    pub fn steps(&self, max_points: usize) -> Vec<[f64; 2]> {
+        let n = self.sorted.len();
+        let fraction = |count: usize| count as f64 / n as f64;
+        let mut points = vec![[self.sorted[0], 0.0]];
+        if 2 * n <= max_points {
+            for (i, &x) in self.sorted.iter().enumerate() {
+                // Equal values make a single step.
+                if self.sorted.get(i + 1) != Some(&x) {
+                    let below = points[points.len() - 1][1];
+                    points.push([x, below]);
+                    points.push([x, fraction(i + 1)]);
+                }
+            }
+            points.remove(1);
+            return points;
+        }
+        let half = (max_points / 2).max(2);
+        let even = (0..half).map(|j| j * (n - 1) / (half - 1));
+        let tail = (0..half).map(|j| {
+            let above = (n as f64).powf(j as f64 / (half - 1) as f64).round() as usize;
+            n - above.clamp(1, n)
+        });
+        let mut ranks = even.chain(tail).collect::<Vec<_>>();
+        ranks.sort_unstable();
+        ranks.dedup();
+        points.extend(ranks.into_iter().map(|i| [self.sorted[i], fraction(i + 1)]));
+        points
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fractions_at_and_between_values() {
+        assert!(Ecdf::new(&[]).is_none());
+        let ecdf = Ecdf::new(&[1.0, 2.0, 2.0, 4.0]).unwrap();
+        let cdf = [0.0, 1.0, 2.0, 3.0, 4.0, 5.0].map(|x| ecdf.cdf(x));
+        assert_eq!(cdf, [0.0, 0.25, 0.75, 0.75, 1.0, 1.0]);
+        assert_eq!(ecdf.survival(2.0), 0.25);
+        assert_eq!(ecdf.quantile(0.5), 2.0);
+        assert_eq!(ecdf.quantile(1.0), 4.0);
+    }
+
+    #[test]
+    fn steps_with_equal_values() {
+        let ecdf = Ecdf::new(&[1.0, 2.0, 2.0, 4.0]).unwrap();
+        assert_eq!(
+            ecdf.steps(100),
+            [
+                [1.0, 0.0],
+                [1.0, 0.25],
+                [2.0, 0.25],
+                [2.0, 0.75],
+                [4.0, 0.75],
+                [4.0, 1.0]
+            ]
+        );
+    }
+
+    #[test]
+    fn steps_of_many_values_keep_the_ends() {
+        let sorted = (0..1000).map(f64::from).collect::<Vec<_>>();
+        let steps = Ecdf::new(&sorted).unwrap().steps(20);
+        assert!(steps.len() <= 21);
+        assert_eq!(steps[0], [0.0, 0.0]);
+        assert_eq!(steps[steps.len() - 1], [999.0, 1.0]);
+        // The tail has a point for each of the last few values.
+        assert_eq!(steps[steps.len() - 2], [998.0, 0.999]);
+        assert!(steps
+            .windows(2)
+            .all(|w| w[0][0] <= w[1][0] && w[0][1] <= w[1][1]));
+    }
+}
@@ -0,0 +1,315 @@
+//! Picking fields out of delimited and `key=value` lines.
+
+use super::DataError;
+use std::str::FromStr;
+
+/// A column of delimited input, by position or by its name in a header row.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Column {
+    /// 1-based position, as with `cut -f`.
+    Index(usize),
+    /// Name of the column in the first line of the input.
+    Name(String),
+}
+
+impl FromStr for Column {
+    type Err = String;
+
+    /// Parses a positive number as a position and anything else as a name.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<usize>() {
+            Ok(0) => Err("columns are numbered from 1".to_owned()),
+            Ok(index) => Ok(Column::Index(index)),
+            Err(_) if s.is_empty() => Err("column name is empty".to_owned()),
+            Err(_) => Ok(Column::Name(s.to_owned())),
+        }
+    }
+}
+
+/// Parses a field delimiter given as a single character, or as `tab` or `\t`.
+pub fn parse_delimiter(s: &str) -> Result<char, String> {
+    match s {
+        "tab" | "\\t" => Ok('\t'),
+        _ => {
+            let mut chars = s.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if c != '"' => Ok(c),
+                _ => Err(format!(
+                    "expected a single delimiter character, got {:?}",
+                    s
+                )),
+            }
+        }
+    }
+}
+
+/// How to take the value out of each line of delimited input.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldSelector {
+    pub column: Column,
+    /// Field delimiter. If `None`, a tab is used if the first line contains one, otherwise a
+    /// comma.
+    pub delimiter: Option<char>,
+    /// Skip the first line. Always the case when a column is given by name.
+    pub header: bool,
+    /// Split the values into series by the key in this column.
+    pub group_by: Option<Column>,
+}
+
+/// Applies a [`FieldSelector`] line by line, resolving the delimiter and column names from the
+/// first line.
+pub(crate) struct FieldExtractor {
+    selector: FieldSelector,
+    delimiter: char,
+    index: usize,
+    group_index: Option<usize>,
+    started: bool,
+}
+
+impl FieldExtractor {
+    pub fn new(selector: &FieldSelector) -> Self {
+        FieldExtractor {
+            selector: selector.clone(),
+            delimiter: selector.delimiter.unwrap_or(','),
+            index: 0,
+            group_index: None,
+            started: false,
+        }
+    }
+
+    /// The selected field of `line` and its group key, if grouping.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DataError::UnknownColumn`] if a column is named and the header does not
+    /// contain it.
+    pub fn extract(&mut self, line: &str) -> Result<Extracted, DataError> {
+        if !self.started {
+            self.started = true;
+            if self.selector.delimiter.is_none() && line.contains('\t') {
+                self.delimiter = '\t';
+            }
+            let header = split_fields(line, self.delimiter);
+            self.index = resolve(&self.selector.column, &header)?;
+            self.group_index = match &self.selector.group_by {
+                Some(column) => Some(resolve(column, &header)?),
+                None => None,
+            };
+            let named = |column: &Column| matches!(column, Column::Name(_));
+            if self.selector.header
+                || named(&self.selector.column)
+                || self.selector.group_by.as_ref().is_some_and(named)
+            {
+                return Ok(Extracted::Header);
+            }
+        }
+        let mut fields = split_fields(line, self.delimiter);
+        let group = match self.group_index {
+            Some(index) if index < fields.len() => Some(fields[index].clone()),
+            Some(_) => return Ok(Extracted::Missing(None)),
+            None => None,
+        };
+        if self.index < fields.len() {
+            Ok(Extracted::Value(fields.swap_remove(self.index), group))
+        } else {
+            Ok(Extracted::Missing(group))
+        }
+    }
+}
+
+/// What [`FieldExtractor::extract`] found on a line.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Extracted {
+    /// The header row.
+    Header,
+    /// The selected field, and the group key if grouping.
+    Value(String, Option<String>),
+    /// The line is too short to have the selected field or the group key. Holds the group key
+    /// if the line does have that.
+    Missing(Option<String>),
+}
+
+/// The 0-based index of `column` in a line whose fields are `header`.
+fn resolve(column: &Column, header: &[String]) -> Result<usize, DataError> {
+    match column {
+        Column::Index(index) => Ok(index - 1),
+        Column::Name(name) => header
+            .iter()
+            .position(|field| field == name)
+            .ok_or_else(|| DataError::UnknownColumn(name.clone())),
+    }
+}
+
+/// Splits a line into fields, following CSV quoting: a field that starts with `"` runs to the
+/// next unpaired `"`, may contain the delimiter, and uses `""` for a literal quote. Unquoted
+/// fields are trimmed.
+pub fn split_fields(line: &str, delimiter: char) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            if c != '"' {
+                field.push(c);
+            } else if chars.peek() == Some(&'"') {
+                field.push('"');
+                chars.next();
+            } else {
+                in_quotes = false;
+            }
+        } else if c == delimiter {
+            fields.push(finish_field(&mut field, quoted));
+            quoted = false;
+        } else if c == '"' && !quoted && field.trim().is_empty() {
+            field.clear();
+            quoted = true;
+            in_quotes = true;
+        } else if !quoted || !c.is_whitespace() {
+            field.push(c);
+        }
+    }
+    fields.push(finish_field(&mut field, quoted));
+    fields
+}
+
+fn finish_field(field: &mut String, quoted: bool) -> String {
+    let value = if quoted {
+        field.clone()
+    } else {
+        field.trim().to_owned()
+    };
+    field.clear();
+    value
+}
+
+/// Splits a `logfmt` line, such as `level=info host=web-1 msg="took long" ms=12.5`, into its
+/// `key=value` pairs. A quoted value may contain spaces and `\"` for a literal quote, and a key
+/// without `=` has an empty value.
+pub fn split_logfmt(line: &str) -> Vec<(String, String)> {
+    let mut pairs = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            return pairs;
+        }
+        let mut key = String::new();
+        while let Some(c) = chars.next_if(|&c| c != '=' && !c.is_whitespace()) {
+            key.push(c);
+        }
+        let mut value = String::new();
+        if chars.next_if_eq(&'=').is_some() {
+            if chars.next_if_eq(&'"').is_some() {
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => value.extend(chars.next()),
+                        _ => value.push(c),
+                    }
+                }
+            } else {
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                    value.push(c);
+                }
+            }
+        }
+        pairs.push((key, value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoted_fields() {
+        assert_eq!(
+            split_fields(r#"a, "b,c" ,"say ""hi""",, d "#, ','),
+            ["a", "b,c", r#"say "hi""#, "", "d"]
+        );
+        assert_eq!(split_fields(r#"" padded ";x"#, ';'), [" padded ", "x"]);
+        assert_eq!(split_fields("1\t2 3", '\t'), ["1", "2 3"]);
+    }
+
+    #[test]
+    fn delimiters_and_columns() {
+        assert_eq!(parse_delimiter("tab"), Ok('\t'));
+        assert_eq!(parse_delimiter("\\t"), Ok('\t'));
+        assert_eq!(parse_delimiter(";"), Ok(';'));
+        for bad in ["", "ab", "\""] {
+            assert!(parse_delimiter(bad).is_err());
+        }
+        assert_eq!("3".parse(), Ok(Column::Index(3)));
+        assert_eq!("ms".parse(), Ok(Column::Name("ms".to_owned())));
+        assert!("0".parse::<Column>().is_err());
+        assert!("".parse::<Column>().is_err());
+    }
+
+    #[test]
+    fn fields_by_name_under_a_header() {
+        let mut extractor = FieldExtractor::new(&FieldSelector {
+            column: Column::Name("b".to_owned()),
+            delimiter: None,
+            header: false,
+            group_by: None,
+        });
+        let lines = ["a\tb", "1\t2", "3"].map(|line| extractor.extract(line).unwrap());
+        assert_eq!(
+            lines,
+            [
+                Extracted::Header,
+                Extracted::Value("2".to_owned(), None),
+                Extracted::Missing(None)
+            ]
+        );
+
+        let mut extractor = FieldExtractor::new(&FieldSelector {
+            column: Column::Name("z".to_owned()),
+            delimiter: None,
+            header: true,
+            group_by: None,
+        });
+        assert!(matches!(
+            extractor.extract("a,b"),
+            Err(DataError::UnknownColumn(name)) if name == "z"
+        ));
+    }
+
+    #[test]
+    fn fields_by_position_with_a_group() {
+        let mut extractor = FieldExtractor::new(&FieldSelector {
+            column: Column::Index(2),
+            delimiter: Some(';'),
+            header: false,
+            group_by: Some(Column::Index(1)),
+        });
+        let lines = ["x;1", "y", ""].map(|line| extractor.extract(line).unwrap());
+        assert_eq!(
+            lines,
+            [
+                Extracted::Value("1".to_owned(), Some("x".to_owned())),
+                Extracted::Missing(Some("y".to_owned())),
+                Extracted::Missing(Some(String::new()))
+            ]
+        );
+    }
+
+    #[test]
+    fn logfmt_pairs() {
+        let pairs = split_logfmt(r#" level=info msg="took \"long\"" ms=12.5  flag empty="#);
+        let expected = [
+            ("level", "info"),
+            ("msg", r#"took "long""#),
+            ("ms", "12.5"),
+            ("flag", ""),
+            ("empty", ""),
+        ];
+        assert_eq!(
+            pairs,
+            expected.map(|(key, value)| (key.to_owned(), value.to_owned()))
+        );
+        assert!(split_logfmt("   ").is_empty());
+    }
+}
@@ -0,0 +1,234 @@
+//! Kernel density estimates.
+
+use super::compare_f64;
+
+/// Number of cells a sample is binned into before its density is estimated.
+const KDE_CELLS: usize = 1024;
+
+/// The shape of the bump each value contributes to a density estimate.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Kernel {
+    #[default]
+    Gaussian,
+    /// `0.75 * (1 - u^2)` on `[-1, 1]`.
+    Epanechnikov,
+}
+
+impl Kernel {
+    fn weight(self, u: f64) -> f64 {
+        match self {
+            Kernel::Gaussian => (-0.5 * u * u).exp() / (2.0 * std::f64::consts::PI).sqrt(),
+            Kernel::Epanechnikov if u.abs() < 1.0 => 0.75 * (1.0 - u * u),
+            Kernel::Epanechnikov => 0.0,
+        }
+    }
+
+    /// Number of bandwidths beyond which the kernel is zero, or negligible.
+    fn support(self) -> f64 {
+        match self {
+            Kernel::Gaussian => 4.0,
+            Kernel::Epanechnikov => 1.0,
+        }
+    }
+}
+
+/// How a bandwidth is chosen from the spread of the sample.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BandwidthRule {
+    /// `0.9 * min(stddev, IQR / 1.34) * n^(-1/5)`, which copes with skewed samples.
+    #[default]
+    Silverman,
+    /// `1.06 * stddev * n^(-1/5)`, optimal for normal samples.
+    Scott,
+}
+
+/// A kernel density estimate of a sample.
+///
+/// The sample is summarised by weighted points on a fine grid, so the estimate can be evaluated
+/// quickly for any kernel and bandwidth however large the sample is.
+#[derive(Clone, Debug)]
+pub struct Kde {
+    /// `(x, weight)` in ascending order of `x`.
+    points: Vec<(f64, f64)>,
+    total: f64,
+    std_dev: f64,
+    iqr: f64,
+}
+
+impl Kde {
+    /// Summarises `values`, ignoring NaN and infinite ones, or returns `None` if none is left.
+    pub fn from_values(values: &[f64]) -> Option<Self> {
+        let finite = || values.iter().copied().filter(|x| x.is_finite());
+        let (min, max) = finite().fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), x| {
+            (lo.min(x), hi.max(x))
+        });
+        if min > max {
+            return None;
+        }
+        if max == min {
+            return Self::from_weighted(vec![(min, finite().count() as f64)]);
+        }
+        let width = (max - min) / KDE_CELLS as f64;
+        let mut weights = vec![0.0; KDE_CELLS];
+        for x in finite() {
+            weights[(((x - min) / width) as usize).min(KDE_CELLS - 1)] += 1.0;
+        }
+        Self::from_weighted(
+            weights
+                .into_iter()
+                .enumerate()
+                .filter(|&(_, w)| w > 0.0)
+                .map(|(i, w)| (min + (i as f64 + 0.5) * width, w))
+                .collect(),
+        )
+    }
+
+    /// Summarises values given as `(x, count)` pairs, such as the midpoints and counts of
+    /// histogram bins, or returns `None` if the counts add up to zero.
+    pub fn from_weighted(mut points: Vec<(f64, f64)>) -> Option<Self> {
+        points.retain(|&(x, w)| x.is_finite() && w > 0.0);
+        points.sort_by(|a, b| compare_f64(&a.0, &b.0));
+        let total = points.iter().map(|&(_, w)| w).sum::<f64>();
+        if total <= 0.0 {
+            return None;
+        }
+        let mean = points.iter().map(|&(x, w)| x * w).sum::<f64>() / total;
+        let variance = points
+            .iter()
+            .map(|&(x, w)| w * (x - mean).powi(2))
+            .sum::<f64>()
+            / (total - 1.0).max(1.0);
+        let quantile = |p: f64| {
+            let mut cumulative = 0.0;
+            for &(x, w) in &points {
+                cumulative += w;
+                if cumulative >= p * total {
+                    return x;
+                }
+            }
+            points[points.len() - 1].0
+        };
+        let iqr = quantile(0.75) - quantile(0.25);
+        Some(Kde {
+            total,
+            std_dev: variance.sqrt(),
+            iqr,
+            points,
+        })
+    }
+
+    /// Number of values in the sample.
+    pub fn total(&self) -> f64 {
+        self.total
+    }
+
+    /// The bandwidth given by `rule`. Samples without spread get a bandwidth of 1% of their
+    /// magnitude, or 1 around zero.
+    pub fn bandwidth(&self, rule: BandwidthRule) -> f64 {
+        let n = self.total.powf(-0.2);
+        let spread = match rule {
+            BandwidthRule::Silverman if self.iqr > 0.0 => 0.9 * self.std_dev.min(self.iqr / 1.34),
+            BandwidthRule::Silverman => 0.9 * self.std_dev,
+            BandwidthRule::Scott => 1.06 * self.std_dev,
+        };
+        if spread > 0.0 {
+            spread * n
+        } else {
+            let magnitude = self.points[0].0.abs();
+            if magnitude > 0.0 {
+                0.01 * magnitude
+            } else {
+                1.0
+            }
+        }
+    }
+
+    /// The estimated probability density at `x`.
+    pub fn density(&self, x: f64, kernel: Kernel, bandwidth: f64) -> f64 {
+        let reach = kernel.support() * bandwidth;
+        let start = self.points.partition_point(|&(p, _)| p < x - reach);
+        let sum = self.points[start..]
+            .iter()
+            .take_while(|&&(p, _)| p <= x + reach)
+            .map(|&(p, w)| w * kernel.weight((x - p) / bandwidth))
+            .sum::<f64>();
+        sum / (self.total * bandwidth)
+    }
+
+    /// The density at `resolution` evenly spaced points covering the sample and the tails of the
+    /// kernel, as `[x, density]` pairs.
+    pub fn curve(&self, kernel: Kernel, bandwidth: f64, resolution: usize) -> Vec<[f64; 2]> {
+        let reach = kernel.support() * bandwidth;
+        let lo = self.points[0].0 - reach;
+        let hi = self.points[self.points.len() - 1].0 + reach;
+        let step = (hi - lo) / (resolution.max(2) - 1) as f64;
+        (0..resolution.max(2))
+            .map(|i| {
+                let x = lo + i as f64 * step;
+                [x, self.density(x, kernel, bandwidth)]
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn only_finite_values_are_summarised() {
+        assert!(Kde::from_values(&[]).is_none());
+        assert!(Kde::from_values(&[f64::NAN, f64::INFINITY]).is_none());
+        assert!(Kde::from_weighted(vec![(1.0, 0.0)]).is_none());
+        let kde = Kde::from_values(&[1.0, f64::NAN, 2.0, 3.0]).unwrap();
+        assert_eq!(kde.total(), 3.0);
+    }
+
+    #[test]
+    fn bandwidth_rules() {
+        let kde = Kde::from_weighted(vec![(2.0, 1.0), (0.0, 1.0)]).unwrap();
+        let n = 2f64.powf(-0.2);
+        let std_dev = 2f64.sqrt();
+        assert!(close(
+            kde.bandwidth(BandwidthRule::Silverman),
+            0.9 * std_dev * n
+        ));
+        assert!(close(
+            kde.bandwidth(BandwidthRule::Scott),
+            1.06 * std_dev * n
+        ));
+        let constant = Kde::from_values(&[5.0, 5.0, 5.0]).unwrap();
+        assert!(close(constant.bandwidth(BandwidthRule::Silverman), 0.05));
+        let zero = Kde::from_values(&[0.0]).unwrap();
+        assert_eq!(zero.bandwidth(BandwidthRule::Scott), 1.0);
+    }
+
+    #[test]
+    fn kernels_around_a_single_value() {
+        let kde = Kde::from_weighted(vec![(0.0, 2.0)]).unwrap();
+        let peak = 1.0 / (2.0 * std::f64::consts::PI).sqrt();
+        assert!(close(kde.density(0.0, Kernel::Gaussian, 1.0), peak));
+        assert!(close(kde.density(0.0, Kernel::Epanechnikov, 2.0), 0.375));
+        assert_eq!(kde.density(1.5, Kernel::Epanechnikov, 1.0), 0.0);
+        assert_eq!(kde.density(5.0, Kernel::Gaussian, 1.0), 0.0);
+    }
+
+    #[test]
+    fn curves_integrate_to_one() {
+        let values = (0..100).map(|i| f64::from(i * i % 37)).collect::<Vec<_>>();
+        let kde = Kde::from_values(&values).unwrap();
+        for kernel in [Kernel::Gaussian, Kernel::Epanechnikov] {
+            let bandwidth = kde.bandwidth(BandwidthRule::Silverman);
+            let curve = kde.curve(kernel, bandwidth, 2000);
+            let area = curve
+                .windows(2)
+                .map(|w| (w[1][0] - w[0][0]) * (w[0][1] + w[1][1]) / 2.0)
+                .sum::<f64>();
+            assert!((area - 1.0).abs() < 1e-3, "{:?}: {}", kernel, area);
+        }
+    }
+}
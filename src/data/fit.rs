@@ -0,0 +1,531 @@
+//! Fitting parametric distributions to a sample by maximum likelihood, and how well they fit.
+
+use std::f64::consts::{PI, SQRT_2};
+
+/// Most Newton steps taken to solve for a shape parameter.
+const MAX_ITERATIONS: usize = 100;
+
+/// A family of distributions that can be fitted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Family {
+    Normal,
+    LogNormal,
+    Exponential,
+    Gamma,
+    Weibull,
+}
+
+impl Family {
+    pub const ALL: [Family; 5] = [
+        Family::Normal,
+        Family::LogNormal,
+        Family::Exponential,
+        Family::Gamma,
+        Family::Weibull,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Family::Normal => "Normal",
+            Family::LogNormal => "Log-normal",
+            Family::Exponential => "Exponential",
+            Family::Gamma => "Gamma",
+            Family::Weibull => "Weibull",
+        }
+    }
+
+    /// Fits the distribution of this family that makes the values in `sorted`, in ascending
+    /// order, most likely, and tests how well it fits them.
+    ///
+    /// Returns `None` if there are fewer than two distinct values, or if some are outside the
+    /// family's support: negative for the exponential, and zero or negative for the others
+    /// except the normal.
+    pub fn fit(self, sorted: &[f64]) -> Option<Fit> {
+        let n = sorted.len();
+        if n < 2 || sorted[0] == sorted[n - 1] || !sorted.iter().all(|x| x.is_finite()) {
+            return None;
+        }
+        let positive = sorted[0] > 0.0;
+        let distribution = match self {
+            Family::Normal => {
+                let (mean, std_dev) = mean_std_dev(sorted.iter().copied());
+                Distribution::Normal { mean, std_dev }
+            }
+            Family::LogNormal if positive => {
+                let (mu, sigma) = mean_std_dev(sorted.iter().map(|x| x.ln()));
+                Distribution::LogNormal { mu, sigma }
+            }
+            Family::Exponential if sorted[0] >= 0.0 => {
+                let mean = sorted.iter().sum::<f64>() / n as f64;
+                Distribution::Exponential { rate: 1.0 / mean }
+            }
+            Family::Gamma if positive => fit_gamma(sorted),
+            Family::Weibull if positive => fit_weibull(sorted),
+            _ => return None,
+        };
+        Some(Fit::new(distribution, sorted))
+    }
+}
+
+/// A distribution with all its parameters.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Distribution {
+    Normal {
+        mean: f64,
+        std_dev: f64,
+    },
+    /// The distribution of `exp(X)` where `X` is normal with mean `mu` and standard deviation
+    /// `sigma`.
+    LogNormal {
+        mu: f64,
+        sigma: f64,
+    },
+    Exponential {
+        rate: f64,
+    },
+    Gamma {
+        shape: f64,
+        scale: f64,
+    },
+    Weibull {
+        shape: f64,
+        scale: f64,
+    },
+}
+
+impl Distribution {
+    pub fn family(&self) -> Family {
+        match self {
+            Distribution::Normal { .. } => Family::Normal,
+            Distribution::LogNormal { .. } => Family::LogNormal,
+            Distribution::Exponential { .. } => Family::Exponential,
+            Distribution::Gamma { .. } => Family::Gamma,
+            Distribution::Weibull { .. } => Family::Weibull,
+        }
+    }
+
+    /// The names and values of the parameters.
+    pub fn params(&self) -> Vec<(&'static str, f64)> {
+        match *self {
+            Distribution::Normal { mean, std_dev } => vec![("μ", mean), ("σ", std_dev)],
+            Distribution::LogNormal { mu, sigma } => vec![("μ", mu), ("σ", sigma)],
+            Distribution::Exponential { rate } => vec![("λ", rate)],
+            Distribution::Gamma { shape, scale } => vec![("k", shape), ("θ", scale)],
+            Distribution::Weibull { shape, scale } => vec![("k", shape), ("λ", scale)],
+        }
+    }
+
+    /// The probability density at `x`.
+    pub fn pdf(&self, x: f64) -> f64 {
+        match *self {
+            Distribution::Normal { mean, std_dev } => {
+                let z = (x - mean) / std_dev;
+                (-0.5 * z * z).exp() / (std_dev * (2.0 * PI).sqrt())
+            }
+            Distribution::LogNormal { mu, sigma } if x > 0.0 => {
+                let z = (x.ln() - mu) / sigma;
+                (-0.5 * z * z).exp() / (x * sigma * (2.0 * PI).sqrt())
+            }
+            Distribution::Exponential { rate } if x >= 0.0 => rate * (-rate * x).exp(),
+            Distribution::Gamma { shape, scale } if x > 0.0 => {
+                ((shape - 1.0) * x.ln() - x / scale - ln_gamma(shape) - shape * scale.ln()).exp()
+            }
+            Distribution::Weibull { shape, scale } if x > 0.0 => {
+                let u = x / scale;
+                shape / scale * u.powf(shape - 1.0) * (-u.powf(shape)).exp()
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// The probability of a value at most `x`.
+    pub fn cdf(&self, x: f64) -> f64 {
+        match *self {
+            Distribution::Normal { mean, std_dev } => normal_cdf((x - mean) / std_dev),
+            Distribution::LogNormal { mu, sigma } if x > 0.0 => normal_cdf((x.ln() - mu) / sigma),
+            Distribution::Exponential { rate } if x > 0.0 => -(-rate * x).exp_m1(),
+            Distribution::Gamma { shape, scale } if x > 0.0 => gamma_p(shape, x / scale),
+            Distribution::Weibull { shape, scale } if x > 0.0 => {
+                -(-(x / scale).powf(shape)).exp_m1()
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+/// A fitted distribution and how well it fits the sample.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fit {
+    pub distribution: Distribution,
+    pub log_likelihood: f64,
+    /// The Kolmogorov–Smirnov statistic: the largest distance between the fitted and the
+    /// empirical distribution functions.
+    pub ks: f64,
+    /// The probability of a Kolmogorov–Smirnov statistic at least as large if the sample came
+    /// from the fitted distribution. It is too high, since the parameters were fitted to the
+    /// same sample, so small values are the telling ones.
+    pub ks_p_value: f64,
+    /// The Anderson–Darling statistic, which weighs the tails more than `ks`. Smaller is better.
+    pub anderson_darling: f64,
+}
+
+impl Fit {
+    fn new(distribution: Distribution, sorted: &[f64]) -> Self {
+        let n = sorted.len() as f64;
+        let log_likelihood = sorted.iter().map(|&x| distribution.pdf(x).ln()).sum();
+        // Clamped so that the logarithms below stay finite.
+        let cdf = sorted
+            .iter()
+            .map(|&x| distribution.cdf(x).clamp(1e-15, 1.0 - 1e-15))
+            .collect::<Vec<_>>();
+        let ks = cdf
+            .iter()
+            .enumerate()
+            .map(|(i, &f)| (f - i as f64 / n).max((i + 1) as f64 / n - f))
+            .fold(0.0, f64::max);
+        let sum = cdf
+            .iter()
+            .zip(cdf.iter().rev())
+            .enumerate()
+            .map(|(i, (&f, &f_mirror))| (2 * i + 1) as f64 * (f.ln() + (-f_mirror).ln_1p()))
+            .sum::<f64>();
+        Fit {
+            distribution,
+            log_likelihood,
+            ks,
+            ks_p_value: kolmogorov_q((n.sqrt() + 0.12 + 0.11 / n.sqrt()) * ks),
+            anderson_darling: -n - sum / n,
+        }
+    }
+}
+
+/// The mean and the maximum-likelihood (divided by `n`) standard deviation.
+fn mean_std_dev(values: impl Iterator<Item = f64> + Clone) -> (f64, f64) {
+    let n = values.clone().count() as f64;
+    let mean = values.clone().sum::<f64>() / n;
+    let variance = values.map(|x| (x - mean) * (x - mean)).sum::<f64>() / n;
+    (mean, variance.sqrt())
+}
+
+/// Solves `ln(k) - digamma(k) = ln(mean) - mean(ln x)` for the shape with Newton's method,
+/// starting from the approximation of Minka.
+fn fit_gamma(sorted: &[f64]) -> Distribution {
+    let n = sorted.len() as f64;
+    let mean = sorted.iter().sum::<f64>() / n;
+    let s = mean.ln() - sorted.iter().map(|x| x.ln()).sum::<f64>() / n;
+    let mut shape = (3.0 - s + ((s - 3.0).powi(2) + 24.0 * s).sqrt()) / (12.0 * s);
+    for _ in 0..MAX_ITERATIONS {
+        let step = (shape.ln() - digamma(shape) - s) / (1.0 / shape - trigamma(shape));
+        let next = if shape - step > 0.0 {
+            shape - step
+        } else {
+            shape / 2.0
+        };
+        let done = (next - shape).abs() < 1e-12 * shape;
+        shape = next;
+        if done {
+            break;
+        }
+    }
+    Distribution::Gamma {
+        shape,
+        scale: mean / shape,
+    }
+}
+
+/// Solves `sum(x^k ln x) / sum(x^k) - 1/k = mean(ln x)` for the shape with Newton's method.
+fn fit_weibull(sorted: &[f64]) -> Distribution {
+    let logs = sorted.iter().map(|x| x.ln()).collect::<Vec<_>>();
+    let n = logs.len() as f64;
+    let mean_log = logs.iter().sum::<f64>() / n;
+    // Powers are taken relative to the largest value so that they cannot overflow.
+    let max_log = logs[logs.len() - 1];
+    let sums = |k: f64| {
+        logs.iter().fold((0.0, 0.0, 0.0), |(b, a, c), &l| {
+            let w = (k * (l - max_log)).exp();
+            (b + w, a + w * l, c + w * l * l)
+        })
+    };
+    let (_, spread) = mean_std_dev(logs.iter().copied());
+    let mut shape = 1.2 / spread;
+    for _ in 0..MAX_ITERATIONS {
+        let (b, a, c) = sums(shape);
+        let f = a / b - 1.0 / shape - mean_log;
+        let slope = (c * b - a * a) / (b * b) + 1.0 / (shape * shape);
+        let next = if shape - f / slope > 0.0 {
+            shape - f / slope
+        } else {
+            shape / 2.0
+        };
+        let done = (next - shape).abs() < 1e-12 * shape;
+        shape = next;
+        if done {
+            break;
+        }
+    }
+    let (b, _, _) = sums(shape);
+    Distribution::Weibull {
+        shape,
+        scale: (max_log + (b / n).ln() / shape).exp(),
+    }
+}
+
+fn normal_cdf(z: f64) -> f64 {
+    0.5 * erfc(-z / SQRT_2)
+}
+
+/// The complementary error function, with a relative error below 1.2e-7 (Numerical Recipes'
+/// `erfcc`).
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let poly = -z * z - 1.265_512_23
+        + t * (1.000_023_68
+            + t * (0.374_091_96
+                + t * (0.096_784_18
+                    + t * (-0.186_288_06
+                        + t * (0.278_868_07
+                            + t * (-1.135_203_98
+                                + t * (1.488_515_87 + t * (-0.822_152_23 + t * 0.170_872_77))))))));
+    let r = t * poly.exp();
+    if x >= 0.0 {
+        r
+    } else {
+        2.0 - r
+    }
+}
+
+/// The logarithm of the gamma function for positive `x`, by the Lanczos approximation.
+fn ln_gamma(x: f64) -> f64 {
+    const G: f64 = 7.0;
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    if x < 0.5 {
+        // Reflection, since the series is accurate for x >= 0.5.
+        return (PI / (PI * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+    let x = x - 1.0;
+    let sum = COEFFICIENTS[1..]
+        .iter()
+        .enumerate()
+        .fold(COEFFICIENTS[0], |sum, (i, c)| {
+            sum + c / (x + i as f64 + 1.0)
+        });
+    let t = x + G + 0.5;
+    0.5 * (2.0 * PI).ln() + (x + 0.5) * t.ln() - t + sum.ln()
+}
+
+/// The derivative of [`ln_gamma`], for positive `x`.
+fn digamma(mut x: f64) -> f64 {
+    let mut result = 0.0;
+    while x < 6.0 {
+        result -= 1.0 / x;
+        x += 1.0;
+    }
+    let inv2 = 1.0 / (x * x);
+    result + x.ln() - 0.5 / x - inv2 * (1.0 / 12.0 - inv2 * (1.0 / 120.0 - inv2 / 252.0))
+}
+
+/// The derivative of [`digamma`], for positive `x`.
+fn trigamma(mut x: f64) -> f64 {
+    let mut result = 0.0;
+    while x < 6.0 {
+        result += 1.0 / (x * x);
+        x += 1.0;
+    }
+    let inv2 = 1.0 / (x * x);
+    result + 1.0 / x + inv2 / 2.0 + inv2 / x * (1.0 / 6.0 - inv2 * (1.0 / 30.0 - inv2 / 42.0))
+}
+
+/// The regularized lower incomplete gamma function `P(a, x)`, by its series below `a + 1` and
+/// its continued fraction above.
+fn gamma_p(a: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    let log_prefactor = a * x.ln() - x - ln_gamma(a);
+    if x < a + 1.0 {
+        let (mut term, mut sum, mut ap) = (1.0 / a, 1.0 / a, a);
+        for _ in 0..1000 {
+            ap += 1.0;
+            term *= x / ap;
+            sum += term;
+            if term.abs() < sum.abs() * 1e-15 {
+                break;
+            }
+        }
+        (sum * log_prefactor.exp()).min(1.0)
+    } else {
+        // Modified Lentz's method.
+        const TINY: f64 = 1e-300;
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / TINY;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for i in 1..1000 {
+            let an = -(i as f64) * (i as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < TINY {
+                d = TINY;
+            }
+            c = b + an / c;
+            if c.abs() < TINY {
+                c = TINY;
+            }
+            d = 1.0 / d;
+            let delta = d * c;
+            h *= delta;
+            if (delta - 1.0).abs() < 1e-15 {
+                break;
+            }
+        }
+        (1.0 - log_prefactor.exp() * h).max(0.0)
+    }
+}
+
+/// The probability that the Kolmogorov distribution exceeds `lambda`.
+fn kolmogorov_q(lambda: f64) -> f64 {
+    if lambda < 0.2 {
+        return 1.0;
+    }
+    let mut sum = 0.0;
+    for j in 1..=100 {
+        let term = (-2.0 * (j * j) as f64 * lambda * lambda).exp();
+        sum += if j % 2 == 1 { term } else { -term };
+        if term < 1e-12 {
+            break;
+        }
+    }
+    (2.0 * sum).clamp(0.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A sample of `n` values that follows `distribution` as closely as possible: its quantiles
+    /// at `(i + 0.5) / n`, found by bisection of the distribution function.
+    fn quantile_sample(distribution: Distribution, n: usize) -> Vec<f64> {
+        (0..n)
+            .map(|i| {
+                let p = (i as f64 + 0.5) / n as f64;
+                let (mut low, mut high) = (0.0, 1.0);
+                while distribution.cdf(high) < p {
+                    high *= 2.0;
+                }
+                for _ in 0..100 {
+                    let mid = (low + high) / 2.0;
+                    if distribution.cdf(mid) < p {
+                        low = mid;
+                    } else {
+                        high = mid;
+                    }
+                }
+                (low + high) / 2.0
+            })
+            .collect()
+    }
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= tolerance * expected.abs().max(1.0),
+            "{} is not within {} of {}",
+            actual,
+            tolerance,
+            expected
+        );
+    }
+
+    #[test]
+    fn special_functions_match_known_values() {
+        assert_close(ln_gamma(5.0), 24f64.ln(), 1e-12);
+        assert_close(ln_gamma(0.5), PI.sqrt().ln(), 1e-12);
+        assert_close(digamma(1.0), -0.577_215_664_901_532_9, 1e-8);
+        assert_close(trigamma(1.0), PI * PI / 6.0, 1e-8);
+        assert_close(gamma_p(1.0, 2.0), 1.0 - (-2f64).exp(), 1e-12);
+        assert_close(
+            gamma_p(3.0, 5.0),
+            1.0 - (-5f64).exp() * (1.0 + 5.0 + 12.5),
+            1e-12,
+        );
+        assert_close(normal_cdf(1.959_963_984_540_054), 0.975, 1e-7);
+        assert_close(kolmogorov_q(1.0), 0.269_999_671_677_354_6, 1e-9);
+    }
+
+    #[test]
+    fn gamma_fit_recovers_its_parameters() {
+        let sample = quantile_sample(
+            Distribution::Gamma {
+                shape: 2.5,
+                scale: 3.0,
+            },
+            2000,
+        );
+        let fit = Family::Gamma.fit(&sample).unwrap();
+        let Distribution::Gamma { shape, scale } = fit.distribution else {
+            panic!("fitted {:?}", fit.distribution);
+        };
+        assert_close(shape, 2.5, 0.02);
+        assert_close(scale, 3.0, 0.02);
+    }
+
+    #[test]
+    fn weibull_fit_recovers_its_parameters() {
+        for (true_shape, true_scale) in [(0.7, 2.0), (1.5, 10.0), (4.0, 0.5)] {
+            let sample = quantile_sample(
+                Distribution::Weibull {
+                    shape: true_shape,
+                    scale: true_scale,
+                },
+                2000,
+            );
+            let fit = Family::Weibull.fit(&sample).unwrap();
+            let Distribution::Weibull { shape, scale } = fit.distribution else {
+                panic!("fitted {:?}", fit.distribution);
+            };
+            assert_close(shape, true_shape, 0.02);
+            assert_close(scale, true_scale, 0.02);
+        }
+    }
+
+    #[test]
+    fn goodness_of_fit_of_two_values() {
+        // The quartiles of the standard normal, whose distribution function is 1/4 and 3/4
+        // there.
+        let fit = Fit::new(
+            Distribution::Normal {
+                mean: 0.0,
+                std_dev: 1.0,
+            },
+            &[-0.674_489_750_196_081_7, 0.674_489_750_196_081_7],
+        );
+        assert_close(fit.ks, 0.25, 1e-6);
+        // -2 - (ln(1/4) + ln(1/4) + 3 (ln(3/4) + ln(3/4))) / 2
+        assert_close(
+            fit.anderson_darling,
+            -2.0 - (0.25f64.ln() + 3.0 * 0.75f64.ln()),
+            1e-6,
+        );
+    }
+
+    #[test]
+    fn goodness_of_fit_tells_good_fits_from_bad_ones() {
+        let sample = quantile_sample(Distribution::Exponential { rate: 0.5 }, 1000);
+        let good = Family::Exponential.fit(&sample).unwrap();
+        let bad = Family::Normal.fit(&sample).unwrap();
+        assert!(good.ks < 0.01 && good.ks_p_value > 0.99, "{:?}", good);
+        assert!(bad.ks > 0.05 && bad.ks_p_value < 0.01, "{:?}", bad);
+        assert!(good.anderson_darling < bad.anderson_darling);
+        assert!(good.log_likelihood > bad.log_likelihood);
+    }
+}
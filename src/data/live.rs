@@ -0,0 +1,431 @@
+//! Keeping the latest values of an input that is still being written, to re-bin as they arrive.
+
+use super::{
+    for_each_value, histograms_from_groups, DataError, HistogramOptions, InputLine, InputSource,
+    Series, StreamingCategories, StreamingHistogram,
+};
+use std::collections::VecDeque;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How many of the latest values of a live input are binned.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Window {
+    /// The last this many values.
+    Count(usize),
+    /// The values that arrived within this long.
+    Duration(Duration),
+}
+
+impl FromStr for Window {
+    type Err = String;
+
+    /// Parses a number of values, e.g. `10000`, or a duration with a unit of `ms`, `s`, `m` or
+    /// `h`, e.g. `60s`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let split = s
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(s.len());
+        let (number, unit) = s.split_at(split);
+        if unit.is_empty() {
+            return match number.parse::<usize>() {
+                Ok(0) => Err("the window must hold at least one value".to_owned()),
+                Ok(count) => Ok(Window::Count(count)),
+                Err(e) => Err(e.to_string()),
+            };
+        }
+        let seconds = match unit {
+            "ms" => 0.001,
+            "s" => 1.0,
+            "m" | "min" => 60.0,
+            "h" => 3600.0,
+            _ => return Err(format!("unknown unit {:?}, expected ms, s, m or h", unit)),
+        };
+        match number.parse::<f64>() {
+            Ok(n) if n > 0.0 => Duration::try_from_secs_f64(n * seconds)
+                .map(Window::Duration)
+                .map_err(|e| e.to_string()),
+            Ok(_) => Err("the window must not be empty".to_owned()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+/// Every value of a live input without a window, counted as it arrives.
+#[derive(Clone, Debug)]
+enum Counts {
+    Numbers(Box<StreamingHistogram>),
+    Categories(Box<StreamingCategories>),
+}
+
+/// The values read so far from a live input, or the latest of them if there is a [`Window`].
+///
+/// Without a window, the values are counted into a [`StreamingHistogram`] or
+/// [`StreamingCategories`] as they arrive, so that memory does not grow with the input, and only
+/// their lines are kept, if the options ask for them.
+#[derive(Debug, Default)]
+pub struct LiveValues {
+    window: Option<Window>,
+    /// The values in the window.
+    values: VecDeque<(Instant, InputLine)>,
+    /// Without a window, every value read so far.
+    counts: Option<Counts>,
+    /// Number of lines read without a window.
+    count: usize,
+    /// Without a window, the lines read since the last [`LiveValues::snapshot`].
+    new_lines: Vec<InputLine>,
+    /// With a window, the number of lines read so far without the selected field.
+    missing: usize,
+    keep_lines: bool,
+    /// Incremented whenever the values change.
+    version: u64,
+    /// Whether the whole input has been read.
+    done: bool,
+    /// Why reading the input stopped early.
+    error: Option<DataError>,
+}
+
+impl LiveValues {
+    /// Creates an empty set of values to be binned with `options`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`StreamingHistogram::new`] if there is no window.
+    pub fn new(window: Option<Window>, options: &HistogramOptions) -> Result<Self, DataError> {
+        let counts = match window {
+            Some(_) => None,
+            None if options.categorical => Some(Counts::Categories(Box::new(
+                StreamingCategories::new(options),
+            ))),
+            None => Some(Counts::Numbers(Box::new(StreamingHistogram::new(options)?))),
+        };
+        Ok(LiveValues {
+            window,
+            counts,
+            keep_lines: options.keep_lines,
+            ..LiveValues::default()
+        })
+    }
+
+    pub fn push(&mut self, line: InputLine, now: Instant) {
+        self.version += 1;
+        match &mut self.counts {
+            Some(counts) => {
+                match counts {
+                    Counts::Numbers(histogram) => histogram.push_value(&line.value),
+                    Counts::Categories(categories) => categories.push(&line.value),
+                }
+                self.count += 1;
+                if self.keep_lines {
+                    self.new_lines.push(line);
+                }
+            }
+            None => {
+                self.values.push_back((now, line));
+                self.expire(now);
+            }
+        }
+    }
+
+    /// Counts a line without the selected field. With a window, such lines are counted from the
+    /// start of the input rather than only within the window.
+    pub fn push_missing(&mut self) {
+        self.version += 1;
+        match &mut self.counts {
+            Some(counts) => {
+                match counts {
+                    Counts::Numbers(histogram) => histogram.push_missing(),
+                    Counts::Categories(categories) => categories.push_missing(),
+                }
+                self.count += 1;
+            }
+            None => self.missing += 1,
+        }
+    }
+
+    /// Drops the values that have fallen out of the window by `now`.
+    pub fn expire(&mut self, now: Instant) {
+        let before = self.values.len();
+        match self.window {
+            Some(Window::Count(count)) => {
+                let excess = self.values.len().saturating_sub(count);
+                self.values.drain(..excess);
+            }
+            Some(Window::Duration(duration)) => {
+                while self
+                    .values
+                    .front()
+                    .is_some_and(|(time, _)| now.duration_since(*time) > duration)
+                {
+                    self.values.pop_front();
+                }
+            }
+            None => {}
+        }
+        if self.values.len() != before {
+            self.version += 1;
+        }
+    }
+
+    /// A number that changes whenever the values do.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Number of values in the window, or of lines read so far if there is none.
+    pub fn len(&self) -> usize {
+        match self.counts {
+            Some(_) => self.count,
+            None => self.values.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn window(&self) -> Option<Window> {
+        self.window
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    pub fn error(&self) -> Option<&DataError> {
+        self.error.as_ref()
+    }
+
+    /// Copies out what is needed to bin the current values, so that they can be binned without
+    /// holding up the thread that reads them. Without a window, this takes the lines read since
+    /// the last snapshot.
+    pub fn snapshot(&mut self) -> Snapshot {
+        match &self.counts {
+            Some(counts) => Snapshot(Taken::Counts {
+                counts: counts.clone(),
+                count: self.count,
+                new_lines: std::mem::take(&mut self.new_lines),
+            }),
+            None => Snapshot(Taken::Window {
+                lines: self.values.iter().map(|(_, line)| line.clone()).collect(),
+                missing: self.missing,
+            }),
+        }
+    }
+}
+
+/// The values of a live input at one time, taken by [`LiveValues::snapshot`].
+#[derive(Debug)]
+pub struct Snapshot(Taken);
+
+#[derive(Debug)]
+enum Taken {
+    /// The values in the window, and the number of lines without the selected field.
+    Window {
+        lines: Vec<InputLine>,
+        missing: usize,
+    },
+    /// Every value read so far, counted, and the lines read since the last snapshot.
+    Counts {
+        counts: Counts,
+        count: usize,
+        new_lines: Vec<InputLine>,
+    },
+}
+
+impl Snapshot {
+    /// Bins the values as a single series named `name`.
+    ///
+    /// If `options.keep_lines` is set, `lines` are then the lines of the values: those of the
+    /// window, or without one, the lines of the earlier snapshots followed by those read since.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`histograms_from_groups`], or of finishing the counts.
+    pub fn series(
+        self,
+        name: &str,
+        options: &HistogramOptions,
+        lines: &mut Vec<InputLine>,
+    ) -> Result<Series, DataError> {
+        match self.0 {
+            Taken::Window {
+                lines: window,
+                missing,
+            } => {
+                let values = window.iter().map(|line| line.value.clone()).collect();
+                if options.keep_lines {
+                    *lines = window;
+                }
+                let mut series =
+                    histograms_from_groups(&[(name.to_owned(), values)], options)?.remove(0);
+                series.histogram.rejected.missing = missing;
+                Ok(series)
+            }
+            Taken::Counts {
+                counts,
+                count,
+                new_lines,
+            } => {
+                lines.extend(new_lines);
+                let histogram = match counts {
+                    Counts::Numbers(histogram) => histogram.finish_lines(count)?,
+                    Counts::Categories(categories) => categories.finish()?,
+                };
+                Ok(Series {
+                    name: name.to_owned(),
+                    histogram,
+                    values: Vec::new(),
+                    lines: Vec::new(),
+                })
+            }
+        }
+    }
+}
+
+/// Reads `input` to the end, adding each value to `live` as soon as it is read, and records
+/// in `live` when the input ends or fails.
+pub fn read_live(input: &InputSource, options: &HistogramOptions, live: &Mutex<LiveValues>) {
+    let result = for_each_value(input, options.field.as_ref(), |number, line, value, _| {
+        let Some(value) = value else {
+            options.check_field(number)?;
+            live.lock().unwrap().push_missing();
+            return Ok(());
+        };
+        options.check_value(number, value)?;
+        let line = InputLine {
+            text: if options.keep_lines {
+                line.to_owned()
+            } else {
+                String::new()
+            },
+            value: value.to_owned(),
+        };
+        live.lock().unwrap().push(line, Instant::now());
+        Ok(())
+    });
+    let mut live = live.lock().unwrap();
+    live.done = true;
+    live.error = result.err();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(value: &str) -> InputLine {
+        InputLine {
+            text: format!("v={}", value),
+            value: value.to_owned(),
+        }
+    }
+
+    #[test]
+    fn windows_from_text() {
+        assert_eq!("10000".parse(), Ok(Window::Count(10000)));
+        let duration = |s: &str| s.parse::<Window>();
+        assert_eq!(
+            duration("60s"),
+            Ok(Window::Duration(Duration::from_secs(60)))
+        );
+        assert_eq!(
+            duration("1.5m"),
+            Ok(Window::Duration(Duration::from_secs(90)))
+        );
+        assert_eq!(
+            duration("250ms"),
+            Ok(Window::Duration(Duration::from_millis(250)))
+        );
+        for bad in ["0", "0s", "2x", "s", "99999999999999999999999h"] {
+            assert!(duration(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn values_fall_out_of_the_window() {
+        let options = HistogramOptions::default();
+        let start = Instant::now();
+        let mut live = LiveValues::new(Some(Window::Count(3)), &options).unwrap();
+        for value in ["1", "2", "3", "4", "5"] {
+            live.push(line(value), start);
+        }
+        assert_eq!(live.len(), 3);
+
+        let second = Duration::from_secs(1);
+        let mut live = LiveValues::new(Some(Window::Duration(second)), &options).unwrap();
+        live.push(line("1"), start);
+        live.push(line("2"), start + second / 2);
+        live.push(line("3"), start + second * 3 / 2);
+        assert_eq!(live.len(), 2);
+        let version = live.version();
+        live.expire(start + second * 3);
+        assert!(live.is_empty());
+        assert!(live.version() > version);
+    }
+
+    #[test]
+    fn window_snapshots_bin_the_window() {
+        let options = HistogramOptions {
+            keep_lines: true,
+            ..HistogramOptions::default()
+        };
+        let mut live = LiveValues::new(Some(Window::Count(2)), &options).unwrap();
+        for value in ["1", "2", "3"] {
+            live.push(line(value), Instant::now());
+        }
+        live.push_missing();
+        let mut lines = Vec::new();
+        let series = live.snapshot().series("s", &options, &mut lines).unwrap();
+        assert_eq!(series.histogram.total, 2);
+        assert_eq!(series.histogram.rejected.missing, 1);
+        assert_eq!(lines, [line("2"), line("3")]);
+    }
+
+    #[test]
+    fn without_a_window_every_value_is_counted() {
+        let options = HistogramOptions {
+            keep_lines: true,
+            ..HistogramOptions::default()
+        };
+        let mut live = LiveValues::new(None, &options).unwrap();
+        let mut lines = Vec::new();
+        for value in ["1", "2"] {
+            live.push(line(value), Instant::now());
+        }
+        live.push_missing();
+        assert_eq!(live.len(), 3);
+        live.snapshot().series("s", &options, &mut lines).unwrap();
+        live.push(line("x"), Instant::now());
+        live.push(line("3"), Instant::now());
+        let series = live.snapshot().series("s", &options, &mut lines).unwrap();
+        let histogram = series.histogram;
+        assert_eq!(histogram.total, 3);
+        assert_eq!(
+            (histogram.rejected.missing, histogram.rejected.unparsed),
+            (1, 1)
+        );
+        assert_eq!(lines, [line("1"), line("2"), line("x"), line("3")]);
+    }
+
+    #[test]
+    fn read_live_reads_to_the_end() {
+        let dir = std::env::temp_dir().join(format!("hist3-live-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("input");
+        std::fs::write(&path, "1\n2\n3\n").unwrap();
+        let input = InputSource::FileName(path.to_str().unwrap().to_owned());
+        let options = HistogramOptions::default();
+        let live = Mutex::new(LiveValues::new(None, &options).unwrap());
+        read_live(&input, &options, &live);
+        let live = live.into_inner().unwrap();
+        assert!(live.is_done() && live.error().is_none());
+        assert_eq!(live.len(), 3);
+
+        let missing = InputSource::FileName(dir.join("missing").to_str().unwrap().to_owned());
+        let live = Mutex::new(LiveValues::new(None, &options).unwrap());
+        read_live(&missing, &options, &live);
+        let live = live.into_inner().unwrap();
+        assert!(live.is_done() && live.error().is_some());
+    }
+}
@@ -0,0 +1,364 @@
+//! Fixed-size summaries of unbounded streams.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeSet, HashMap};
+use std::hash::{Hash, Hasher};
+
+/// Streaming estimate of a single quantile using the P² algorithm (Jain & Chlamtac, 1985).
+///
+/// Keeps five markers whose heights are adjusted with piecewise-parabolic interpolation as
+/// values arrive, so memory use is constant regardless of how many values are seen.
+#[derive(Clone, Debug)]
+pub struct P2Quantile {
+    p: f64,
+    count: usize,
+    heights: [f64; 5],
+    positions: [f64; 5],
+    desired: [f64; 5],
+    increments: [f64; 5],
+}
+
+impl P2Quantile {
+    pub fn new(p: f64) -> Self {
+        P2Quantile {
+            p,
+            count: 0,
+            heights: [0.0; 5],
+            positions: [0.0, 1.0, 2.0, 3.0, 4.0],
+            desired: [0.0, 2.0 * p, 4.0 * p, 2.0 + 2.0 * p, 4.0],
+            increments: [0.0, p / 2.0, p, (1.0 + p) / 2.0, 1.0],
+        }
+    }
+
+    pub fn p(&self) -> f64 {
+        self.p
+    }
+
+    pub fn push(&mut self, x: f64) {
+        if self.count < 5 {
+            self.heights[self.count] = x;
+            self.count += 1;
+            if self.count == 5 {
+                self.heights
+                    .sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+            }
+            return;
+        }
+        self.count += 1;
+
+        let k = if x < self.heights[0] {
+            self.heights[0] = x;
+            0
+        } else if x >= self.heights[4] {
+            self.heights[4] = x;
+            3
+        } else {
+            (0..4).find(|&i| x < self.heights[i + 1]).unwrap_or(3)
+        };
+        for position in self.positions.iter_mut().skip(k + 1) {
+            *position += 1.0;
+        }
+        for (desired, increment) in self.desired.iter_mut().zip(self.increments.iter()) {
+            *desired += increment;
+        }
+
+        for i in 1..4 {
+            let d = self.desired[i] - self.positions[i];
+            if (d >= 1.0 && self.positions[i + 1] - self.positions[i] > 1.0)
+                || (d <= -1.0 && self.positions[i - 1] - self.positions[i] < -1.0)
+            {
+                let d = d.signum();
+                let candidate = self.parabolic(i, d);
+                self.heights[i] =
+                    if self.heights[i - 1] < candidate && candidate < self.heights[i + 1] {
+                        candidate
+                    } else {
+                        self.linear(i, d)
+                    };
+                self.positions[i] += d;
+            }
+        }
+    }
+
+    /// The current estimate, or `None` if no values have been pushed.
+    pub fn estimate(&self) -> Option<f64> {
+        match self.count {
+            0 => None,
+            n if n < 5 => {
+                let mut seen = self.heights[..n].to_vec();
+                seen.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+                Some(seen[(((n as f64) * self.p) as usize).min(n - 1)])
+            }
+            _ => Some(self.heights[2]),
+        }
+    }
+
+    fn parabolic(&self, i: usize, d: f64) -> f64 {
+        let (q, n) = (&self.heights, &self.positions);
+        q[i] + d / (n[i + 1] - n[i - 1])
+            * ((n[i] - n[i - 1] + d) * (q[i + 1] - q[i]) / (n[i + 1] - n[i])
+                + (n[i + 1] - n[i] - d) * (q[i] - q[i - 1]) / (n[i] - n[i - 1]))
+    }
+
+    fn linear(&self, i: usize, d: f64) -> f64 {
+        let (q, n) = (&self.heights, &self.positions);
+        let j = if d > 0.0 { i + 1 } else { i - 1 };
+        q[i] + d * (q[j] - q[i]) / (n[j] - n[i])
+    }
+}
+
+/// A key monitored by a [`SpaceSaving`] summary.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Counter {
+    pub key: String,
+    /// Upper bound on the number of times the key was seen.
+    pub count: usize,
+    /// How much `count` may overstate the true count, which is at least `count - error`.
+    pub error: usize,
+}
+
+/// The most frequent keys of a stream, found with the Space-Saving algorithm (Metwally, Agrawal
+/// & El Abbadi, 2005).
+///
+/// At most `capacity` keys are monitored. A key that is not monitored when the summary is full
+/// takes over the counter with the smallest count, inheriting that count as its error, so the
+/// error of any counter is at most `n / capacity` after `n` keys. Until a counter is taken over
+/// the counts are exact.
+#[derive(Clone, Debug)]
+pub struct SpaceSaving {
+    capacity: usize,
+    counters: Vec<Counter>,
+    /// The counter of each monitored key.
+    index: HashMap<String, usize>,
+    /// `(count, counter)` pairs, to find the smallest counter.
+    by_count: BTreeSet<(usize, usize)>,
+    evictions: usize,
+}
+
+impl SpaceSaving {
+    pub fn new(capacity: usize) -> Self {
+        SpaceSaving {
+            capacity: capacity.max(1),
+            counters: Vec::new(),
+            index: HashMap::new(),
+            by_count: BTreeSet::new(),
+            evictions: 0,
+        }
+    }
+
+    pub fn push(&mut self, key: &str) {
+        let slot = match self.index.get(key) {
+            Some(&slot) => {
+                let counter = &mut self.counters[slot];
+                self.by_count.remove(&(counter.count, slot));
+                counter.count += 1;
+                slot
+            }
+            None if self.counters.len() < self.capacity => {
+                self.counters.push(Counter {
+                    key: key.to_owned(),
+                    count: 1,
+                    error: 0,
+                });
+                let slot = self.counters.len() - 1;
+                self.index.insert(key.to_owned(), slot);
+                slot
+            }
+            None => {
+                let (min, slot) = self.by_count.pop_first().expect("the summary is full");
+                let counter = &mut self.counters[slot];
+                self.index.remove(&counter.key);
+                counter.key = key.to_owned();
+                counter.count = min + 1;
+                counter.error = min;
+                self.index.insert(key.to_owned(), slot);
+                self.evictions += 1;
+                slot
+            }
+        };
+        self.by_count.insert((self.counters[slot].count, slot));
+    }
+
+    /// The monitored keys, in no particular order. Their counts add up to the number of keys
+    /// pushed.
+    pub fn counters(&self) -> &[Counter] {
+        &self.counters
+    }
+
+    /// Whether every key seen is monitored, so that the counts are exact.
+    pub fn is_exact(&self) -> bool {
+        self.evictions == 0
+    }
+}
+
+/// Number of bits of the hash that pick a [`HyperLogLog`] register.
+const HLL_PRECISION: u32 = 14;
+
+/// Estimate of the number of distinct keys in a stream with HyperLogLog (Flajolet et al., 2007),
+/// in a fixed 16 KiB of registers.
+#[derive(Clone, Debug)]
+pub struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl Default for HyperLogLog {
+    fn default() -> Self {
+        HyperLogLog {
+            registers: vec![0; 1 << HLL_PRECISION],
+        }
+    }
+}
+
+impl HyperLogLog {
+    pub fn push(&mut self, key: &str) {
+        // The default hasher is keyed the same way every time, so estimates are reproducible.
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let hash = hasher.finish();
+        let register = (hash >> (64 - HLL_PRECISION)) as usize;
+        // The marker bit bounds the rank when the remaining bits are all zero.
+        let rest = (hash << HLL_PRECISION) | (1 << (HLL_PRECISION - 1));
+        let rank = rest.leading_zeros() as u8 + 1;
+        self.registers[register] = self.registers[register].max(rank);
+    }
+
+    /// The estimated number of distinct keys pushed.
+    pub fn estimate(&self) -> f64 {
+        let m = self.registers.len() as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum = self
+            .registers
+            .iter()
+            .map(|&r| 2f64.powi(-(r as i32)))
+            .sum::<f64>();
+        let raw = alpha * m * m / sum;
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        if raw <= 2.5 * m && zeros > 0 {
+            // Linear counting is more accurate while many registers are still empty.
+            m * (m / zeros as f64).ln()
+        } else {
+            raw
+        }
+    }
+
+    /// The relative standard error of [`HyperLogLog::estimate`].
+    pub fn relative_error(&self) -> f64 {
+        1.04 / (self.registers.len() as f64).sqrt()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Uniform values in `[0, 1)` from a fixed xorshift sequence.
+    fn uniform(n: usize) -> Vec<f64> {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        (0..n)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                (state >> 11) as f64 / (1u64 << 53) as f64
+            })
+            .collect()
+    }
+
+    #[test]
+    fn p2_of_few_values_is_exact() {
+        let mut max = P2Quantile::new(1.0);
+        let mut min = P2Quantile::new(0.0);
+        assert_eq!(max.estimate(), None);
+        for x in [3.0, 1.0, 2.0] {
+            max.push(x);
+            min.push(x);
+        }
+        assert_eq!(max.estimate(), Some(3.0));
+        assert_eq!(min.estimate(), Some(1.0));
+    }
+
+    #[test]
+    fn p2_is_close_to_the_exact_quantile() {
+        let values = uniform(100_000);
+        let mut sorted = values.clone();
+        sorted.sort_by(f64::total_cmp);
+        for p in [0.1, 0.5, 0.9, 0.99] {
+            let mut quantile = P2Quantile::new(p);
+            for &x in &values {
+                quantile.push(x);
+            }
+            let exact = sorted[(p * sorted.len() as f64) as usize];
+            let estimate = quantile.estimate().unwrap();
+            assert!(
+                (estimate - exact).abs() < 0.005,
+                "p{}: {} estimated as {}",
+                p,
+                exact,
+                estimate
+            );
+        }
+    }
+
+    #[test]
+    fn space_saving_bounds_the_true_counts() {
+        // Key i appears about 1 / (i + 1) as often as key 0.
+        let keys = uniform(50_000)
+            .into_iter()
+            .map(|u| format!("k{}", (1000f64.powf(u) - 1.0) as usize))
+            .collect::<Vec<_>>();
+        let mut exact = HashMap::new();
+        for key in &keys {
+            *exact.entry(key.as_str()).or_insert(0) += 1;
+        }
+        let capacity = 50;
+        let mut summary = SpaceSaving::new(capacity);
+        for key in &keys {
+            summary.push(key);
+        }
+        assert!(!summary.is_exact());
+        let counters = summary.counters();
+        assert_eq!(counters.len(), capacity);
+        assert_eq!(counters.iter().map(|c| c.count).sum::<usize>(), keys.len());
+        for counter in counters {
+            let count = exact[counter.key.as_str()];
+            assert!(counter.count - counter.error <= count && count <= counter.count);
+            assert!(counter.error <= keys.len() / capacity);
+        }
+        // Every key seen more often than the largest possible error is monitored.
+        for (key, &count) in &exact {
+            if count > keys.len() / capacity {
+                assert!(counters.iter().any(|c| c.key == *key), "{} is missing", key);
+            }
+        }
+    }
+
+    #[test]
+    fn space_saving_is_exact_below_capacity() {
+        let mut summary = SpaceSaving::new(10);
+        for key in ["a", "b", "a", "c", "a"] {
+            summary.push(key);
+        }
+        assert!(summary.is_exact());
+        let a = summary.counters().iter().find(|c| c.key == "a").unwrap();
+        assert_eq!((a.count, a.error), (3, 0));
+    }
+
+    #[test]
+    fn hyperloglog_estimates_distinct_keys() {
+        for distinct in [100, 10_000, 1_000_000] {
+            let mut hll = HyperLogLog::default();
+            for i in 0..distinct {
+                // Every key twice, which must not change the estimate.
+                hll.push(&i.to_string());
+                hll.push(&i.to_string());
+            }
+            let error = (hll.estimate() - distinct as f64).abs() / distinct as f64;
+            assert!(
+                error < 3.0 * hll.relative_error(),
+                "{} distinct keys estimated as {}",
+                distinct,
+                hll.estimate()
+            );
+        }
+    }
+}
@@ -0,0 +1,118 @@
+//! Descriptive statistics of numeric values.
+
+use super::binning::sorted_quantile;
+use serde::Serialize;
+
+/// Count, extremes, moments and spread of the values of a histogram.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Stats {
+    /// Number of values the statistics are computed from.
+    pub count: usize,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    /// Sample standard deviation, with Bessel's correction.
+    pub std_dev: f64,
+    /// Moment coefficient of skewness, `m3 / m2^1.5`.
+    pub skewness: f64,
+    /// Excess kurtosis, `m4 / m2^2 - 3`, which is 0 for a normal distribution.
+    pub kurtosis: f64,
+    /// Median absolute deviation from the median, or `None` if the values were not kept.
+    pub mad: Option<f64>,
+}
+
+impl Stats {
+    /// The statistics of already sorted, non-empty values.
+    pub(crate) fn of_sorted(sorted_nums: &[f64]) -> Self {
+        let n = sorted_nums.len() as f64;
+        let mean = sorted_nums.iter().sum::<f64>() / n;
+        let (mut m2, mut m3, mut m4) = (0.0, 0.0, 0.0);
+        for &x in sorted_nums {
+            let d = x - mean;
+            m2 += d * d;
+            m3 += d * d * d;
+            m4 += d * d * d * d;
+        }
+        let median = sorted_quantile(sorted_nums, 0.5);
+        let mut deviations = sorted_nums
+            .iter()
+            .map(|&x| (x - median).abs())
+            .collect::<Vec<_>>();
+        deviations.sort_by(super::compare_f64);
+        Stats {
+            mad: Some(sorted_quantile(&deviations, 0.5)),
+            ..Moments {
+                count: sorted_nums.len(),
+                min: sorted_nums[0],
+                max: sorted_nums[sorted_nums.len() - 1],
+                mean,
+                m2,
+                m3,
+                m4,
+            }
+            .stats()
+        }
+    }
+}
+
+/// Count, extremes and central moments of a stream of values, updated one value at a time.
+#[derive(Clone, Debug)]
+pub(crate) struct Moments {
+    count: usize,
+    min: f64,
+    max: f64,
+    mean: f64,
+    /// Sums of the second, third and fourth powers of the deviations from the mean.
+    m2: f64,
+    m3: f64,
+    m4: f64,
+}
+
+impl Default for Moments {
+    fn default() -> Self {
+        Moments {
+            count: 0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            mean: 0.0,
+            m2: 0.0,
+            m3: 0.0,
+            m4: 0.0,
+        }
+    }
+}
+
+impl Moments {
+    /// Adds a value with the one-pass update of Terriberry (2007).
+    pub fn push(&mut self, x: f64) {
+        let n1 = self.count as f64;
+        self.count += 1;
+        let n = self.count as f64;
+        let delta = x - self.mean;
+        let delta_n = delta / n;
+        let term = delta * delta_n * n1;
+        self.mean += delta_n;
+        self.m4 += term * delta_n * delta_n * (n * n - 3.0 * n + 3.0)
+            + 6.0 * delta_n * delta_n * self.m2
+            - 4.0 * delta_n * self.m3;
+        self.m3 += term * delta_n * (n - 2.0) - 3.0 * delta_n * self.m2;
+        self.m2 += term;
+        self.min = self.min.min(x);
+        self.max = self.max.max(x);
+    }
+
+    /// The statistics of the values pushed so far, without a median absolute deviation.
+    pub fn stats(&self) -> Stats {
+        let n = self.count as f64;
+        Stats {
+            count: self.count,
+            min: self.min,
+            max: self.max,
+            mean: self.mean,
+            std_dev: (self.m2 / (n - 1.0).max(1.0)).sqrt(),
+            skewness: n.sqrt() * self.m3 / self.m2.powf(1.5),
+            kurtosis: n * self.m4 / (self.m2 * self.m2) - 3.0,
+            mad: None,
+        }
+    }
+}
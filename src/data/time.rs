@@ -0,0 +1,500 @@
+//! Reading timestamps as seconds since the Unix epoch, and writing them back out.
+
+use std::str::FromStr;
+
+const SECONDS_PER_DAY: i64 = 86_400;
+const MONTH_NAMES: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+/// How the timestamps of an input are written.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimeFormat(Format);
+
+#[derive(Clone, Debug, PartialEq)]
+enum Format {
+    /// A number of seconds, or of `unit`s of a second, since the epoch.
+    Epoch {
+        unit: f64,
+    },
+    /// `2024-05-01`, `2024-05-01T12:30:00`, `2024-05-01 12:30:00.250+02:00` and so on.
+    Iso8601,
+    Pattern(Vec<Item>),
+}
+
+/// One piece of a `strftime`-like pattern.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Item {
+    Literal(char),
+    Year,
+    Month,
+    MonthName,
+    Day,
+    Hour,
+    Minute,
+    Second,
+    /// Digits of a fraction of a second.
+    Fraction,
+    /// An optional `.` followed by the digits of a fraction of a second.
+    DotFraction,
+    /// `Z`, or an offset from UTC such as `+02:00` or `-0500`.
+    Offset,
+    /// Seconds since the epoch.
+    Epoch,
+}
+
+impl FromStr for TimeFormat {
+    type Err = String;
+
+    /// Parses `epoch`, `epoch-ms`, `epoch-us` or `epoch-ns` for numbers of (milli-, micro-,
+    /// nano-) seconds since the epoch, `iso8601` for ISO 8601 dates and times, or a `strftime`
+    /// pattern made of `%Y`, `%m`, `%b`, `%d`, `%H`, `%M`, `%S`, `%f`, `%.f`, `%z`, `%s`, the
+    /// shorthands `%F` (`%Y-%m-%d`) and `%T` (`%H:%M:%S`), `%%`, and literal text.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let format = match s {
+            "epoch" => Format::Epoch { unit: 1.0 },
+            "epoch-ms" => Format::Epoch { unit: 1e-3 },
+            "epoch-us" => Format::Epoch { unit: 1e-6 },
+            "epoch-ns" => Format::Epoch { unit: 1e-9 },
+            "iso8601" | "iso" => Format::Iso8601,
+            _ => Format::Pattern(parse_pattern(s)?),
+        };
+        Ok(TimeFormat(format))
+    }
+}
+
+fn parse_pattern(s: &str) -> Result<Vec<Item>, String> {
+    let mut items = Vec::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            items.push(Item::Literal(c));
+            continue;
+        }
+        match chars.next() {
+            Some('Y') => items.push(Item::Year),
+            Some('m') => items.push(Item::Month),
+            Some('b') => items.push(Item::MonthName),
+            Some('d') => items.push(Item::Day),
+            Some('H') => items.push(Item::Hour),
+            Some('M') => items.push(Item::Minute),
+            Some('S') => items.push(Item::Second),
+            Some('f') => items.push(Item::Fraction),
+            Some('z') => items.push(Item::Offset),
+            Some('s') => items.push(Item::Epoch),
+            Some('%') => items.push(Item::Literal('%')),
+            Some('F') => items.extend(parse_pattern("%Y-%m-%d")?),
+            Some('T') => items.extend(parse_pattern("%H:%M:%S")?),
+            Some('.') if chars.next() == Some('f') => items.push(Item::DotFraction),
+            Some(c) => return Err(format!("unsupported time format specifier %{}", c)),
+            None => return Err("time format ends with a lone %".to_owned()),
+        }
+    }
+    if items.is_empty() {
+        return Err("time format is empty".to_owned());
+    }
+    Ok(items)
+}
+
+impl TimeFormat {
+    /// The time written at the start of `s`, in seconds since the epoch, and the length in
+    /// bytes of the text it takes up, or `None` if `s` does not start with a valid time.
+    ///
+    /// Times without an offset from UTC are taken to be in UTC.
+    pub fn parse_prefix(&self, s: &str) -> Option<(f64, usize)> {
+        let mut cursor = Cursor { s, pos: 0 };
+        let seconds = match &self.0 {
+            Format::Epoch { unit } => cursor.decimal()? * unit,
+            Format::Iso8601 => cursor.iso8601()?,
+            Format::Pattern(items) => cursor.pattern(items)?,
+        };
+        Some((seconds, cursor.pos))
+    }
+
+    /// The time written in `s`, ignoring surrounding whitespace, in seconds since the epoch.
+    pub fn parse(&self, s: &str) -> Option<f64> {
+        let s = s.trim();
+        match self.parse_prefix(s)? {
+            (seconds, len) if len == s.len() => Some(seconds),
+            _ => None,
+        }
+    }
+}
+
+struct Cursor<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl Cursor<'_> {
+    fn peek(&self) -> Option<char> {
+        self.s[self.pos..].chars().next()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// An unsigned number of between one and `max_digits` digits.
+    fn number(&mut self, max_digits: usize) -> Option<u32> {
+        let digits = self.s[self.pos..]
+            .bytes()
+            .take(max_digits)
+            .take_while(u8::is_ascii_digit)
+            .count();
+        if digits == 0 {
+            return None;
+        }
+        let value = self.s[self.pos..self.pos + digits].parse().ok()?;
+        self.pos += digits;
+        Some(value)
+    }
+
+    /// The fraction written by the digits that follow, or `None` if no digit follows.
+    fn fraction(&mut self) -> Option<f64> {
+        let digits = self.s[self.pos..]
+            .bytes()
+            .take_while(u8::is_ascii_digit)
+            .count();
+        if digits == 0 {
+            return None;
+        }
+        let fraction = format!("0.{}", &self.s[self.pos..self.pos + digits]);
+        self.pos += digits;
+        fraction.parse().ok()
+    }
+
+    /// A decimal number with an optional sign and fractional part.
+    fn decimal(&mut self) -> Option<f64> {
+        let start = self.pos;
+        if !self.eat('-') {
+            self.eat('+');
+        }
+        let whole = self.s[self.pos..]
+            .bytes()
+            .take_while(u8::is_ascii_digit)
+            .count();
+        self.pos += whole;
+        let dot = self.pos;
+        if !(self.eat('.') && self.fraction().is_some()) {
+            self.pos = dot;
+            if whole == 0 {
+                self.pos = start;
+                return None;
+            }
+        }
+        self.s[start..self.pos].parse().ok()
+    }
+
+    /// `Z` or `±hh[:mm]` or `±hhmm`, as seconds to subtract to get UTC.
+    fn offset(&mut self) -> Option<i64> {
+        if self.eat('Z') || self.eat('z') {
+            return Some(0);
+        }
+        let sign = if self.eat('+') {
+            1
+        } else if self.eat('-') {
+            -1
+        } else {
+            return None;
+        };
+        let start = self.pos;
+        let hours = self.number(2)?;
+        if self.pos - start != 2 {
+            return None;
+        }
+        self.eat(':');
+        let minutes = self.number(2).unwrap_or(0);
+        (hours <= 23 && minutes <= 59).then(|| sign * (hours as i64 * 3600 + minutes as i64 * 60))
+    }
+
+    fn month_name(&mut self) -> Option<u32> {
+        let rest = self.s[self.pos..].get(..3)?.to_ascii_lowercase();
+        let month = MONTH_NAMES.iter().position(|name| *name == rest)?;
+        self.pos += 3;
+        Some(month as u32 + 1)
+    }
+
+    fn iso8601(&mut self) -> Option<f64> {
+        let year = self.number(4)? as i64;
+        self.eat('-').then_some(())?;
+        let month = self.number(2)?;
+        self.eat('-').then_some(())?;
+        let mut time = Civil {
+            year,
+            month,
+            day: self.number(2)?,
+            ..Civil::default()
+        };
+        let date_end = self.pos;
+        if self.eat('T') || self.eat('t') || self.eat(' ') {
+            match self.number(2) {
+                Some(hour) if self.eat(':') => {
+                    time.hour = hour;
+                    time.minute = self.number(2)?;
+                    if self.eat(':') {
+                        time.second = self.number(2)? as f64;
+                        let dot = self.pos;
+                        if self.eat('.') || self.eat(',') {
+                            match self.fraction() {
+                                Some(fraction) => time.second += fraction,
+                                None => self.pos = dot,
+                            }
+                        }
+                    }
+                    let zone = self.pos;
+                    match self.offset() {
+                        Some(offset) => time.offset = offset,
+                        None => self.pos = zone,
+                    }
+                }
+                // Only a date, followed by something else.
+                _ => self.pos = date_end,
+            }
+        }
+        time.seconds()
+    }
+
+    fn pattern(&mut self, items: &[Item]) -> Option<f64> {
+        let mut time = Civil::default();
+        let mut epoch = None;
+        for item in items {
+            match *item {
+                Item::Literal(c) => self.eat(c).then_some(())?,
+                Item::Year => time.year = self.number(4)? as i64,
+                Item::Month => time.month = self.number(2)?,
+                Item::MonthName => time.month = self.month_name()?,
+                Item::Day => time.day = self.number(2)?,
+                Item::Hour => time.hour = self.number(2)?,
+                Item::Minute => time.minute = self.number(2)?,
+                Item::Second => time.second += self.number(2)? as f64,
+                Item::Fraction => time.second += self.fraction()?,
+                Item::DotFraction => {
+                    let dot = self.pos;
+                    if self.eat('.') {
+                        match self.fraction() {
+                            Some(fraction) => time.second += fraction,
+                            None => self.pos = dot,
+                        }
+                    }
+                }
+                Item::Offset => time.offset = self.offset()?,
+                Item::Epoch => epoch = Some(self.decimal()?),
+            }
+        }
+        epoch.or_else(|| time.seconds())
+    }
+}
+
+/// The parts of a date and time as they are written.
+struct Civil {
+    year: i64,
+    month: u32,
+    day: u32,
+    hour: u32,
+    minute: u32,
+    second: f64,
+    /// Seconds ahead of UTC.
+    offset: i64,
+}
+
+impl Default for Civil {
+    fn default() -> Self {
+        Civil {
+            year: 1970,
+            month: 1,
+            day: 1,
+            hour: 0,
+            minute: 0,
+            second: 0.0,
+            offset: 0,
+        }
+    }
+}
+
+impl Civil {
+    /// Seconds since the epoch, or `None` if any part is out of range.
+    fn seconds(&self) -> Option<f64> {
+        let valid = (1..=12).contains(&self.month)
+            && self.day >= 1
+            && self.day <= days_in_month(self.year, self.month)
+            && self.hour <= 23
+            && self.minute <= 59
+            && self.second < 61.0;
+        valid.then(|| {
+            let days = days_from_civil(self.year, self.month, self.day);
+            let whole = days * SECONDS_PER_DAY + self.hour as i64 * 3600 + self.minute as i64 * 60
+                - self.offset;
+            whole as f64 + self.second
+        })
+    }
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days from 1970-01-01 to the given date of the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_from_march = (month as i64 + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The year, month and day that are `days` after 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_from_march + 2) / 5 + 1) as u32;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Writes the time `seconds` since the epoch in UTC as `YYYY-MM-DD hh:mm:ss.fff`, leaving out
+/// the parts finer than `resolution` seconds: the time of day from a day on, and the seconds
+/// from a minute on.
+pub fn format_timestamp(seconds: f64, resolution: f64) -> String {
+    let decimals = if resolution >= 1.0 {
+        0
+    } else {
+        (-resolution.log10()).ceil().clamp(0.0, 6.0) as u32
+    };
+    let scale = 10_i64.pow(decimals);
+    let ticks = (seconds * scale as f64).round() as i64;
+    let (days, ticks_of_day) = (
+        ticks.div_euclid(SECONDS_PER_DAY * scale),
+        ticks.rem_euclid(SECONDS_PER_DAY * scale),
+    );
+    let (year, month, day) = civil_from_days(days);
+    let date = format!("{:04}-{:02}-{:02}", year, month, day);
+    if resolution >= SECONDS_PER_DAY as f64 {
+        return date;
+    }
+    let second_of_day = ticks_of_day / scale;
+    let (hour, minute, second) = (
+        second_of_day / 3600,
+        second_of_day / 60 % 60,
+        second_of_day % 60,
+    );
+    if resolution >= 60.0 {
+        return format!("{} {:02}:{:02}", date, hour, minute);
+    }
+    let mut text = format!("{} {:02}:{:02}:{:02}", date, hour, minute, second);
+    if decimals > 0 {
+        let fraction = ticks_of_day % scale;
+        text += &format!(".{:0width$}", fraction, width = decimals as usize);
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(format: &str, s: &str) -> Option<f64> {
+        format.parse::<TimeFormat>().unwrap().parse(s)
+    }
+
+    #[test]
+    fn days_round_trip_through_dates() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(
+            days_from_civil(2000, 3, 1) - days_from_civil(2000, 2, 28),
+            2
+        );
+        assert_eq!(
+            days_from_civil(1900, 3, 1) - days_from_civil(1900, 2, 28),
+            1
+        );
+        assert_eq!(civil_from_days(days_from_civil(2024, 2, 29)), (2024, 2, 29));
+        let mut previous = None;
+        for days in -800_000..800_000 {
+            let (year, month, day) = civil_from_days(days);
+            assert!((1..=days_in_month(year, month)).contains(&day));
+            assert_eq!(days_from_civil(year, month, day), days);
+            if let Some((previous_year, previous_month, previous_day)) = previous {
+                if day == 1 {
+                    assert_eq!(previous_day, days_in_month(previous_year, previous_month));
+                } else {
+                    assert_eq!((year, month), (previous_year, previous_month));
+                }
+            }
+            previous = Some((year, month, day));
+        }
+    }
+
+    #[test]
+    fn parses_formats() {
+        assert_eq!(parse("epoch", "1714566605.5"), Some(1_714_566_605.5));
+        assert_eq!(parse("epoch-ms", "1714566605500"), Some(1_714_566_605.5));
+        assert_eq!(parse("iso8601", "2024-05-01"), Some(1_714_521_600.0));
+        assert_eq!(
+            parse("iso8601", "2024-05-01T14:30:05.5+02:00"),
+            Some(1_714_566_605.5)
+        );
+        assert_eq!(parse("iso", "1960-03-01T00:00Z"), Some(-310_435_200.0));
+        assert_eq!(
+            parse("%Y-%m-%dT%H:%M:%S", "2024-05-01T12:30:05"),
+            Some(1_714_566_605.0)
+        );
+        assert_eq!(
+            parse("%d/%b/%Y:%T %z", "01/May/2024:14:30:05 +0200"),
+            Some(1_714_566_605.0)
+        );
+        assert_eq!(
+            parse("%F %T%.f", "2024-05-01 12:30:05"),
+            Some(1_714_566_605.0)
+        );
+        assert_eq!(parse("%s", "-1"), Some(-1.0));
+    }
+
+    #[test]
+    fn rejects_invalid_times() {
+        assert_eq!(parse("iso8601", "2024-02-30"), None);
+        assert_eq!(parse("iso8601", "2023-02-29"), None);
+        assert_eq!(parse("iso8601", "2024-05-01T24:00"), None);
+        assert_eq!(parse("%F", "2024-05-01 trailing"), None);
+        assert!("%q".parse::<TimeFormat>().is_err());
+        assert!("%".parse::<TimeFormat>().is_err());
+    }
+
+    #[test]
+    fn formats_timestamps() {
+        assert_eq!(
+            format_timestamp(1_714_566_605.25, 0.01),
+            "2024-05-01 12:30:05.25"
+        );
+        assert_eq!(
+            format_timestamp(1_714_566_605.0, 1.0),
+            "2024-05-01 12:30:05"
+        );
+        assert_eq!(format_timestamp(1_714_566_605.0, 60.0), "2024-05-01 12:30");
+        assert_eq!(format_timestamp(-310_435_200.0, 86_400.0), "1960-03-01");
+        assert_eq!(format_timestamp(-0.5, 0.1), "1969-12-31 23:59:59.5");
+    }
+}
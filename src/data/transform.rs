@@ -0,0 +1,527 @@
+//! Rolling statistics and other transforms of a series, computed one value at a time.
+
+use super::binning::sorted_quantile;
+use std::collections::VecDeque;
+
+/// One step of a [`Pipeline`]. A window holds the latest `width` values, the current one
+/// included, unless it is centered. Missing values, which are NaN, stay missing and are left out
+/// of windows.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Transform {
+    /// Exponentially weighted moving average, giving the newest value a weight of `alpha`.
+    Ewma { alpha: f64 },
+    /// Mean of a window, or if `centered`, of the `width / 2` values on either side and the
+    /// value itself, which is only known once the values after it have arrived.
+    Mean { width: usize, centered: bool },
+    /// Median of a window.
+    Median { width: usize },
+    /// A band from the smallest to the largest value of a window, around the unchanged values.
+    MinMax { width: usize },
+    /// A band between the `low` and `high` percentiles of a window, around the unchanged values.
+    Percentiles { width: usize, low: f64, high: f64 },
+    /// Change from the previous value.
+    Difference,
+    /// Change from the previous value per unit of x.
+    Rate,
+    /// Change from the previous value, in percent of it.
+    PercentChange,
+    /// Running total.
+    Sum,
+}
+
+impl Transform {
+    /// Every kind of transform, with default parameters.
+    pub const ALL: [Transform; 9] = [
+        Transform::Ewma { alpha: 0.1 },
+        Transform::Mean {
+            width: 10,
+            centered: true,
+        },
+        Transform::Median { width: 10 },
+        Transform::MinMax { width: 10 },
+        Transform::Percentiles {
+            width: 100,
+            low: 10.0,
+            high: 90.0,
+        },
+        Transform::Difference,
+        Transform::Rate,
+        Transform::PercentChange,
+        Transform::Sum,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Transform::Ewma { .. } => "EWMA",
+            Transform::Mean { .. } => "Mean",
+            Transform::Median { .. } => "Median",
+            Transform::MinMax { .. } => "Min/max",
+            Transform::Percentiles { .. } => "Percentiles",
+            Transform::Difference => "Difference",
+            Transform::Rate => "Rate",
+            Transform::PercentChange => "% change",
+            Transform::Sum => "Cumulative",
+        }
+    }
+
+    /// Whether the transform draws a band around the values rather than changing them.
+    pub fn is_band(&self) -> bool {
+        matches!(
+            self,
+            Transform::MinMax { .. } | Transform::Percentiles { .. }
+        )
+    }
+
+    fn state(&self) -> State {
+        match *self {
+            Transform::Ewma { .. } => State::Ewma(None),
+            Transform::Mean {
+                width,
+                centered: true,
+            } => State::Centered(Centered::new(width / 2)),
+            Transform::Mean { width, .. } => State::Window(Window::new(width, false)),
+            Transform::Median { width }
+            | Transform::MinMax { width }
+            | Transform::Percentiles { width, .. } => State::Window(Window::new(width, true)),
+            Transform::Difference | Transform::Rate | Transform::PercentChange => {
+                State::Previous(None)
+            }
+            Transform::Sum => State::Sum(0.0),
+        }
+    }
+}
+
+/// The value of a series at `x` after a [`Pipeline`], with the band around it from the last
+/// band transform, if there is one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transformed {
+    pub x: f64,
+    pub y: f64,
+    pub band: Option<(f64, f64)>,
+}
+
+/// Transforms applied in order to a series, one value at a time, so that values that arrive
+/// later are added without going over the earlier ones again.
+///
+/// The transforms after a band apply to its bottom and top as well as to the values. A centered
+/// mean holds back the latest values until those after them arrive, or until
+/// [`Pipeline::finish`].
+#[derive(Clone, Debug)]
+pub struct Pipeline {
+    stages: Vec<Stage>,
+}
+
+#[derive(Clone, Debug)]
+struct Stage {
+    transform: Transform,
+    /// For the values, and for the bottom and top of a band.
+    states: [State; 3],
+}
+
+impl Pipeline {
+    pub fn new(transforms: &[Transform]) -> Self {
+        let stages = transforms
+            .iter()
+            .map(|transform| Stage {
+                transform: *transform,
+                states: [transform.state(), transform.state(), transform.state()],
+            })
+            .collect();
+        Pipeline { stages }
+    }
+
+    /// Adds the value `y` at `x`, returning the next transformed value, if none is held back.
+    pub fn push(&mut self, x: f64, y: f64) -> Option<Transformed> {
+        self.push_from(0, Transformed { x, y, band: None })
+    }
+
+    /// Returns the values held back at the end of the series, after which nothing more can be
+    /// pushed.
+    pub fn finish(&mut self) -> Vec<Transformed> {
+        let mut flushed = Vec::new();
+        for i in 0..self.stages.len() {
+            while let Some(value) = self.stages[i].flush() {
+                flushed.extend(self.push_from(i + 1, value));
+            }
+        }
+        flushed
+    }
+
+    /// Runs `value` through the stages from `start` on.
+    fn push_from(&mut self, start: usize, mut value: Transformed) -> Option<Transformed> {
+        for stage in &mut self.stages[start..] {
+            let transform = &stage.transform;
+            let [values, bottoms, tops] = &mut stage.states;
+            if let State::Centered(centered) = values {
+                // The bottom and top are always pushed, so that they are held back along with
+                // the values.
+                let (bottom, top) = value.band.unwrap_or((f64::NAN, f64::NAN));
+                let band = centered_band(bottoms, tops, value.x, bottom, top);
+                let (x, y) = centered.push(value.x, value.y)?;
+                value = Transformed { x, y, band };
+            } else if transform.is_band() {
+                let State::Window(window) = values else {
+                    unreachable!("bands are taken over windows")
+                };
+                window.push(value.y);
+                value.band = band(transform, window).filter(|_| !value.y.is_nan());
+            } else {
+                let x = value.x;
+                value.y = apply(transform, values, x, value.y);
+                value.band = value.band.map(|(bottom, top)| {
+                    (
+                        apply(transform, bottoms, x, bottom),
+                        apply(transform, tops, x, top),
+                    )
+                });
+            }
+        }
+        Some(value)
+    }
+}
+
+impl Stage {
+    /// The next value held back by the stage, at the end of the series.
+    fn flush(&mut self) -> Option<Transformed> {
+        let [values, bottoms, tops] = &mut self.states;
+        let State::Centered(centered) = values else {
+            return None;
+        };
+        let (x, y) = centered.flush()?;
+        let flush = |state: &mut State| match state {
+            State::Centered(centered) => centered.flush().map_or(f64::NAN, |(_, y)| y),
+            _ => unreachable!("the bottom and top are held back with the values"),
+        };
+        let (bottom, top) = (flush(bottoms), flush(tops));
+        Some(Transformed {
+            x,
+            y,
+            band: (!(bottom.is_nan() && top.is_nan())).then_some((bottom, top)),
+        })
+    }
+}
+
+/// Pushes the bottom and top of a band through the states of a centered mean, returning the
+/// band that is no longer held back, if it is not missing.
+fn centered_band(
+    bottoms: &mut State,
+    tops: &mut State,
+    x: f64,
+    bottom: f64,
+    top: f64,
+) -> Option<(f64, f64)> {
+    let push = |state: &mut State, y: f64| match state {
+        State::Centered(centered) => centered.push(x, y).map_or(f64::NAN, |(_, y)| y),
+        _ => unreachable!("the bottom and top are held back with the values"),
+    };
+    let (bottom, top) = (push(bottoms, bottom), push(tops, top));
+    (!(bottom.is_nan() && top.is_nan())).then_some((bottom, top))
+}
+
+#[derive(Clone, Debug)]
+enum State {
+    Ewma(Option<f64>),
+    Window(Window),
+    Centered(Centered),
+    /// The x and value of the last value that was not missing.
+    Previous(Option<(f64, f64)>),
+    Sum(f64),
+}
+
+/// The latest values of a series.
+#[derive(Clone, Debug)]
+struct Window {
+    width: usize,
+    /// NaN where a value is missing.
+    values: VecDeque<f64>,
+    /// The values that are not missing in ascending order, if they are needed.
+    sorted: Option<Vec<f64>>,
+    sum: f64,
+    count: usize,
+}
+
+impl Window {
+    fn new(width: usize, sorted: bool) -> Self {
+        Window {
+            width: width.max(1),
+            values: VecDeque::new(),
+            sorted: sorted.then(Vec::new),
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn push(&mut self, value: f64) {
+        if self.values.len() == self.width {
+            self.pop();
+        }
+        self.values.push_back(value);
+        if !value.is_nan() {
+            self.sum += value;
+            self.count += 1;
+            if let Some(sorted) = &mut self.sorted {
+                sorted.insert(sorted.partition_point(|&v| v < value), value);
+            }
+        }
+    }
+
+    /// Drops the oldest value.
+    fn pop(&mut self) {
+        let Some(old) = self.values.pop_front() else {
+            return;
+        };
+        if !old.is_nan() {
+            self.sum -= old;
+            self.count -= 1;
+            if let Some(sorted) = &mut self.sorted {
+                sorted.remove(sorted.partition_point(|&v| v < old));
+            }
+        }
+    }
+
+    fn mean(&self) -> f64 {
+        self.sum / self.count as f64
+    }
+
+    /// The value at probability `p` of the values in the window, if there are any.
+    fn quantile(&self, p: f64) -> Option<f64> {
+        let sorted = self
+            .sorted
+            .as_ref()
+            .expect("window keeps its values sorted");
+        (!sorted.is_empty()).then(|| sorted_quantile(sorted, p))
+    }
+}
+
+/// The mean of a window centered on each value, given the values one at a time.
+#[derive(Clone, Debug)]
+struct Centered {
+    /// How many values on either side of a value are averaged with it.
+    half: usize,
+    window: Window,
+    /// The x of the values whose mean is not known yet, the oldest first.
+    pending: VecDeque<f64>,
+}
+
+impl Centered {
+    fn new(half: usize) -> Self {
+        Centered {
+            half,
+            window: Window::new(2 * half + 1, false),
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Adds the value `y` at `x`, returning the x and mean of the value `half` places before it,
+    /// if there is one.
+    fn push(&mut self, x: f64, y: f64) -> Option<(f64, f64)> {
+        self.window.push(y);
+        self.pending.push_back(x);
+        if self.pending.len() <= self.half {
+            return None;
+        }
+        Some(self.next())
+    }
+
+    /// Returns the x and mean of the oldest value still held back, with as many of the values
+    /// after it as there are, at the end of the series.
+    fn flush(&mut self) -> Option<(f64, f64)> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        while self.window.values.len() > self.half + self.pending.len() {
+            self.window.pop();
+        }
+        Some(self.next())
+    }
+
+    /// The x and mean of the oldest pending value, which is no longer pending.
+    fn next(&mut self) -> (f64, f64) {
+        let window = &self.window;
+        let y = window.values[window.values.len() - self.pending.len()];
+        let x = self.pending.pop_front().expect("a value is pending");
+        (x, if y.is_nan() { y } else { window.mean() })
+    }
+}
+
+fn apply(transform: &Transform, state: &mut State, x: f64, y: f64) -> f64 {
+    match (transform, state) {
+        (Transform::Ewma { alpha }, State::Ewma(average)) => {
+            if y.is_nan() {
+                return y;
+            }
+            let next = average.map_or(y, |average| average + alpha * (y - average));
+            *average = Some(next);
+            next
+        }
+        (Transform::Mean { .. }, State::Window(window)) => {
+            window.push(y);
+            if y.is_nan() {
+                y
+            } else {
+                window.mean()
+            }
+        }
+        (Transform::Median { .. }, State::Window(window)) => {
+            window.push(y);
+            if y.is_nan() {
+                y
+            } else {
+                window.quantile(0.5).unwrap()
+            }
+        }
+        (_, State::Previous(previous)) => {
+            if y.is_nan() {
+                return y;
+            }
+            let change = match previous.replace((x, y)) {
+                None => f64::NAN,
+                Some((x0, y0)) => match transform {
+                    Transform::Difference => y - y0,
+                    Transform::Rate => (y - y0) / (x - x0),
+                    _ => (y - y0) / y0.abs() * 100.0,
+                },
+            };
+            // Changes over no time or from zero are missing.
+            if change.is_finite() {
+                change
+            } else {
+                f64::NAN
+            }
+        }
+        (Transform::Sum, State::Sum(total)) => {
+            if y.is_nan() {
+                return y;
+            }
+            *total += y;
+            *total
+        }
+        (transform, state) => unreachable!("{:?} does not keep a {:?}", transform, state),
+    }
+}
+
+fn band(transform: &Transform, window: &Window) -> Option<(f64, f64)> {
+    match *transform {
+        Transform::MinMax { .. } => Some((window.quantile(0.0)?, window.quantile(1.0)?)),
+        Transform::Percentiles { low, high, .. } => Some((
+            window.quantile(low / 100.0)?,
+            window.quantile(high / 100.0)?,
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const YS: [f64; 7] = [1.0, 5.0, f64::NAN, 3.0, 2.0, 8.0, 4.0];
+
+    /// The values of `ys` at x = 0, 1, … after `transforms`, with those held back at the end.
+    fn run(transforms: &[Transform], ys: &[f64]) -> Vec<Transformed> {
+        let mut pipeline = Pipeline::new(transforms);
+        let mut values: Vec<_> = ys
+            .iter()
+            .enumerate()
+            .filter_map(|(x, &y)| pipeline.push(x as f64, y))
+            .collect();
+        values.extend(pipeline.finish());
+        assert!(values.iter().enumerate().all(|(x, v)| v.x == x as f64));
+        values
+    }
+
+    fn assert_ys(values: &[Transformed], expected: &[f64]) {
+        assert_eq!(values.len(), expected.len());
+        for (value, &y) in values.iter().zip(expected) {
+            assert!(
+                value.y == y || (value.y - y).abs() < 1e-12 || (value.y.is_nan() && y.is_nan()),
+                "{:?} is not {:?}",
+                values,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn trailing_windows_evict_their_oldest_values() {
+        let mean = run(
+            &[Transform::Mean {
+                width: 2,
+                centered: false,
+            }],
+            &YS,
+        );
+        assert_ys(&mean, &[1.0, 3.0, f64::NAN, 3.0, 2.5, 5.0, 6.0]);
+        let median = run(&[Transform::Median { width: 3 }], &YS);
+        assert_ys(&median, &[1.0, 3.0, f64::NAN, 4.0, 2.5, 3.0, 4.0]);
+    }
+
+    #[test]
+    fn percentile_bands_evict_their_oldest_values() {
+        let values = run(
+            &[Transform::Percentiles {
+                width: 4,
+                low: 25.0,
+                high: 75.0,
+            }],
+            &YS,
+        );
+        assert_ys(&values, &YS);
+        let bands: Vec<_> = values.iter().map(|v| v.band).collect();
+        assert_eq!(
+            bands,
+            [
+                Some((1.0, 1.0)),
+                Some((2.0, 4.0)),
+                None,
+                Some((2.0, 4.0)),
+                Some((2.5, 4.0)),
+                Some((2.5, 5.5)),
+                Some((2.75, 5.0)),
+            ]
+        );
+        let min_max = run(&[Transform::MinMax { width: 2 }], &YS);
+        assert_eq!(min_max[6].band, Some((4.0, 8.0)));
+    }
+
+    #[test]
+    fn centered_mean_holds_values_back_until_the_end() {
+        let transforms = [Transform::Mean {
+            width: 2,
+            centered: true,
+        }];
+        let mut pipeline = Pipeline::new(&transforms);
+        assert_eq!(pipeline.push(0.0, 1.0), None);
+        assert!(pipeline.push(1.0, 5.0).is_some());
+        let values = run(&transforms, &YS);
+        assert_ys(
+            &values,
+            &[3.0, 3.0, f64::NAN, 2.5, 13.0 / 3.0, 14.0 / 3.0, 6.0],
+        );
+    }
+
+    #[test]
+    fn transforms_after_a_centered_mean_see_the_flushed_values() {
+        let values = run(
+            &[
+                Transform::MinMax { width: 1 },
+                Transform::Mean {
+                    width: 2,
+                    centered: true,
+                },
+                Transform::Sum,
+            ],
+            &[1.0, 2.0, 6.0],
+        );
+        assert_ys(&values, &[1.5, 4.5, 8.5]);
+        assert_eq!(values[2].band, Some((8.5, 8.5)));
+    }
+
+    #[test]
+    fn changes_skip_missing_values() {
+        let values = run(&[Transform::Difference], &YS);
+        assert_ys(&values, &[f64::NAN, 4.0, f64::NAN, -2.0, -1.0, 6.0, -4.0]);
+        let values = run(&[Transform::PercentChange], &[2.0, 3.0, 0.0, 1.0]);
+        assert_ys(&values, &[f64::NAN, 50.0, -100.0, f64::NAN]);
+    }
+}
@@ -1,4 +1,6 @@
 pub mod data;
+pub mod render;
+pub mod report;
 
 use regex::Regex;
 
@@ -0,0 +1,874 @@
+//! Drawing histograms without opening a window: to SVG and PNG files, or as text.
+
+use crate::data::{Histogram, Normalization, Scale, Series};
+use ab_glyph::{point, Font, FontRef, PxScale, ScaleFont};
+use egui::Color32;
+use std::fmt::{self, Write as _};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Image size, matching the default window size of the viewers.
+const WIDTH: f32 = 1024.0;
+const HEIGHT: f32 = 600.0;
+
+const MARGIN_LEFT: f32 = 70.0;
+const MARGIN_RIGHT: f32 = 20.0;
+const MARGIN_TOP: f32 = 50.0;
+const MARGIN_BOTTOM: f32 = 50.0;
+
+const BACKGROUND_COLOR: Color32 = Color32::WHITE;
+const TEXT_COLOR: Color32 = Color32::from_rgb(40, 40, 40);
+const AXIS_COLOR: Color32 = Color32::from_rgb(120, 120, 120);
+const GRID_COLOR: Color32 = Color32::from_rgb(225, 225, 225);
+
+/// Bar characters in eighths of a cell, from one eighth to a full block.
+const BLOCKS: [char; 8] = ['▏', '▎', '▍', '▌', '▋', '▊', '▉', '█'];
+/// Longest label printed in text mode before it is shortened.
+const MAX_TEXT_LABEL: usize = 30;
+
+const TITLE_SIZE: f32 = 20.0;
+const LABEL_SIZE: f32 = 12.0;
+
+/// Errors that can occur while writing an image.
+#[derive(Debug)]
+pub enum RenderError {
+    /// The image could not be written.
+    Io(io::Error),
+    /// The file extension is not one of the supported image formats.
+    UnsupportedFormat(String),
+    /// The PNG encoder failed.
+    Png(String),
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderError::Io(e) => write!(f, "failed to write image: {}", e),
+            RenderError::UnsupportedFormat(ext) => write!(
+                f,
+                "unsupported image format {:?}, expected .svg or .png",
+                ext
+            ),
+            RenderError::Png(e) => write!(f, "failed to encode PNG: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RenderError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for RenderError {
+    fn from(e: io::Error) -> Self {
+        RenderError::Io(e)
+    }
+}
+
+/// How the bars of several series share a bin.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum BarLayout {
+    /// On top of each other, translucent.
+    #[default]
+    Overlay,
+    /// Side by side, each a fraction of the bin wide.
+    Grouped,
+}
+
+impl BarLayout {
+    /// The `(left, right)` edges of the bar of series `index` out of `count` in the bin spanning
+    /// `[lower, upper]`, leaving a small gap between bins.
+    pub fn bar_span(self, lower: f64, upper: f64, index: usize, count: usize) -> (f64, f64) {
+        let gap = (upper - lower) * 0.01;
+        let (lower, upper) = (lower + gap, upper - gap);
+        match self {
+            BarLayout::Overlay => (lower, upper),
+            BarLayout::Grouped => {
+                let width = (upper - lower) / count.max(1) as f64;
+                let left = lower + index as f64 * width;
+                (left, left + width)
+            }
+        }
+    }
+
+    /// The fill colour of a bar in a chart of `count` series.
+    pub fn bar_color(self, color: Color32, count: usize) -> Color32 {
+        if self == BarLayout::Overlay && count > 1 {
+            color.gamma_multiply(0.5)
+        } else {
+            color
+        }
+    }
+}
+
+/// One or more histograms on the same bins, with a title and colours, ready to be drawn to a
+/// file.
+pub struct Figure<'a> {
+    pub series: &'a [Series],
+    pub title: &'a str,
+    pub layout: BarLayout,
+    /// Bar colour of each series, repeated if there are more series than colours.
+    pub bar_colors: &'a [Color32],
+    /// Colours of the percentile lines of a single series, in the order of
+    /// [`Histogram::quantiles`] and repeated if there are more quantiles. With several series the lines take the colour of their series,
+    /// dashed for all but the median.
+    pub quantile_colors: &'a [Color32],
+    /// Draw categorical bars horizontally, from the top down, with their labels on the left.
+    /// Ignored for numeric histograms.
+    pub horizontal: bool,
+    /// What the bar heights stand for.
+    pub normalization: Normalization,
+}
+
+impl Figure<'_> {
+    /// Writes the figure to `path` as SVG or PNG, depending on its extension.
+    pub fn save(&self, path: &Path) -> Result<(), RenderError> {
+        let ext = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        match ext.as_str() {
+            "svg" => Ok(fs::write(path, self.to_svg())?),
+            "png" => Ok(fs::write(path, self.to_png()?)?),
+            _ => Err(RenderError::UnsupportedFormat(ext)),
+        }
+    }
+
+    pub fn to_svg(&self) -> String {
+        svg(&self.shapes())
+    }
+
+    /// The figure as an encoded PNG image.
+    pub fn to_png(&self) -> Result<Vec<u8>, RenderError> {
+        png(&self.shapes())
+    }
+
+    /// Lays out the figure as a list of shapes, in pixels from the top left corner.
+    fn shapes(&self) -> Vec<Shape> {
+        let series = self.series;
+        let Some(first) = series.first() else {
+            return Vec::new();
+        };
+        let bins = &first.histogram.bins;
+        let scale = first.histogram.scale;
+        let horizontal = self.horizontal && first.histogram.is_categorical();
+        let left = if horizontal {
+            let label_width = bins
+                .iter()
+                .map(|b| text_width(&shorten(&b.label, MAX_TEXT_LABEL), LABEL_SIZE))
+                .fold(0.0, f32::max);
+            (label_width + 16.0).clamp(MARGIN_LEFT, WIDTH / 3.0)
+        } else {
+            MARGIN_LEFT
+        };
+        let right = WIDTH - MARGIN_RIGHT;
+        let (top, bottom) = (MARGIN_TOP, HEIGHT - MARGIN_BOTTOM);
+
+        let x_min = bins.first().map_or(0.0, |b| scale.forward(b.lower));
+        let x_max = bins.last().map_or(1.0, |b| scale.forward(b.upper));
+        let x_span = if x_max > x_min { x_max - x_min } else { 1.0 };
+        let max_height = series
+            .iter()
+            .map(|s| s.histogram.max_height(self.normalization))
+            .fold(0.0, f64::max);
+        let y_max = if max_height > 0.0 {
+            max_height * 1.05
+        } else {
+            1.0
+        };
+        // Counts are whole numbers, so only whole ticks are labelled.
+        let whole = self.normalization == Normalization::Count;
+        let to_x = |x: f64| left + ((x - x_min) / x_span) as f32 * (right - left);
+        let to_y = |y: f64| bottom - (y / y_max) as f32 * (bottom - top);
+        let bar_color = |i: usize| {
+            let color = self.bar_colors[i % self.bar_colors.len().max(1)];
+            self.layout.bar_color(color, series.len())
+        };
+
+        let mut shapes = vec![Shape::Rect {
+            x: 0.0,
+            y: 0.0,
+            w: WIDTH,
+            h: HEIGHT,
+            color: BACKGROUND_COLOR,
+        }];
+
+        if horizontal {
+            self.push_horizontal_bars(&mut shapes, left, y_max, whole);
+        } else {
+            for tick in nice_ticks(0.0, y_max, 6)
+                .into_iter()
+                .filter(|tick| !whole || tick.fract() == 0.0)
+            {
+                let y = to_y(tick);
+                shapes.push(Shape::line(left, y, right, y, GRID_COLOR, 1.0));
+                shapes.push(Shape::text(
+                    left - 8.0,
+                    y + LABEL_SIZE / 3.0,
+                    format_number(tick),
+                    LABEL_SIZE,
+                    Anchor::End,
+                ));
+            }
+
+            if first.histogram.is_categorical() {
+                let step = bins.len().div_ceil(40).max(1);
+                for bin in bins.iter().step_by(step) {
+                    shapes.push(Shape::text(
+                        to_x(bin.mid()),
+                        bottom + 18.0,
+                        bin.label.clone(),
+                        LABEL_SIZE,
+                        Anchor::Middle,
+                    ));
+                }
+            } else {
+                for tick in nice_ticks(x_min, x_max, 8) {
+                    let x = to_x(tick);
+                    shapes.push(Shape::line(x, bottom, x, bottom + 5.0, AXIS_COLOR, 1.0));
+                    shapes.push(Shape::text(
+                        x,
+                        bottom + 18.0,
+                        format_number(scale.inverse(tick)),
+                        LABEL_SIZE,
+                        Anchor::Middle,
+                    ));
+                }
+            }
+
+            for (i, s) in series.iter().enumerate() {
+                let heights = s.histogram.heights(self.normalization);
+                for (bin, height) in s.histogram.bins.iter().zip(heights) {
+                    let (lower, upper) = self.layout.bar_span(
+                        scale.forward(bin.lower),
+                        scale.forward(bin.upper),
+                        i,
+                        series.len(),
+                    );
+                    let (lower, upper) = (to_x(lower), to_x(upper));
+                    let y = to_y(height);
+                    shapes.push(Shape::Rect {
+                        x: lower,
+                        y,
+                        w: (upper - lower).max(0.5),
+                        h: bottom - y,
+                        color: bar_color(i),
+                    });
+                }
+            }
+        }
+
+        shapes.push(Shape::line(left, bottom, right, bottom, AXIS_COLOR, 1.0));
+        shapes.push(Shape::line(left, top, left, bottom, AXIS_COLOR, 1.0));
+
+        let mut legend = Vec::new();
+        for (i, s) in series.iter().enumerate() {
+            let quantiles = &s.histogram.quantiles;
+            let colors = if series.len() == 1 {
+                self.quantile_colors
+            } else {
+                std::slice::from_ref(&self.bar_colors[i % self.bar_colors.len().max(1)])
+            };
+            for (q, &color) in quantiles.iter().zip(colors.iter().cycle()) {
+                let x = to_x(scale.forward(q.value));
+                if (left..=right).contains(&x) {
+                    let dashed = series.len() > 1 && q.p != 0.5;
+                    shapes.push(Shape::Line {
+                        from: (x, top),
+                        to: (x, bottom),
+                        color,
+                        width: 2.0,
+                        dashed,
+                    });
+                }
+                if series.len() == 1 {
+                    legend.push((
+                        format!("{} ptile: {:.4}", q.percent(), q.value),
+                        LegendMark::Line(color),
+                    ));
+                }
+            }
+            if series.len() > 1 {
+                let percentiles = quantiles
+                    .iter()
+                    .map(|q| format!("p{}: {:.4}", q.percent(), q.value))
+                    .collect::<Vec<_>>();
+                let label = if percentiles.is_empty() {
+                    s.name.clone()
+                } else {
+                    format!("{} ({})", s.name, percentiles.join(", "))
+                };
+                legend.push((label, LegendMark::Bar(bar_color(i))));
+            }
+        }
+        if !legend.is_empty() {
+            let text_width = legend
+                .iter()
+                .map(|(label, _)| text_width(label, LABEL_SIZE))
+                .fold(0.0, f32::max);
+            let (w, h) = (text_width + 40.0, legend.len() as f32 * 18.0 + 8.0);
+            let (x, y) = (right - w - 10.0, top + 10.0);
+            shapes.push(Shape::Rect {
+                x,
+                y,
+                w,
+                h,
+                color: BACKGROUND_COLOR,
+            });
+            for (i, (label, mark)) in legend.into_iter().enumerate() {
+                let row = y + 4.0 + i as f32 * 18.0 + 9.0;
+                shapes.push(match mark {
+                    LegendMark::Line(color) => Shape::line(x + 6.0, row, x + 26.0, row, color, 2.0),
+                    LegendMark::Bar(color) => Shape::Rect {
+                        x: x + 6.0,
+                        y: row - 5.0,
+                        w: 20.0,
+                        h: 10.0,
+                        color,
+                    },
+                });
+                shapes.push(Shape::text(
+                    x + 32.0,
+                    row + LABEL_SIZE / 3.0,
+                    label,
+                    LABEL_SIZE,
+                    Anchor::Start,
+                ));
+            }
+        }
+
+        shapes.push(Shape::text(
+            WIDTH / 2.0,
+            MARGIN_TOP / 2.0 + TITLE_SIZE / 3.0,
+            self.title.to_owned(),
+            TITLE_SIZE,
+            Anchor::Middle,
+        ));
+        let non_positive = series
+            .iter()
+            .map(|s| s.histogram.non_positive)
+            .sum::<usize>();
+        if non_positive > 0 {
+            shapes.push(Shape::text(
+                left,
+                HEIGHT - 8.0,
+                format!(
+                    "{} zero or negative values not shown on the log scale",
+                    non_positive
+                ),
+                LABEL_SIZE,
+                Anchor::Start,
+            ));
+        }
+        shapes
+    }
+
+    /// Draws the height ticks along the bottom, only whole ones if `whole`, and one horizontal
+    /// bar per category from the top down with its label on the left.
+    fn push_horizontal_bars(&self, shapes: &mut Vec<Shape>, left: f32, x_max: f64, whole: bool) {
+        let series = self.series;
+        let bins = &series[0].histogram.bins;
+        let (right, top, bottom) = (WIDTH - MARGIN_RIGHT, MARGIN_TOP, HEIGHT - MARGIN_BOTTOM);
+        let to_x = |height: f64| left + (height / x_max) as f32 * (right - left);
+        // Bin `i` spans the positions `[i - 0.5, i + 0.5]`.
+        let to_y = |pos: f64| top + ((pos + 0.5) / bins.len() as f64) as f32 * (bottom - top);
+
+        for tick in nice_ticks(0.0, x_max, 8)
+            .into_iter()
+            .filter(|tick| !whole || tick.fract() == 0.0)
+        {
+            let x = to_x(tick);
+            shapes.push(Shape::line(x, top, x, bottom, GRID_COLOR, 1.0));
+            shapes.push(Shape::text(
+                x,
+                bottom + 18.0,
+                format_number(tick),
+                LABEL_SIZE,
+                Anchor::Middle,
+            ));
+        }
+        let row_height = (bottom - top) / bins.len() as f32;
+        let step = (LABEL_SIZE / row_height).ceil().max(1.0) as usize;
+        for bin in bins.iter().step_by(step) {
+            shapes.push(Shape::text(
+                left - 8.0,
+                to_y(bin.mid()) + LABEL_SIZE / 3.0,
+                shorten(&bin.label, MAX_TEXT_LABEL),
+                LABEL_SIZE,
+                Anchor::End,
+            ));
+        }
+        for (i, s) in series.iter().enumerate() {
+            let color = self.layout.bar_color(
+                self.bar_colors[i % self.bar_colors.len().max(1)],
+                series.len(),
+            );
+            let heights = s.histogram.heights(self.normalization);
+            for (bin, height) in s.histogram.bins.iter().zip(heights) {
+                let (lower, upper) = self.layout.bar_span(bin.lower, bin.upper, i, series.len());
+                let (y, y_end) = (to_y(lower), to_y(upper));
+                shapes.push(Shape::Rect {
+                    x: left,
+                    y,
+                    w: to_x(height) - left,
+                    h: (y_end - y).max(0.5),
+                    color,
+                });
+            }
+        }
+    }
+}
+
+/// What a legend entry is drawn with.
+enum LegendMark {
+    Line(Color32),
+    Bar(Color32),
+}
+
+/// Draws `hist` as horizontal bars of Unicode blocks, one bin per line, fitting in `width`
+/// columns. Each line shows the bin label, its count, or its height under `normalization`, and
+/// its percentage of the total, the error of approximate counts, and marks the bins that contain
+/// the percentiles.
+pub fn text(hist: &Histogram, title: &str, width: usize, normalization: Normalization) -> String {
+    let labels = hist
+        .bins
+        .iter()
+        .map(|bin| shorten(&bin.label, MAX_TEXT_LABEL))
+        .collect::<Vec<_>>();
+    let markers = hist
+        .bins
+        .iter()
+        .enumerate()
+        .map(|(i, bin)| {
+            let last = i + 1 == hist.bins.len();
+            hist.quantiles
+                .iter()
+                .filter(|q| {
+                    bin.lower <= q.value && (q.value < bin.upper || last && q.value <= bin.upper)
+                })
+                .map(|q| format!("p{}", q.percent()))
+                .collect::<Vec<_>>()
+        })
+        .zip(&hist.bins)
+        .map(|(names, bin)| {
+            let mut marker = if bin.error > 0 {
+                format!(" ±{}", bin.error)
+            } else {
+                String::new()
+            };
+            if !names.is_empty() {
+                let _ = write!(marker, " ◀ {}", names.join(" "));
+            }
+            marker
+        })
+        .collect::<Vec<_>>();
+
+    let label_width = labels.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+    let heights = hist.heights(normalization);
+    let max_height = heights.iter().copied().fold(0.0, f64::max);
+    let values = hist
+        .bins
+        .iter()
+        .zip(&heights)
+        .map(|(bin, &height)| match normalization {
+            Normalization::Count => bin.count.to_string(),
+            _ => format_number(height),
+        })
+        .collect::<Vec<_>>();
+    let count_width = values.iter().map(|v| v.len()).max().unwrap_or(0);
+    let marker_width = markers.iter().map(|m| m.chars().count()).max().unwrap_or(0);
+    // label, " │", bar, " ", count, " ", "100.0%", markers
+    let fixed = label_width + 2 + 1 + count_width + 1 + 6 + marker_width;
+    let bar_width = width.saturating_sub(fixed).max(10);
+
+    let mut out = String::new();
+    if !title.is_empty() {
+        out.push_str(title);
+        out.push('\n');
+    }
+    for (((bin, label), marker), (height, value)) in hist
+        .bins
+        .iter()
+        .zip(&labels)
+        .zip(&markers)
+        .zip(heights.iter().zip(&values))
+    {
+        let fraction = if max_height > 0.0 {
+            height / max_height
+        } else {
+            0.0
+        };
+        let percent = if hist.total > 0 {
+            100.0 * bin.count as f64 / hist.total as f64
+        } else {
+            0.0
+        };
+        let _ = writeln!(
+            out,
+            "{:>lw$} │{:<bw$} {:>cw$} {:>5.1}%{}",
+            label,
+            bar(fraction, bar_width),
+            value,
+            percent,
+            marker,
+            lw = label_width,
+            bw = bar_width,
+            cw = count_width,
+        );
+    }
+    let _ = write!(out, "Total Points: {}", hist.total);
+    if let Some(distinct) = &hist.distinct {
+        let _ = write!(out, " | {}", distinct);
+    }
+    for q in &hist.quantiles {
+        let _ = write!(out, " | p{}: {:.4}", q.percent(), q.value);
+    }
+    out.push('\n');
+    if let Some(stats) = &hist.stats {
+        let _ = write!(
+            out,
+            "mean: {:.4} | std dev: {:.4} | skewness: {:.4} | kurtosis: {:.4}",
+            stats.mean, stats.std_dev, stats.skewness, stats.kurtosis
+        );
+        if let Some(mad) = stats.mad {
+            let _ = write!(out, " | MAD: {:.4}", mad);
+        }
+        out.push('\n');
+    }
+    if hist.rejected.total() > 0 {
+        let _ = writeln!(out, "{}", hist.rejected);
+    }
+    if hist.non_positive > 0 {
+        let _ = writeln!(
+            out,
+            "{} zero or negative values not shown on the log scale",
+            hist.non_positive
+        );
+    }
+    out
+}
+
+/// A bar `fraction` of `width` cells long, in eighths of a cell.
+fn bar(fraction: f64, width: usize) -> String {
+    let eighths = (fraction.clamp(0.0, 1.0) * (width * 8) as f64).round() as usize;
+    // Keep non-empty bins visible.
+    let eighths = if fraction > 0.0 { eighths.max(1) } else { 0 };
+    let mut bar = BLOCKS[7].to_string().repeat(eighths / 8);
+    if eighths % 8 > 0 {
+        bar.push(BLOCKS[eighths % 8 - 1]);
+    }
+    bar
+}
+
+fn shorten(label: &str, max: usize) -> String {
+    if label.chars().count() <= max {
+        label.to_owned()
+    } else {
+        let mut short = label.chars().take(max - 1).collect::<String>();
+        short.push('…');
+        short
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Anchor {
+    Start,
+    Middle,
+    End,
+}
+
+#[derive(Clone, Debug)]
+enum Shape {
+    Rect {
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+        color: Color32,
+    },
+    Line {
+        from: (f32, f32),
+        to: (f32, f32),
+        color: Color32,
+        width: f32,
+        dashed: bool,
+    },
+    /// Text whose baseline starts, is centred or ends at `(x, y)`.
+    Text {
+        x: f32,
+        y: f32,
+        text: String,
+        size: f32,
+        anchor: Anchor,
+    },
+}
+
+impl Shape {
+    fn line(x1: f32, y1: f32, x2: f32, y2: f32, color: Color32, width: f32) -> Self {
+        Shape::Line {
+            from: (x1, y1),
+            to: (x2, y2),
+            color,
+            width,
+            dashed: false,
+        }
+    }
+
+    fn text(x: f32, y: f32, text: String, size: f32, anchor: Anchor) -> Self {
+        Shape::Text {
+            x,
+            y,
+            text,
+            size,
+            anchor,
+        }
+    }
+}
+
+/// Roughly `target` evenly spaced round numbers within `[lo, hi]`.
+fn nice_ticks(lo: f64, hi: f64, target: usize) -> Vec<f64> {
+    if !(hi - lo).is_finite() || hi <= lo {
+        return vec![lo];
+    }
+    let raw = (hi - lo) / target as f64;
+    let magnitude = 10f64.powf(raw.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0]
+        .iter()
+        .map(|m| m * magnitude)
+        .find(|&step| step >= raw)
+        .unwrap_or(10.0 * magnitude);
+    let first = (lo / step).ceil() as i64;
+    let last = (hi / step + 1e-9).floor() as i64;
+    (first..=last).map(|i| i as f64 * step).collect()
+}
+
+fn format_number(x: f64) -> String {
+    if x == 0.0 {
+        return "0".to_owned();
+    }
+    if x.abs() >= 1e6 || x.abs() < 1e-3 {
+        return format!("{:.2e}", x);
+    }
+    let s = format!("{:.4}", x);
+    s.trim_end_matches('0').trim_end_matches('.').to_owned()
+}
+
+/// Label of the tick at `t` on an axis drawn in the space of `scale`, where ticks are `step`
+/// apart: the value, with as many decimals as tell it apart from the next tick.
+pub fn tick_label(scale: Scale, t: f64, step: f64) -> String {
+    let value = scale.inverse(t);
+    if value == 0.0 || value.abs() >= 1e6 || value.abs() < 1e-3 {
+        return format_number(value);
+    }
+    // On a log scale, the ticks are further apart in value the larger it is.
+    let spacing = (scale.inverse(t + step) - value).abs();
+    let decimals = (-spacing.log10().floor()).clamp(0.0, 6.0) as usize;
+    format!("{:.*}", decimals, value)
+}
+
+fn font() -> FontRef<'static> {
+    FontRef::try_from_slice(epaint_default_fonts::UBUNTU_LIGHT).expect("built-in font is valid")
+}
+
+fn text_width(text: &str, size: f32) -> f32 {
+    let font = font();
+    let font = font.as_scaled(PxScale::from(size));
+    text.chars().map(|c| font.h_advance(font.glyph_id(c))).sum()
+}
+
+/// SVG attributes painting `attr` (`fill` or `stroke`) with the colour, and its opacity if it
+/// is translucent.
+fn paint_attrs(attr: &str, color: Color32) -> String {
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+    let mut attrs = format!("{}=\"#{:02x}{:02x}{:02x}\"", attr, r, g, b);
+    if a < 255 {
+        let _ = write!(attrs, " {}-opacity=\"{:.3}\"", attr, a as f32 / 255.0);
+    }
+    attrs
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn svg(shapes: &[Shape]) -> String {
+    let mut out = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" \
+         viewBox=\"0 0 {w} {h}\" font-family=\"Ubuntu, sans-serif\">\n",
+        w = WIDTH,
+        h = HEIGHT
+    );
+    for shape in shapes {
+        // Writing to a String cannot fail.
+        let _ = match shape {
+            Shape::Rect { x, y, w, h, color } => writeln!(
+                out,
+                "<rect x=\"{:.2}\" y=\"{:.2}\" width=\"{:.2}\" height=\"{:.2}\" {}/>",
+                x,
+                y,
+                w,
+                h,
+                paint_attrs("fill", *color)
+            ),
+            Shape::Line {
+                from,
+                to,
+                color,
+                width,
+                dashed,
+            } => writeln!(
+                out,
+                "<line x1=\"{:.2}\" y1=\"{:.2}\" x2=\"{:.2}\" y2=\"{:.2}\" {} \
+                 stroke-width=\"{}\"{}/>",
+                from.0,
+                from.1,
+                to.0,
+                to.1,
+                paint_attrs("stroke", *color),
+                width,
+                if *dashed {
+                    " stroke-dasharray=\"6 4\""
+                } else {
+                    ""
+                }
+            ),
+            Shape::Text {
+                x,
+                y,
+                text,
+                size,
+                anchor,
+            } => writeln!(
+                out,
+                "<text x=\"{:.2}\" y=\"{:.2}\" font-size=\"{}\" text-anchor=\"{}\" {}>{}</text>",
+                x,
+                y,
+                size,
+                match anchor {
+                    Anchor::Start => "start",
+                    Anchor::Middle => "middle",
+                    Anchor::End => "end",
+                },
+                paint_attrs("fill", TEXT_COLOR),
+                escape_xml(text)
+            ),
+        };
+    }
+    out.push_str("</svg>\n");
+    out
+}
+
+fn png(shapes: &[Shape]) -> Result<Vec<u8>, RenderError> {
+    use tiny_skia::{Paint, PathBuilder, Pixmap, Rect, Stroke, StrokeDash, Transform};
+
+    let mut pixmap = Pixmap::new(WIDTH as u32, HEIGHT as u32).expect("image size is not zero");
+    let paint = |color: Color32| {
+        let [r, g, b, a] = color.to_srgba_unmultiplied();
+        let mut paint = Paint::default();
+        paint.set_color_rgba8(r, g, b, a);
+        paint.anti_alias = true;
+        paint
+    };
+    for shape in shapes {
+        match shape {
+            Shape::Rect { x, y, w, h, color } => {
+                if let Some(rect) = Rect::from_xywh(*x, *y, *w, *h) {
+                    pixmap.fill_rect(rect, &paint(*color), Transform::identity(), None);
+                }
+            }
+            Shape::Line {
+                from,
+                to,
+                color,
+                width,
+                dashed,
+            } => {
+                let mut path = PathBuilder::new();
+                path.move_to(from.0, from.1);
+                path.line_to(to.0, to.1);
+                if let Some(path) = path.finish() {
+                    let stroke = Stroke {
+                        width: *width,
+                        dash: if *dashed {
+                            StrokeDash::new(vec![6.0, 4.0], 0.0)
+                        } else {
+                            None
+                        },
+                        ..Stroke::default()
+                    };
+                    pixmap.stroke_path(&path, &paint(*color), &stroke, Transform::identity(), None);
+                }
+            }
+            Shape::Text {
+                x,
+                y,
+                text,
+                size,
+                anchor,
+            } => draw_text(&mut pixmap, *x, *y, text, *size, *anchor),
+        }
+    }
+    pixmap
+        .encode_png()
+        .map_err(|e| RenderError::Png(e.to_string()))
+}
+
+/// Rasterises `text` onto an opaque pixmap in [`TEXT_COLOR`].
+fn draw_text(
+    pixmap: &mut tiny_skia::Pixmap,
+    x: f32,
+    y: f32,
+    text: &str,
+    size: f32,
+    anchor: Anchor,
+) {
+    let font = font();
+    let scaled = font.as_scaled(PxScale::from(size));
+    let width = text_width(text, size);
+    let mut caret = match anchor {
+        Anchor::Start => x,
+        Anchor::Middle => x - width / 2.0,
+        Anchor::End => x - width,
+    };
+    let (pixmap_width, pixmap_height) = (pixmap.width() as i32, pixmap.height() as i32);
+    let pixels = pixmap.pixels_mut();
+    for c in text.chars() {
+        let glyph = scaled.scaled_glyph(c);
+        let advance = scaled.h_advance(glyph.id);
+        let glyph = glyph.id.with_scale_and_position(size, point(caret, y));
+        caret += advance;
+        let outlined = match font.outline_glyph(glyph) {
+            Some(outlined) => outlined,
+            None => continue,
+        };
+        let bounds = outlined.px_bounds();
+        outlined.draw(|gx, gy, coverage| {
+            let px = bounds.min.x as i32 + gx as i32;
+            let py = bounds.min.y as i32 + gy as i32;
+            if px < 0 || py < 0 || px >= pixmap_width || py >= pixmap_height {
+                return;
+            }
+            let pixel = &mut pixels[(py * pixmap_width + px) as usize];
+            let blend = |dst: u8, src: u8| {
+                (src as f32 * coverage + dst as f32 * (1.0 - coverage)).round() as u8
+            };
+            if let Some(blended) = tiny_skia::PremultipliedColorU8::from_rgba(
+                blend(pixel.red(), TEXT_COLOR.r()),
+                blend(pixel.green(), TEXT_COLOR.g()),
+                blend(pixel.blue(), TEXT_COLOR.b()),
+                255,
+            ) {
+                *pixel = blended;
+            }
+        });
+    }
+}
@@ -0,0 +1,300 @@
+//! Machine-readable summaries of histograms, for scripts and for diffing between runs.
+
+use crate::data::{Distinct, Histogram, Rejected, Scale, Series, Stats};
+use serde::Serialize;
+use std::fmt::Write as _;
+
+#[derive(Serialize)]
+struct Report<'a> {
+    total: usize,
+    /// `[min, max]` of the values, or `null` for categorical histograms.
+    range: Option<(f64, f64)>,
+    /// Base of the logarithm the bins are evenly spaced in, or `null` for linear bins.
+    log_base: Option<f64>,
+    non_positive: usize,
+    /// Descriptive statistics, or `null` for categorical histograms.
+    stats: Option<&'a Stats>,
+    /// Values left out because they are not finite numbers, by reason.
+    rejected: &'a Rejected,
+    /// Number of distinct categories, omitted for numeric histograms.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    distinct: Option<Distinct>,
+    percentiles: Vec<Percentile>,
+    bins: Vec<BinRow<'a>>,
+}
+
+#[derive(Serialize)]
+struct SeriesReport<'a> {
+    name: &'a str,
+    #[serde(flatten)]
+    report: Report<'a>,
+}
+
+#[derive(Serialize)]
+struct SeriesReports<'a> {
+    series: Vec<SeriesReport<'a>>,
+}
+
+#[derive(Serialize)]
+struct Percentile {
+    /// In percent, e.g. 25 for the lower quartile.
+    p: f64,
+    value: f64,
+}
+
+#[derive(Serialize)]
+struct BinRow<'a> {
+    label: &'a str,
+    lower: f64,
+    upper: f64,
+    count: usize,
+    /// How far an approximate count may be off, omitted when the count is exact.
+    #[serde(skip_serializing_if = "is_zero")]
+    error: usize,
+    cumulative: usize,
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+fn bin_rows(hist: &Histogram) -> Vec<BinRow<'_>> {
+    let mut cumulative = 0;
+    hist.bins
+        .iter()
+        .map(|bin| {
+            cumulative += bin.count;
+            BinRow {
+                label: &bin.label,
+                lower: bin.lower,
+                upper: bin.upper,
+                count: bin.count,
+                error: bin.error,
+                cumulative,
+            }
+        })
+        .collect()
+}
+
+fn report(hist: &Histogram) -> Report<'_> {
+    Report {
+        total: hist.total,
+        range: hist.range,
+        log_base: match hist.scale {
+            Scale::Linear => None,
+            Scale::Log(base) => Some(base),
+        },
+        non_positive: hist.non_positive,
+        stats: hist.stats.as_ref(),
+        rejected: &hist.rejected,
+        distinct: hist.distinct,
+        percentiles: hist
+            .quantiles
+            .iter()
+            .map(|q| Percentile {
+                p: q.percent(),
+                value: q.value,
+            })
+            .collect(),
+        bins: bin_rows(hist),
+    }
+}
+
+fn pretty_json<T: Serialize>(value: &T) -> String {
+    serde_json::to_string_pretty(value).expect("a histogram report is always valid JSON") + "\n"
+}
+
+/// The bins with their edges, counts and cumulative counts, together with the percentiles,
+/// statistics, total and range, as pretty-printed JSON.
+pub fn to_json(hist: &Histogram) -> String {
+    pretty_json(&report(hist))
+}
+
+/// Like [`to_json`] for several series, as `{"series": [...]}` with a `name` for each.
+pub fn series_to_json(series: &[Series]) -> String {
+    pretty_json(&SeriesReports {
+        series: series
+            .iter()
+            .map(|s| SeriesReport {
+                name: &s.name,
+                report: report(&s.histogram),
+            })
+            .collect(),
+    })
+}
+
+/// Comment rows before the header with the total, the range and the percentiles, e.g.
+/// `# total=1000`, `# range=0.5,99.5` and `# p50=42`, each after `prefix`.
+fn summary_rows(out: &mut String, prefix: &str, hist: &Histogram) {
+    // Writing to a String cannot fail.
+    let _ = writeln!(out, "# {}total={}", prefix, hist.total);
+    if let Some((min, max)) = hist.range {
+        let _ = writeln!(out, "# {}range={},{}", prefix, min, max);
+    }
+    for q in &hist.quantiles {
+        let _ = writeln!(out, "# {}p{}={}", prefix, q.percent(), q.value);
+    }
+}
+
+/// One row per bin with its edges, count, cumulative count and label, after comment rows with
+/// the total, range and percentiles. Categorical bins have unit-wide edges around their
+/// position.
+pub fn to_csv(hist: &Histogram) -> String {
+    let mut out = String::new();
+    summary_rows(&mut out, "", hist);
+    out.push_str("lower,upper,count,cumulative,label\n");
+    for row in bin_rows(hist) {
+        let _ = writeln!(
+            out,
+            "{},{},{},{},{}",
+            row.lower,
+            row.upper,
+            row.count,
+            row.cumulative,
+            csv_field(row.label)
+        );
+    }
+    out
+}
+
+/// Like [`to_csv`] for several series, with the series name in an extra first column and
+/// before the comment rows of each series, e.g. `# a.txt: total=1000`.
+pub fn series_to_csv(series: &[Series]) -> String {
+    let mut out = String::new();
+    for s in series {
+        summary_rows(&mut out, &format!("{}: ", s.name), &s.histogram);
+    }
+    out.push_str("series,lower,upper,count,cumulative,label\n");
+    for s in series {
+        for row in bin_rows(&s.histogram) {
+            let _ = writeln!(
+                out,
+                "{},{},{},{},{},{}",
+                csv_field(&s.name),
+                row.lower,
+                row.upper,
+                row.count,
+                row.cumulative,
+                csv_field(row.label)
+            );
+        }
+    }
+    out
+}
+
+/// Quotes a CSV field if it contains a delimiter, quote or line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{
+        histogram_from_categories, histogram_from_numbers, histograms_from_groups, BinRule,
+        HistogramOptions,
+    };
+    use serde_json::{json, Value};
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|&v| v.to_owned()).collect()
+    }
+
+    fn options() -> HistogramOptions {
+        HistogramOptions {
+            rule: BinRule::Count(2),
+            quantiles: vec![0.5],
+            ..HistogramOptions::default()
+        }
+    }
+
+    fn numbers() -> Histogram {
+        histogram_from_numbers(&strings(&["1", "2", "3", "4", "x"]), &options()).unwrap()
+    }
+
+    #[test]
+    fn csv_with_the_summary_rows() {
+        assert_eq!(
+            to_csv(&numbers()),
+            "# total=4\n\
+             # range=1,4\n\
+             # p50=2.5\n\
+             lower,upper,count,cumulative,label\n\
+             1,2.5,2,2,1.0000 - 2.5000\n\
+             2.5,4,2,4,2.5000 - 4.0000\n"
+        );
+    }
+
+    #[test]
+    fn csv_of_categories_quotes_the_labels() {
+        let hist =
+            histogram_from_categories(&strings(&["a,b", "a", "a,b", "say \"hi\""]), &options())
+                .unwrap();
+        assert_eq!(
+            to_csv(&hist),
+            "# total=4\n\
+             lower,upper,count,cumulative,label\n\
+             -0.5,0.5,1,1,a\n\
+             0.5,1.5,1,2,\"say \"\"hi\"\"\"\n\
+             1.5,2.5,2,4,\"a,b\"\n"
+        );
+    }
+
+    #[test]
+    fn json_report() {
+        let report: Value = serde_json::from_str(&to_json(&numbers())).unwrap();
+        assert_eq!(report["total"], 4);
+        assert_eq!(report["range"], json!([1.0, 4.0]));
+        assert_eq!(report["log_base"], Value::Null);
+        assert_eq!(report["rejected"]["unparsed"], 1);
+        assert_eq!(report["percentiles"], json!([{ "p": 50.0, "value": 2.5 }]));
+        assert_eq!(report["stats"]["mean"], 2.5);
+        assert!(report.get("distinct").is_none());
+        assert_eq!(
+            report["bins"][1],
+            json!({
+                "label": "2.5000 - 4.0000",
+                "lower": 2.5,
+                "upper": 4.0,
+                "count": 2,
+                "cumulative": 4
+            })
+        );
+    }
+
+    #[test]
+    fn several_series() {
+        let groups = [
+            ("a.txt".to_owned(), strings(&["1", "2"])),
+            ("b, c".to_owned(), strings(&["4"])),
+        ];
+        let series = histograms_from_groups(&groups, &options()).unwrap();
+        let csv = series_to_csv(&series);
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[..7],
+            [
+                "# a.txt: total=2",
+                "# a.txt: range=1,2",
+                "# a.txt: p50=1.5",
+                "# b, c: total=1",
+                "# b, c: range=4,4",
+                "# b, c: p50=4",
+                "series,lower,upper,count,cumulative,label"
+            ]
+        );
+        assert!(lines[7].starts_with("a.txt,1,"));
+        assert!(lines[lines.len() - 1].starts_with("\"b, c\","));
+
+        let report: Value = serde_json::from_str(&series_to_json(&series)).unwrap();
+        let names = report["series"].as_array().unwrap().iter();
+        let names = names
+            .map(|s| (s["name"].as_str().unwrap(), s["total"].as_u64().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(names, [("a.txt", 2), ("b, c", 1)]);
+    }
+}